@@ -0,0 +1,426 @@
+//! Cipher descriptions as systems of Boolean polynomials, and the ElimLin algorithm.
+//!
+//! Every bit named like the compiler names it becomes a variable, and every operation one
+//! polynomial per output bit which vanishes on the correct values: XORs, copies, permutations and
+//! matrices give linear polynomials, and S-boxes the ANF of their outputs. ElimLin alternates
+//! between substituting linear polynomials and Gaussian elimination over the linearized system,
+//! with monomials of higher degree eliminated first, until no new linear polynomial appears.
+use super::compiler::{bit_name, monomials, unroll, CompileError, Layout};
+use super::constant::Constant;
+use super::description::{Description, Opcode, Operation};
+use super::leakage::Leakage;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::{Add, Mul};
+
+/// A Boolean polynomial as its monomials, each the increasing indices of its variables. They are
+/// kept by decreasing degree then in lexicographic order, so the constant monomial comes last.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polynomial {
+    monomials: Vec<Vec<u32>>,
+}
+
+fn order(a: &[u32], b: &[u32]) -> Ordering {
+    b.len().cmp(&a.len()).then_with(|| a.cmp(b))
+}
+
+impl Polynomial {
+    pub fn zero() -> Self {
+        Polynomial { monomials: vec![] }
+    }
+
+    pub fn one() -> Self {
+        Polynomial {
+            monomials: vec![vec![]],
+        }
+    }
+
+    pub fn variable(var: u32) -> Self {
+        Polynomial {
+            monomials: vec![vec![var]],
+        }
+    }
+
+    /// Collect monomials, those occurring twice cancelling.
+    pub fn from_monomials(mut monomials: Vec<Vec<u32>>) -> Self {
+        for monomial in monomials.iter_mut() {
+            monomial.sort_unstable();
+            monomial.dedup();
+        }
+        monomials.sort_by(|a, b| order(a, b));
+        let mut kept: Vec<Vec<u32>> = vec![];
+        for monomial in monomials.into_iter() {
+            if kept.last() == Some(&monomial) {
+                kept.pop();
+            } else {
+                kept.push(monomial);
+            }
+        }
+        Polynomial { monomials: kept }
+    }
+
+    pub fn monomials(&self) -> &[Vec<u32>] {
+        &self.monomials
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.monomials.is_empty()
+    }
+
+    /// Degree of the polynomial, 0 for the zero polynomial.
+    pub fn degree(&self) -> usize {
+        self.monomials.first().map_or(0, |m| m.len())
+    }
+
+    pub fn contains(&self, var: u32) -> bool {
+        self.monomials
+            .iter()
+            .any(|monomial| monomial.binary_search(&var).is_ok())
+    }
+
+    /// The polynomial with `var` replaced by `value`.
+    pub fn substitute(&self, var: u32, value: &Polynomial) -> Polynomial {
+        let (with, without): (Vec<&Vec<u32>>, Vec<&Vec<u32>>) = self
+            .monomials
+            .iter()
+            .partition(|monomial| monomial.binary_search(&var).is_ok());
+        let cofactor = Polynomial::from_monomials(
+            with.into_iter()
+                .map(|monomial| monomial.iter().cloned().filter(|&v| v != var).collect())
+                .collect(),
+        );
+        let rest = Polynomial {
+            monomials: without.into_iter().cloned().collect(),
+        };
+        &rest + &(&cofactor * value)
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: &Polynomial) -> Polynomial {
+        let mut sum = Vec::with_capacity(self.monomials.len() + other.monomials.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.monomials.len() && j < other.monomials.len() {
+            match order(&self.monomials[i], &other.monomials[j]) {
+                Ordering::Less => {
+                    sum.push(self.monomials[i].clone());
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    sum.push(other.monomials[j].clone());
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        sum.extend_from_slice(&self.monomials[i..]);
+        sum.extend_from_slice(&other.monomials[j..]);
+        Polynomial { monomials: sum }
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        let mut products = vec![];
+        for a in self.monomials.iter() {
+            for b in other.monomials.iter() {
+                let mut product = a.clone();
+                product.extend_from_slice(b);
+                products.push(product);
+            }
+        }
+        Polynomial::from_monomials(products)
+    }
+}
+
+/// The polynomials of one or more descriptions over shared constants, each equal to zero.
+pub struct AnfSystem {
+    layout: Layout,
+    names: Vec<String>,
+    variables: HashMap<String, u32>,
+    pub polynomials: Vec<Polynomial>,
+    /// Auxiliary variables of the leakages added so far.
+    aux: usize,
+}
+
+impl AnfSystem {
+    pub fn new(constants: Vec<Constant>) -> Self {
+        AnfSystem {
+            layout: Layout::new(constants),
+            names: vec![],
+            variables: HashMap::new(),
+            polynomials: vec![],
+            aux: 0,
+        }
+    }
+
+    /// Index of a variable, added on first use.
+    pub fn variable(&mut self, name: &str) -> u32 {
+        if let Some(&var) = self.variables.get(name) {
+            return var;
+        }
+        let var = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.variables.insert(name.to_string(), var);
+        var
+    }
+
+    pub fn name(&self, var: u32) -> &str {
+        &self.names[var as usize]
+    }
+
+    /// Unroll the rounds of `desc` and add their polynomials.
+    pub fn add(&mut self, desc: &Description) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            self.add_operation(*round, op)?;
+        }
+        Ok(())
+    }
+
+    /// Fix the bits of a word, such as a known plaintext.
+    pub fn fix(&mut self, name: &str, round: i32, index: i32, bits: &[bool]) {
+        for (i, &bit) in bits.iter().enumerate() {
+            let var = self.variable(&bit_name(name, round, index, i as u32 + 1));
+            self.add_linear(&[var], bit);
+        }
+    }
+
+    /// Add the clauses of a leakage, whose bits are named like the bits of the description. An
+    /// OR clause holds when the product of its negated literals vanishes. Soft clauses are left
+    /// out.
+    pub fn leak(&mut self, mut leakage: Leakage) {
+        self.aux = leakage.renumber(self.aux);
+        for clause in leakage.into_clauses() {
+            let lits: Vec<(u32, bool)> = clause
+                .literals()
+                .into_iter()
+                .map(|(name, value)| (self.variable(name), value))
+                .collect();
+            if clause.is_xor() {
+                let vars: Vec<u32> = lits.iter().map(|&(var, _)| var).collect();
+                let rhs = lits.iter().fold(true, |acc, &(_, value)| acc ^ !value);
+                self.add_linear(&vars, rhs);
+                continue;
+            }
+            let mut product = Polynomial::one();
+            for &(var, value) in lits.iter() {
+                let mut negated = Polynomial::variable(var);
+                if value {
+                    negated = &negated + &Polynomial::one();
+                }
+                product = &product * &negated;
+            }
+            self.polynomials.push(product);
+        }
+    }
+
+    /// The system as text, one polynomial per line such as `x*y + z + 1`.
+    pub fn to_anf(&self) -> String {
+        let mut anf = String::new();
+        for polynomial in self.polynomials.iter() {
+            let terms: Vec<String> = polynomial
+                .monomials
+                .iter()
+                .map(|monomial| match monomial.len() {
+                    0 => "1".to_string(),
+                    _ => {
+                        let names: Vec<&str> = monomial.iter().map(|&v| self.name(v)).collect();
+                        names.join("*")
+                    }
+                })
+                .collect();
+            if terms.is_empty() {
+                anf.push('0');
+            }
+            anf.push_str(&terms.join(" + "));
+            anf.push('\n');
+        }
+        anf
+    }
+
+    /// Run ElimLin, leaving the polynomials which are still nonlinear in the system. Returns the
+    /// variables whose values it found, or `None` if the system is inconsistent.
+    pub fn elimlin(&mut self) -> Option<HashMap<String, bool>> {
+        let mut substitutions: Vec<(u32, Polynomial)> = vec![];
+        loop {
+            self.substitute_linear(&mut substitutions)?;
+            if !self.linearize()? {
+                break;
+            }
+        }
+
+        let mut values: HashMap<u32, bool> = HashMap::new();
+        for (var, value) in substitutions.iter().rev() {
+            let mut known = Some(false);
+            for monomial in value.monomials.iter() {
+                known = match monomial[..] {
+                    [] => known.map(|k| !k),
+                    [v] => match (known, values.get(&v)) {
+                        (Some(k), Some(&b)) => Some(k ^ b),
+                        _ => None,
+                    },
+                    _ => unreachable!(),
+                };
+            }
+            if let Some(value) = known {
+                values.insert(*var, value);
+            }
+        }
+        Some(
+            values
+                .into_iter()
+                .map(|(var, value)| (self.names[var as usize].clone(), value))
+                .collect(),
+        )
+    }
+
+    /// Substitute linear polynomials, fewest monomials first, until none is left. Returns `None`
+    /// if a polynomial became 1.
+    fn substitute_linear(&mut self, substitutions: &mut Vec<(u32, Polynomial)>) -> Option<()> {
+        let mut occurs: HashMap<u32, HashSet<usize>> = HashMap::new();
+        let mut linear = BinaryHeap::new();
+        for (i, polynomial) in self.polynomials.iter().enumerate() {
+            for &var in polynomial.monomials.iter().flatten() {
+                occurs.entry(var).or_default().insert(i);
+            }
+            if polynomial.degree() <= 1 {
+                linear.push(Reverse((polynomial.monomials.len(), i)));
+            }
+        }
+        while let Some(Reverse((len, i))) = linear.pop() {
+            let polynomial = &self.polynomials[i];
+            if polynomial.monomials.len() != len || polynomial.degree() > 1 {
+                continue;
+            }
+            let var = match polynomial.monomials.first() {
+                None => continue,
+                Some(monomial) if monomial.is_empty() => return None,
+                Some(monomial) => monomial[0],
+            };
+            let value = polynomial + &Polynomial::variable(var);
+            self.polynomials[i] = Polynomial::zero();
+            for j in occurs.remove(&var).unwrap_or_default().into_iter() {
+                if !self.polynomials[j].contains(var) {
+                    continue;
+                }
+                self.polynomials[j] = self.polynomials[j].substitute(var, &value);
+                for &other in value.monomials.iter().flatten() {
+                    occurs.entry(other).or_default().insert(j);
+                }
+                if self.polynomials[j].degree() <= 1 {
+                    linear.push(Reverse((self.polynomials[j].monomials.len(), j)));
+                }
+            }
+            substitutions.push((var, value));
+        }
+        self.polynomials.retain(|polynomial| !polynomial.is_zero());
+        Some(())
+    }
+
+    /// Bring the system to echelon form over its monomials, highest degree first. Returns
+    /// whether this gave linear polynomials, or `None` if it gave 1.
+    fn linearize(&mut self) -> Option<bool> {
+        let mut rows: Vec<Polynomial> = vec![];
+        let mut pivots: HashMap<Vec<u32>, usize> = HashMap::new();
+        for polynomial in self.polynomials.iter() {
+            let mut row = polynomial.clone();
+            while let Some(&i) = row.monomials.first().and_then(|m| pivots.get(m)) {
+                row = &row + &rows[i];
+            }
+            if let Some(leading) = row.monomials.first() {
+                pivots.insert(leading.clone(), rows.len());
+                rows.push(row);
+            }
+        }
+        if rows.iter().any(|row| row == &Polynomial::one()) {
+            return None;
+        }
+        let found = rows.iter().any(|row| row.degree() <= 1);
+        self.polynomials = rows;
+        Some(found)
+    }
+
+    fn add_linear(&mut self, vars: &[u32], rhs: bool) {
+        let mut monomials: Vec<Vec<u32>> = vars.iter().map(|&var| vec![var]).collect();
+        if rhs {
+            monomials.push(vec![]);
+        }
+        self.polynomials.push(Polynomial::from_monomials(monomials));
+    }
+
+    fn add_operation(&mut self, round: u32, op: &Operation) -> Result<(), CompileError> {
+        let mut operands: Vec<Vec<u32>> = vec![];
+        for var in op.operands.iter() {
+            let bits = self.layout.bits(var, round, op.line)?;
+            operands.push(bits.iter().map(|name| self.variable(name)).collect());
+        }
+        let output = operands.pop().unwrap();
+        match op.opcode {
+            Opcode::Xor | Opcode::Assign => {
+                for (i, &out) in output.iter().enumerate() {
+                    let mut vars: Vec<u32> = operands.iter().map(|bits| bits[i]).collect();
+                    vars.push(out);
+                    self.add_linear(&vars, false);
+                }
+            }
+            Opcode::Permutation(n) => {
+                let perm = self
+                    .layout
+                    .permutation(n, operands[0].len(), op.line)?
+                    .to_vec();
+                for (&p, &out) in perm.iter().zip(output.iter()) {
+                    self.add_linear(&[operands[0][p as usize], out], false);
+                }
+            }
+            Opcode::Matrix(n) => {
+                let rows = self.layout.matrix(&format!("m{}", n), op.line)?.to_vec();
+                for (row, &out) in rows.iter().zip(output.iter()) {
+                    let mut vars = vec![out];
+                    for &i in row.iter() {
+                        vars.push(*operands[0].get(i as usize).ok_or_else(|| {
+                            CompileError::new(op.line, &format!("m{} refers to bit {}", n, i))
+                        })?);
+                    }
+                    self.add_linear(&vars, false);
+                }
+            }
+            Opcode::Constant => {
+                let width = output.len();
+                let slice = self.layout.round_constant(round, width, op.line)?.to_vec();
+                for i in 0..width {
+                    self.add_linear(&[operands[1][i]], slice[i] != 0);
+                    self.add_linear(&[operands[0][i], operands[1][i], output[i]], false);
+                }
+            }
+            Opcode::Sbox => {
+                let mut offset = 0;
+                for name in op.params.iter() {
+                    let n = self.layout.sbox_size(name, op.line)?;
+                    let anf = self.layout.array(name, op.line)?.to_vec();
+                    let input = &operands[0][offset..offset + n];
+                    let terms = monomials(n);
+                    for (i, &out) in output[offset..offset + n].iter().enumerate() {
+                        let mut monomials = vec![vec![out]];
+                        for (term, &c) in terms.iter().zip(anf[i << n..(i + 1) << n].iter()) {
+                            if c != 0 {
+                                monomials.push(term.iter().map(|&t| input[t]).collect());
+                            }
+                        }
+                        self.polynomials.push(Polynomial::from_monomials(monomials));
+                    }
+                    offset += n;
+                }
+            }
+        }
+        Ok(())
+    }
+}
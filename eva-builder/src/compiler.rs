@@ -0,0 +1,876 @@
+//! Lowering of cipher descriptions into CNF+XOR instances.
+//!
+//! Every bit gets the name `NAME#ROUND#INDEX#BIT#`, with the absolute round and the bit starting
+//! at 1. A reference `NAME#-1#I#` is word `I` of the current round `r`, any other `NAME#W#K#` is
+//! word `W` of round `r - 1 + K`. Permutations and copies only rename bits, XORs and matrices
+//! become XOR clauses, and S-boxes are encoded from their ANF with one AND gate per monomial.
+use super::constant::Constant;
+use super::description::{Description, Opcode, Operation, Statement, VarRef};
+use super::generic::{Clause, Instance, NameMap};
+use super::leakage::{Fault, FaultModel, Leakage, LeakageSpec, LeakageType, Observation};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// An error in a description which parsed fine, at a 1-based line.
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Accumulates the clauses of one or more descriptions over shared constants.
+pub struct Compiler {
+    layout: Layout,
+    instance: Instance,
+    aliases: NameMap<String>,
+    gates: usize,
+}
+
+/// The constants of a cipher and the widths of its words.
+pub(crate) struct Layout {
+    constants: HashMap<String, Constant>,
+    pub(crate) widths: HashMap<(String, i32), u32>,
+}
+
+/// A compiled instance, with the mapping from bit names back to its variables.
+pub struct Compiled {
+    pub instance: Instance,
+    aliases: NameMap<String>,
+    widths: HashMap<(String, i32), u32>,
+}
+
+pub fn bit_name(name: &str, round: i32, index: i32, bit: u32) -> String {
+    format!("{}#{}#{}#{}#", name, round, index, bit)
+}
+
+/// Name of the copy of word `name` in the faulty run `run`, such as `A4'1`.
+pub fn faulty_name(name: &str, run: u32) -> String {
+    format!("{}'{}", name, run)
+}
+
+/// Name of word `name` in the encryption of pair `pair`, such as `P2_A4`.
+pub fn pair_name(name: &str, pair: u32) -> String {
+    format!("P{}_{}", pair, name)
+}
+
+/// One encryption under the shared key: its plaintext, its ciphertext if known, and leakage
+/// whose bits are named like those of the description.
+pub struct Pair {
+    pub plaintext: Vec<bool>,
+    pub ciphertext: Option<Vec<bool>>,
+    pub leakage: Vec<Leakage>,
+}
+
+impl CompileError {
+    pub(crate) fn new(line: usize, message: &str) -> Self {
+        CompileError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Absolute round and word of a reference made in an operation of `round`: `NAME#-1#I#` is word
+/// `I` of `round`, and `NAME#W#K#` is word `W` of round `round - 1 + K`.
+pub(crate) fn locate(var: &VarRef, round: u32, line: usize) -> Result<(i32, i32), CompileError> {
+    if var.round == -1 {
+        Ok((round as i32, var.index))
+    } else if var.round >= 0 && round as i32 - 1 + var.index >= 0 {
+        Ok((round as i32 - 1 + var.index, var.round))
+    } else {
+        Err(CompileError::new(
+            line,
+            &format!("invalid reference to {}", var.name),
+        ))
+    }
+}
+
+/// The bit a bit name stands for after the renamings of `aliases`.
+fn resolve(aliases: &NameMap<String>, name: &str) -> String {
+    let mut name = name;
+    while let Some(next) = aliases.get(name) {
+        name = next;
+    }
+    name.to_string()
+}
+
+/// Monomials of `n` variables, by degree then in lexicographic order of their indices.
+pub(crate) fn monomials(n: usize) -> Vec<Vec<usize>> {
+    fn extend(prefix: Vec<usize>, start: usize, n: usize, left: usize, out: &mut Vec<Vec<usize>>) {
+        if left == 0 {
+            out.push(prefix);
+            return;
+        }
+        for i in start..n {
+            let mut next = prefix.clone();
+            next.push(i);
+            extend(next, i + 1, n, left - 1, out);
+        }
+    }
+    let mut out = vec![];
+    for degree in 0..=n {
+        extend(vec![], 0, n, degree, &mut out);
+    }
+    out
+}
+
+impl Compiler {
+    pub fn new(constants: Vec<Constant>) -> Self {
+        Compiler {
+            layout: Layout::new(constants),
+            instance: Instance::new(),
+            aliases: NameMap::default(),
+            gates: 0,
+        }
+    }
+
+    /// Unroll the rounds of `desc` and add their clauses.
+    pub fn compile(&mut self, desc: &Description) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            let mut operands = vec![];
+            for var in op.operands.iter() {
+                operands.push(self.layout.bits(var, *round, op.line)?);
+            }
+            self.compile_operation(*round, op, operands)?;
+        }
+        Ok(())
+    }
+
+    /// Add a faulty run of `desc`, which has been compiled already, numbered `run`.
+    ///
+    /// The faulted bits become the correct ones XORed with a difference constrained by the fault
+    /// model, which is word `run` of `fault` in round 0. From there on, every operation which
+    /// reads a faulty bit is compiled again over the words of the run, named by `faulty_name`,
+    /// while everything else, such as the round keys, is shared with the correct run.
+    pub fn compile_fault(
+        &mut self,
+        desc: &Description,
+        fault: &Fault,
+        run: u32,
+    ) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+
+        // The fault hits after the operation writing the word, or before all of them for inputs.
+        let mut start = 0;
+        for (i, (round, op)) in ops.iter().enumerate() {
+            let var = op.operands.last().unwrap();
+            let (r, index) = locate(var, *round, op.line)?;
+            if (var.name.as_str(), r, index) == (fault.name.as_str(), fault.round, fault.index) {
+                start = i + 1;
+            }
+        }
+        let line = match start {
+            0 => 0,
+            _ => ops[start - 1].1.line,
+        };
+        let (lo, hi) = fault.bits;
+        let width = self.layout.widths.get(&(fault.name.clone(), fault.index));
+        if lo == 0 || lo > hi || !matches!(width, Some(&width) if hi <= width) {
+            return Err(CompileError::new(line, "fault outside of the word"));
+        }
+        let expected = match fault.model {
+            FaultModel::BitFlip => hi - lo + 1,
+            FaultModel::RandomByte => 8,
+            FaultModel::RandomNibble => 4,
+        };
+        if hi - lo + 1 != expected {
+            return Err(CompileError::new(
+                line,
+                "fault range does not match the model",
+            ));
+        }
+
+        let words: Vec<(String, i32)> = self.layout.widths.keys().cloned().collect();
+        for (name, index) in words.into_iter() {
+            let width = self.layout.widths[&(name.clone(), index)];
+            self.layout
+                .widths
+                .insert((faulty_name(&name, run), index), width);
+        }
+        self.layout
+            .widths
+            .insert(("fault".to_string(), run as i32), hi - lo + 1);
+        let mut faulty: HashSet<String> = HashSet::new();
+        let mut difference = vec![];
+        for b in lo..=hi {
+            let correct = bit_name(&fault.name, fault.round, fault.index, b);
+            let wrong = bit_name(&faulty_name(&fault.name, run), fault.round, fault.index, b);
+            let delta = bit_name("fault", 0, run as i32, b - lo + 1);
+            self.xor(&[&correct, &delta, &wrong], false);
+            faulty.insert(correct);
+            difference.push(delta);
+        }
+        let mut leakage = Leakage::new(LeakageType::Fault);
+        leakage.add_fault(&difference, fault.model);
+        for clause in leakage.into_clauses() {
+            for name in clause.names() {
+                self.instance.add_variable(name);
+            }
+            self.instance.add_clause(clause);
+        }
+
+        for (round, op) in ops[start..].iter() {
+            let mut correct = vec![];
+            let mut wrong = vec![];
+            for var in op.operands.iter() {
+                correct.push(self.layout.bits(var, *round, op.line)?);
+                let var = VarRef {
+                    name: faulty_name(&var.name, run),
+                    ..var.clone()
+                };
+                wrong.push(self.layout.bits(&var, *round, op.line)?);
+            }
+            let output = correct.pop().unwrap();
+            if !correct.iter().flatten().any(|bit| faulty.contains(bit)) {
+                continue;
+            }
+            let mut operands: Vec<Vec<String>> = correct
+                .iter()
+                .zip(wrong.iter())
+                .map(|(correct, wrong)| {
+                    correct
+                        .iter()
+                        .zip(wrong.iter())
+                        .map(|(c, w)| if faulty.contains(c) { w } else { c }.clone())
+                        .collect()
+                })
+                .collect();
+            operands.push(wrong.pop().unwrap());
+            faulty.extend(output);
+            self.compile_operation(*round, op, operands)?;
+        }
+        Ok(())
+    }
+
+    /// Add one copy of `desc` per pair, numbered from 1, with known plaintexts and ciphertexts
+    /// fixed and the leakage of each pair added. The words of each copy are named by `pair_name`,
+    /// except those of `shared`, such as the round keys. `plaintext` and `ciphertext` are
+    /// references with absolute rounds like `A4#1#1#`, and the texts must have as many bits.
+    ///
+    /// The shared words are compiled beforehand with `compile`, such as a key schedule, and the
+    /// finished instance of all pairs is returned.
+    pub fn compile_pairs(
+        mut self,
+        desc: &Description,
+        shared: &[&str],
+        plaintext: &VarRef,
+        ciphertext: &VarRef,
+        pairs: Vec<Pair>,
+    ) -> Result<Compiled, CompileError> {
+        for (i, pair) in pairs.into_iter().enumerate() {
+            let n = i as u32 + 1;
+            self.compile_pair(desc, n, shared)?;
+            let words = [
+                (plaintext, Some(pair.plaintext)),
+                (ciphertext, pair.ciphertext),
+            ];
+            for (var, bits) in words.iter() {
+                let bits = match bits {
+                    Some(bits) => bits,
+                    None => continue,
+                };
+                let name = pair_name(&var.name, n);
+                let width = match self.layout.widths.get(&(name.clone(), var.index)) {
+                    Some(&width) => width,
+                    None => {
+                        return Err(CompileError::new(
+                            0,
+                            &format!("unknown word {} {}", var.name, var.index),
+                        ))
+                    }
+                };
+                let (lo, hi) = var.bits.unwrap_or((1, width));
+                if lo == 0 || lo > hi || hi > width {
+                    return Err(CompileError::new(
+                        0,
+                        &format!("bits outside of {}", var.name),
+                    ));
+                }
+                if (hi - lo + 1) as usize != bits.len() {
+                    return Err(CompileError::new(
+                        0,
+                        &format!(
+                            "{} has {} bits, {} given",
+                            var.name,
+                            hi - lo + 1,
+                            bits.len()
+                        ),
+                    ));
+                }
+                for (b, &value) in bits.iter().enumerate() {
+                    let bit = bit_name(&name, var.round, var.index, lo + b as u32);
+                    self.unit(&bit, value);
+                }
+            }
+            for leakage in pair.leakage.into_iter() {
+                let aliases = &self.aliases;
+                self.instance.add_leakage(leakage, |name| {
+                    let word = name.split('#').next().unwrap();
+                    match shared.contains(&word) {
+                        true => resolve(aliases, name),
+                        false => resolve(aliases, &pair_name(name, n)),
+                    }
+                });
+            }
+        }
+        Ok(self.finish())
+    }
+
+    /// Compile `desc` again as the encryption of pair `pair`.
+    fn compile_pair(
+        &mut self,
+        desc: &Description,
+        pair: u32,
+        shared: &[&str],
+    ) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            let mut operands = vec![];
+            for var in op.operands.iter() {
+                if shared.contains(&var.name.as_str()) {
+                    operands.push(self.layout.bits(var, *round, op.line)?);
+                    continue;
+                }
+                let (_, index) = locate(var, *round, op.line)?;
+                let name = pair_name(&var.name, pair);
+                if let Some(&width) = self.layout.widths.get(&(var.name.clone(), index)) {
+                    self.layout.widths.insert((name.clone(), index), width);
+                }
+                let var = VarRef {
+                    name,
+                    ..var.clone()
+                };
+                operands.push(self.layout.bits(&var, *round, op.line)?);
+            }
+            self.compile_operation(*round, op, operands)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Compiled {
+        Compiled {
+            instance: self.instance,
+            aliases: self.aliases,
+            widths: self.layout.widths,
+        }
+    }
+
+    /// Add the clauses of `op`, over the bit names of its operands.
+    fn compile_operation(
+        &mut self,
+        round: u32,
+        op: &Operation,
+        mut operands: Vec<Vec<String>>,
+    ) -> Result<(), CompileError> {
+        let output = operands.pop().unwrap();
+        match op.opcode {
+            Opcode::Xor if operands.len() == 1 => self.copy(&operands[0], &output),
+            Opcode::Assign => self.copy(&operands[0], &output),
+            Opcode::Xor => {
+                for (i, out) in output.iter().enumerate() {
+                    let mut names: Vec<&str> =
+                        operands.iter().map(|bits| bits[i].as_str()).collect();
+                    names.push(out);
+                    self.xor(&names, false);
+                }
+            }
+            Opcode::Permutation(n) => {
+                let perm = self.layout.permutation(n, operands[0].len(), op.line)?;
+                let input: Vec<String> = perm
+                    .iter()
+                    .map(|&p| operands[0][p as usize].clone())
+                    .collect();
+                self.copy(&input, &output);
+            }
+            Opcode::Matrix(n) => {
+                let rows = self.layout.matrix(&format!("m{}", n), op.line)?.to_vec();
+                for (row, out) in rows.iter().zip(output.iter()) {
+                    let mut names: Vec<&str> = vec![];
+                    for &i in row.iter() {
+                        names.push(operands[0].get(i as usize).ok_or_else(|| {
+                            CompileError::new(op.line, &format!("m{} refers to bit {}", n, i))
+                        })?);
+                    }
+                    names.push(out);
+                    self.xor(&names, false);
+                }
+            }
+            Opcode::Constant => {
+                let width = output.len();
+                let slice = self.layout.round_constant(round, width, op.line)?.to_vec();
+                for i in 0..width {
+                    self.unit(&operands[1][i], slice[i] != 0);
+                    self.xor(&[&operands[0][i], &operands[1][i], &output[i]], false);
+                }
+            }
+            Opcode::Sbox => {
+                let mut offset = 0;
+                for name in op.params.iter() {
+                    let n = self.layout.sbox_size(name, op.line)?;
+                    let anf = self.layout.array(name, op.line)?.to_vec();
+                    let input = operands[0][offset..offset + n].to_vec();
+                    self.sbox(&anf, &input, &output[offset..offset + n]);
+                    offset += n;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, name: &str) -> String {
+        resolve(&self.aliases, name)
+    }
+
+    /// Make each bit of `output` equal to the bit of `input` at the same position, by renaming
+    /// when the output bit has not been used yet.
+    fn copy(&mut self, input: &[String], output: &[String]) {
+        for (src, dst) in input.iter().zip(output.iter()) {
+            let src = self.resolve(src);
+            if self.aliases.contains_key(dst) || self.instance.variables.contains_key(dst) {
+                self.xor(&[dst, &src], false);
+            } else if *dst != src {
+                self.aliases.insert(dst.clone(), src);
+            }
+        }
+    }
+
+    fn add_clause(&mut self, xor: bool, lits: Vec<(String, bool)>) {
+        self.instance.add_clause(Clause::from_names(xor, lits));
+    }
+
+    fn unit(&mut self, name: &str, value: bool) {
+        let name = self.resolve(name);
+        self.add_clause(false, vec![(name, value)]);
+    }
+
+    /// Constrain the XOR of the bits `names` to `rhs`. Repeated bits cancel out.
+    fn xor(&mut self, names: &[&str], rhs: bool) {
+        let mut vars: Vec<String> = vec![];
+        for name in names.iter() {
+            let name = self.resolve(name);
+            match vars.iter().position(|v| *v == name) {
+                Some(i) => {
+                    vars.remove(i);
+                }
+                None => vars.push(name),
+            }
+        }
+        match vars.len() {
+            0 if rhs => self.add_clause(false, vec![]),
+            0 => (),
+            1 => self.unit(&vars[0], rhs),
+            _ => {
+                let mut lits: Vec<(String, bool)> = vars.into_iter().map(|v| (v, true)).collect();
+                lits[0].1 = rhs;
+                self.add_clause(true, lits);
+            }
+        }
+    }
+
+    /// Encode an S-box from `n` ANF vectors, one output bit each, with shared AND gates.
+    fn sbox(&mut self, anf: &[u8], input: &[String], output: &[String]) {
+        let n = input.len();
+        let input: Vec<String> = input.iter().map(|name| self.resolve(name)).collect();
+        let monomials = monomials(n);
+        let mut terms: Vec<Option<String>> = vec![None; monomials.len()];
+        for (t, monomial) in monomials.iter().enumerate() {
+            let used = (0..n).any(|i| anf[(i << n) + t] != 0);
+            terms[t] = match monomial.len() {
+                0 => None,
+                1 => Some(input[monomial[0]].clone()),
+                _ if used => {
+                    self.gates += 1;
+                    let gate = format!("and#{}#", self.gates);
+                    // gate -> x_i for every i, and all x_i -> gate.
+                    for &i in monomial.iter() {
+                        self.add_clause(
+                            false,
+                            vec![(gate.clone(), false), (input[i].clone(), true)],
+                        );
+                    }
+                    let mut lits: Vec<(String, bool)> = monomial
+                        .iter()
+                        .map(|&i| (input[i].clone(), false))
+                        .collect();
+                    lits.push((gate.clone(), true));
+                    self.add_clause(false, lits);
+                    Some(gate)
+                }
+                _ => None,
+            };
+        }
+        for (i, out) in output.iter().enumerate() {
+            let coefficients = &anf[i << n..(i + 1) << n];
+            let mut names: Vec<&str> = vec![out];
+            for (t, term) in terms.iter().enumerate() {
+                if let (Some(term), true) = (term, coefficients[t] != 0) {
+                    names.push(term);
+                }
+            }
+            self.xor(&names, coefficients[0] != 0);
+        }
+    }
+}
+
+impl Layout {
+    pub(crate) fn new(constants: Vec<Constant>) -> Self {
+        Layout {
+            constants: constants
+                .into_iter()
+                .map(|c| (c.name().to_string(), c))
+                .collect(),
+            widths: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn array(&self, name: &str, line: usize) -> Result<&[u8], CompileError> {
+        match self.constants.get(name) {
+            Some(Constant::Array(arr)) => Ok(&arr.value),
+            Some(Constant::Matrix(_)) => {
+                Err(CompileError::new(line, &format!("{} is a matrix", name)))
+            }
+            None => Err(CompileError::new(
+                line,
+                &format!("unknown constant {}", name),
+            )),
+        }
+    }
+
+    pub(crate) fn matrix(&self, name: &str, line: usize) -> Result<&[Vec<u8>], CompileError> {
+        match self.constants.get(name) {
+            Some(Constant::Matrix(mat)) => Ok(&mat.value),
+            Some(Constant::Array(_)) => Err(CompileError::new(
+                line,
+                &format!("{} is not a matrix", name),
+            )),
+            None => Err(CompileError::new(
+                line,
+                &format!("unknown constant {}", name),
+            )),
+        }
+    }
+
+    /// The round constants used by `c`, the only constant named `c*`.
+    pub(crate) fn round_constants(&self, line: usize) -> Result<&[u8], CompileError> {
+        let mut names = self.constants.keys().filter(|name| name.starts_with('c'));
+        match (names.next(), names.next()) {
+            (Some(name), None) => self.array(name, line),
+            _ => Err(CompileError::new(
+                line,
+                "expected exactly one round constant c*",
+            )),
+        }
+    }
+
+    /// The `width` bits of the round constants added in `round`, the first round being 1.
+    pub(crate) fn round_constant(
+        &self,
+        round: u32,
+        width: usize,
+        line: usize,
+    ) -> Result<&[u8], CompileError> {
+        let rc = self.round_constants(line)?;
+        if round == 0 {
+            return Err(CompileError::new(line, "no round constant for round 0"));
+        }
+        let start = (round as usize - 1) * width;
+        rc.get(start..start + width)
+            .ok_or_else(|| CompileError::new(line, "no round constant for this round"))
+    }
+
+    /// The permutation `p{n}` of a word of `width` bits.
+    pub(crate) fn permutation(
+        &self,
+        n: u32,
+        width: usize,
+        line: usize,
+    ) -> Result<&[u8], CompileError> {
+        let perm = self.array(&format!("p{}", n), line)?;
+        match perm.iter().find(|&&p| p as usize >= width) {
+            Some(p) => Err(CompileError::new(
+                line,
+                &format!("p{} refers to bit {}", n, p),
+            )),
+            None => Ok(perm),
+        }
+    }
+
+    /// Number of input bits of an S-box given as `n` ANF vectors of `2^n` coefficients.
+    pub(crate) fn sbox_size(&self, name: &str, line: usize) -> Result<usize, CompileError> {
+        let len = self.array(name, line)?.len();
+        (1..16)
+            .find(|n| n << n == len)
+            .ok_or_else(|| CompileError::new(line, &format!("{} is not an ANF S-box", name)))
+    }
+
+    /// Widths of the words follow from the constants, then spread through XORs and copies.
+    pub(crate) fn infer_widths(&mut self, ops: &[(u32, &Operation)]) -> Result<(), CompileError> {
+        let mut bounds: HashMap<(String, i32), u32> = HashMap::new();
+        for (round, op) in ops.iter() {
+            let fixed = match op.opcode {
+                Opcode::Sbox => {
+                    let mut size = 0;
+                    for name in op.params.iter() {
+                        size += self.sbox_size(name, op.line)? as u32;
+                    }
+                    Some(size)
+                }
+                Opcode::Permutation(n) => {
+                    Some(self.array(&format!("p{}", n), op.line)?.len() as u32)
+                }
+                Opcode::Matrix(n) => Some(self.matrix(&format!("m{}", n), op.line)?.len() as u32),
+                _ => None,
+            };
+            for var in op.operands.iter() {
+                let (_, index) = locate(var, *round, op.line)?;
+                let key = (var.name.clone(), index);
+                match (var.bits, fixed) {
+                    (Some((lo, hi)), Some(size)) if hi - lo + 1 != size => {
+                        return Err(CompileError::new(
+                            op.line,
+                            "bit range does not match the constant",
+                        ))
+                    }
+                    (Some((_, hi)), _) => {
+                        let bound = bounds.entry(key).or_insert(0);
+                        *bound = (*bound).max(hi);
+                    }
+                    (None, Some(size)) => self.set_width(key, size, op.line)?,
+                    (None, None) => (),
+                }
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (round, op) in ops.iter() {
+                let mut widths = vec![];
+                for var in op.operands.iter() {
+                    let (_, index) = locate(var, *round, op.line)?;
+                    widths.push(match var.bits {
+                        Some((lo, hi)) => Some(hi - lo + 1),
+                        None => self.widths.get(&(var.name.clone(), index)).cloned(),
+                    });
+                }
+                let known = match widths.iter().find_map(|w| *w) {
+                    Some(width) => width,
+                    None => continue,
+                };
+                for (var, width) in op.operands.iter().zip(widths.iter()) {
+                    if width.is_none() {
+                        let (_, index) = locate(var, *round, op.line)?;
+                        self.set_width((var.name.clone(), index), known, op.line)?;
+                        changed = true;
+                    } else if *width != Some(known) {
+                        return Err(CompileError::new(op.line, "operands have different widths"));
+                    }
+                }
+            }
+        }
+
+        for (key, bound) in bounds.into_iter() {
+            match self.widths.get(&key) {
+                Some(&width) if width < bound => {
+                    return Err(CompileError::new(
+                        0,
+                        &format!("bit range beyond the width of {}", key.0),
+                    ))
+                }
+                Some(_) => (),
+                None => {
+                    self.widths.insert(key, bound);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_width(
+        &mut self,
+        key: (String, i32),
+        width: u32,
+        line: usize,
+    ) -> Result<(), CompileError> {
+        match self.widths.get(&key) {
+            Some(&known) if known != width => Err(CompileError::new(
+                line,
+                &format!("{} word {} has width {} and {}", key.0, key.1, known, width),
+            )),
+            _ => {
+                self.widths.insert(key, width);
+                Ok(())
+            }
+        }
+    }
+
+    /// Names of the bits of an operand.
+    pub(crate) fn bits(
+        &self,
+        var: &VarRef,
+        round: u32,
+        line: usize,
+    ) -> Result<Vec<String>, CompileError> {
+        let (round, index) = locate(var, round, line)?;
+        let (lo, hi) = match var.bits {
+            Some(range) => range,
+            None => match self.widths.get(&(var.name.clone(), index)) {
+                Some(&width) => (1, width),
+                None => {
+                    return Err(CompileError::new(
+                        line,
+                        &format!("cannot infer the width of {}", var.name),
+                    ))
+                }
+            },
+        };
+        Ok((lo..=hi)
+            .map(|bit| bit_name(&var.name, round, index, bit))
+            .collect())
+    }
+}
+
+/// Expand `CP,n` into `n` more copies of the preceding round, labelled with the next rounds.
+pub(crate) fn unroll(desc: &Description) -> Vec<(u32, &Operation)> {
+    let mut ops: Vec<(u32, &Operation)> = vec![];
+    for statement in desc.statements.iter() {
+        match statement {
+            Statement::Operation(op) => ops.push((op.round, op)),
+            Statement::Repeat(n) => {
+                let last = ops.last().unwrap().0;
+                let block: Vec<&Operation> = ops
+                    .iter()
+                    .rev()
+                    .take_while(|(round, _)| *round == last)
+                    .map(|(_, op)| *op)
+                    .collect();
+                for k in 1..=*n {
+                    ops.extend(block.iter().rev().map(|op| (last + k, *op)));
+                }
+            }
+        }
+    }
+    ops
+}
+
+impl Compiled {
+    /// Variable of a bit, following renamings.
+    pub fn variable(&self, name: &str) -> Option<u32> {
+        let mut name = name;
+        while let Some(next) = self.aliases.get(name) {
+            name = next;
+        }
+        self.instance.variables.get(name).cloned()
+    }
+
+    /// Number of bits of the words `index` of `name`.
+    pub fn width(&self, name: &str, index: i32) -> Option<u32> {
+        self.widths.get(&(name.to_string(), index)).cloned()
+    }
+
+    /// Variables of the bits of a word, adding those which no clause uses yet.
+    pub fn variables(&mut self, name: &str, round: i32, index: i32) -> Option<Vec<u32>> {
+        let width = self.width(name, index)?;
+        let mut vars = vec![];
+        for bit in 1..=width {
+            let mut name = bit_name(name, round, index, bit);
+            while let Some(next) = self.aliases.get(&name) {
+                name = next.clone();
+            }
+            self.instance.add_variable(&name);
+            vars.push(self.instance.variables[&name]);
+        }
+        Some(vars)
+    }
+
+    /// Fix the bits of a word with unit clauses, such as a known plaintext.
+    pub fn fix(&mut self, name: &str, round: i32, index: i32, bits: &[bool]) {
+        for (i, &bit) in bits.iter().enumerate() {
+            let mut name = bit_name(name, round, index, i as u32 + 1);
+            while let Some(next) = self.aliases.get(&name) {
+                name = next.clone();
+            }
+            self.instance.add_variable(&name);
+            self.instance
+                .add_clause(Clause::new(false, vec![(&name, bit)]));
+        }
+    }
+
+    /// Add the clauses of a leakage, whose bits are named like the bits of the description.
+    pub fn leak(&mut self, leakage: Leakage) {
+        let aliases = &self.aliases;
+        self.instance
+            .add_leakage(leakage, |name| resolve(aliases, name));
+    }
+
+    /// Add the observations of a leakage specification.
+    pub fn observe(&mut self, spec: &LeakageSpec) -> Result<(), CompileError> {
+        let mut collisions = Leakage::new(LeakageType::Collision);
+        let mut partial = Leakage::new(LeakageType::PartialBit);
+        for observation in spec.observations.iter() {
+            match observation {
+                Observation::Collision { a, b, line } => {
+                    let a = self.bits(a, *line)?;
+                    let b = self.bits(b, *line)?;
+                    if a.len() != b.len() {
+                        return Err(CompileError::new(*line, "words have different widths"));
+                    }
+                    collisions.add_collision(&a, &b);
+                }
+                Observation::PartialBit { var, values, line } => {
+                    let bits = self.bits(var, *line)?;
+                    if bits.len() != values.len() {
+                        return Err(CompileError::new(
+                            *line,
+                            &format!("expected {} bits", bits.len()),
+                        ));
+                    }
+                    partial.add_partial_bits(&bits, values);
+                }
+            }
+        }
+        self.leak(collisions);
+        self.leak(partial);
+        Ok(())
+    }
+
+    /// Names of the bits of a reference with an absolute round.
+    fn bits(&self, var: &VarRef, line: usize) -> Result<Vec<String>, CompileError> {
+        let (lo, hi) = match (var.bits, self.width(&var.name, var.index)) {
+            (Some(range), _) => range,
+            (None, Some(width)) => (1, width),
+            (None, None) => {
+                return Err(CompileError::new(
+                    line,
+                    &format!("unknown word {} {}", var.name, var.index),
+                ))
+            }
+        };
+        Ok((lo..=hi)
+            .map(|bit| bit_name(&var.name, var.round, var.index, bit))
+            .collect())
+    }
+
+    /// Read a word back from a model, where `model[v - 1]` is the value of variable `v`.
+    pub fn read(&self, model: &[bool], name: &str, round: i32, index: i32) -> Option<Vec<bool>> {
+        let width = self.width(name, index)?;
+        (1..=width)
+            .map(|bit| {
+                let var = self.variable(&bit_name(name, round, index, bit))?;
+                model.get(var as usize - 1).cloned()
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 #[derive(Debug, PartialEq)]
 pub enum Constant {
     Array(ConstantArr),
@@ -15,3 +17,28 @@ pub struct ConstantMat {
     pub name: String,
     pub value: Vec<Vec<u8>>,
 }
+
+/// All constants of a `const` file, by name.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConstantTable {
+    pub constants: HashMap<String, Constant>,
+}
+
+impl Constant {
+    pub fn name(&self) -> &str {
+        match self {
+            Constant::Array(arr) => &arr.name,
+            Constant::Matrix(mat) => &mat.name,
+        }
+    }
+}
+
+impl ConstantTable {
+    pub fn get(&self, name: &str) -> Option<&Constant> {
+        self.constants.get(name)
+    }
+
+    pub fn into_constants(self) -> Vec<Constant> {
+        self.constants.into_values().collect()
+    }
+}
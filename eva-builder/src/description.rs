@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// A parsed cipher description, such as `ciphers/aes/enc`.
+#[derive(Debug, PartialEq)]
+pub struct Description {
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Statement {
+    Operation(Operation),
+    /// `CP,n`: repeat the operations of the preceding round `n` more times.
+    Repeat(u32),
+}
+
+/// One line like `1,x,A4#-1#1#,K1#0#0#,A4#-1#2#,`.
+#[derive(Debug, PartialEq)]
+pub struct Operation {
+    /// The round label in front of the opcode.
+    pub round: u32,
+    pub opcode: Opcode,
+    pub operands: Vec<VarRef>,
+    /// Trailing parameters, such as the S-box names in `s1;s1;`.
+    pub params: Vec<String>,
+    /// Line of the operation in the description, starting at 1.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    /// `x`: the last operand is the XOR of the others.
+    Xor,
+    /// `s`: S-box layer, one S-box per parameter.
+    Sbox,
+    /// `pN`: bit permutation by the constant `pN`.
+    Permutation(u32),
+    /// `mN`: binary matrix multiplication by the constant `mN`.
+    Matrix(u32),
+    /// `rx`: the second operand is a copy of the bits of the first.
+    Assign,
+    /// `c`: the last operand is the first one XORed with a round constant.
+    Constant,
+}
+
+impl fmt::Display for Opcode {
+    /// The opcode as written in a description.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opcode::Xor => write!(f, "x"),
+            Opcode::Sbox => write!(f, "s"),
+            Opcode::Permutation(n) => write!(f, "p{}", n),
+            Opcode::Matrix(n) => write!(f, "m{}", n),
+            Opcode::Assign => write!(f, "rx"),
+            Opcode::Constant => write!(f, "c"),
+        }
+    }
+}
+
+/// A variable reference with two numbers, optionally restricted to the bits `LO#HI#`.
+///
+/// Outside a description, in leakage specifications and on the command line, `NAME#R#I#` is
+/// word `I` of the absolute round `R`. In an operation of round `r`, `NAME#-1#I#` is word `I` of
+/// round `r`, while any other `NAME#W#K#` is word `W` of round `r - 1 + K`: the first number is
+/// then the word and the second a round offset. `compiler::locate` resolves both forms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarRef {
+    pub name: String,
+    /// The absolute round, `-1` for the current round, or the word of a relative reference.
+    pub round: i32,
+    /// The word, or the round offset of a relative reference.
+    pub index: i32,
+    /// Inclusive bit range, starting at 1.
+    pub bits: Option<(u32, u32)>,
+}
@@ -1,4 +1,52 @@
-use std::collections::HashMap;
+use super::leakage::Leakage;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ops::RangeInclusive;
+
+/// FNV-1a, for the short bit names of unrolled ciphers. On the full AES unrolling, numbering
+/// the clauses takes about a fifth less time than with the default SipHash.
+#[derive(Default)]
+pub struct NameHasher(u64);
+
+impl Hasher for NameHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.0 == 0 {
+            self.0 = 0xcbf2_9ce4_8422_2325;
+        }
+        for &byte in bytes.iter() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// A map keyed by variable names.
+pub type NameMap<V> = HashMap<String, V, BuildHasherDefault<NameHasher>>;
+
+/// A bit `NAME#ROUND#INDEX#BIT#` of a word, as the compiler names it. Instances index the
+/// variables with such names by their `VarId` as well.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VarId {
+    pub name: String,
+    pub round: i32,
+    pub index: i32,
+    /// Starting at 1.
+    pub bit: u32,
+}
+
+/// A clause over variable numbers, true for an XOR clause.
+pub type NumberedClause = (bool, Vec<(u32, bool)>);
+
+/// A soft clause over variable numbers, with its weight.
+pub type WeightedClause = (u64, Vec<(u32, bool)>);
+
+/// A name used by a clause which is not a variable of the instance.
+#[derive(Debug, PartialEq)]
+pub struct UnknownVariable(pub String);
 
 pub struct Literal {
     name: String,
@@ -10,12 +58,75 @@ pub struct Clause {
     clause_xor: bool,
 }
 
+/// What a solver printed: its `s` status line and the values of its `v` lines.
+#[derive(Debug, PartialEq)]
+pub struct SolverOutput {
+    /// `Some(true)` for `s SATISFIABLE`, `None` without a status line or for `s UNKNOWN`.
+    pub satisfiable: Option<bool>,
+    pub values: HashMap<u32, bool>,
+}
+
 #[derive(Default)]
 pub struct Instance {
     pub equations: Vec<Clause>,
-    pub variables: HashMap<String, u32>,
+    /// Variables by name, to be added with `add_variable` so that `bits` stays in step.
+    pub variables: NameMap<u32>,
+    /// The variables which are bits of words, ordered by word for `state`.
+    bits: BTreeMap<VarId, u32>,
+    /// Clauses which may be violated at the cost of their weight, for MaxSAT.
+    pub soft: Vec<(u64, Clause)>,
+    /// Auxiliary variables of the leakages added so far.
+    aux: usize,
+}
+
+impl VarId {
+    pub fn new(name: &str, round: i32, index: i32, bit: u32) -> Self {
+        VarId {
+            name: name.to_string(),
+            round,
+            index,
+            bit,
+        }
+    }
+
+    /// The bit a variable name stands for, if it has the form `NAME#ROUND#INDEX#BIT#`.
+    fn of_name(name: &str) -> Option<VarId> {
+        let mut parts = name.split('#');
+        let word = parts.next().filter(|word| !word.is_empty())?;
+        let round = parts.next()?.parse().ok()?;
+        let index = parts.next()?.parse().ok()?;
+        let bit = parts.next()?.parse().ok()?;
+        match (parts.next(), parts.next()) {
+            (Some(""), None) => Some(VarId::new(word, round, index, bit)),
+            _ => None,
+        }
+    }
+
+    /// The bits `bits` of a word, such as `1..=128` for a whole AES state.
+    pub fn word(name: &str, round: i32, index: i32, bits: RangeInclusive<u32>) -> Vec<VarId> {
+        bits.map(|bit| VarId::new(name, round, index, bit))
+            .collect()
+    }
 }
 
+impl fmt::Display for VarId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}#{}#{}#{}#",
+            self.name, self.round, self.index, self.bit
+        )
+    }
+}
+
+impl fmt::Display for UnknownVariable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown variable {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownVariable {}
+
 impl From<(&str, bool)> for Literal {
     fn from(tuple: (&str, bool)) -> Self {
         Literal {
@@ -32,42 +143,154 @@ impl Clause {
             clause_xor,
         }
     }
+
+    /// Names of the variables in the clause.
+    pub fn names(&self) -> Vec<&str> {
+        self.value.iter().map(|lit| lit.name.as_str()).collect()
+    }
+
+    /// A clause over owned names, saving a copy of each.
+    pub fn from_names(clause_xor: bool, value: Vec<(String, bool)>) -> Self {
+        Clause {
+            value: value
+                .into_iter()
+                .map(|(name, value)| Literal { name, value })
+                .collect(),
+            clause_xor,
+        }
+    }
+
+    /// The literals of the clause, true standing for a positive literal.
+    pub fn literals(&self) -> Vec<(&str, bool)> {
+        self.value
+            .iter()
+            .map(|lit| (lit.name.as_str(), lit.value))
+            .collect()
+    }
+
+    pub fn is_xor(&self) -> bool {
+        self.clause_xor
+    }
+
+    /// The same clause over renamed variables.
+    pub fn rename<F: Fn(&str) -> String>(self, f: F) -> Self {
+        Clause {
+            value: self
+                .value
+                .into_iter()
+                .map(|lit| Literal {
+                    name: f(&lit.name),
+                    value: lit.value,
+                })
+                .collect(),
+            clause_xor: self.clause_xor,
+        }
+    }
 }
 
 impl Instance {
     pub fn new() -> Self {
         Instance {
             equations: vec![],
-            variables: HashMap::new(),
-        }
-    }
-
-    pub fn clauses(self) -> Vec<(bool, Vec<(u32, bool)>)> {
-        let eqns = self.equations;
-        let vars = self.variables;
-        eqns.into_iter()
-            .map(|clause| {
-                (
-                    clause.clause_xor,
-                    clause
-                        .value
-                        .into_iter()
-                        .map(|lit| (*(vars.get(&lit.name).unwrap()), lit.value))
-                        .collect(),
-                )
-            })
+            variables: NameMap::default(),
+            bits: BTreeMap::new(),
+            soft: vec![],
+            aux: 0,
+        }
+    }
+
+    /// The clauses over variable numbers, or the first name which is not a variable.
+    pub fn clauses(self) -> Result<Vec<NumberedClause>, UnknownVariable> {
+        self.numbered()
+    }
+
+    /// Number of a bit of a word.
+    pub fn variable(&self, id: &VarId) -> Option<u32> {
+        self.bits.get(id).cloned()
+    }
+
+    /// All bits of the words of `name` in `round` which are variables, in order of word and bit.
+    pub fn state(&self, name: &str, round: i32) -> Vec<(VarId, u32)> {
+        let first = VarId::new(name, round, i32::MIN, 0);
+        let last = VarId::new(name, round, i32::MAX, u32::MAX);
+        self.bits
+            .range(first..=last)
+            .map(|(id, &var)| (id.clone(), var))
             .collect()
     }
 
+    /// Add a clause, making variables of the names which are not yet.
     pub fn add_clause(&mut self, clause: Clause) {
+        for lit in clause.value.iter() {
+            self.add_variable(&lit.name);
+        }
         self.equations.push(clause);
     }
 
+    /// Add a soft clause, which cannot be an XOR clause.
+    pub fn add_soft_clause(&mut self, weight: u64, clause: Clause) {
+        assert!(!clause.clause_xor, "soft XOR clauses are not supported");
+        for lit in clause.value.iter() {
+            self.add_variable(&lit.name);
+        }
+        self.soft.push((weight, clause));
+    }
+
+    /// Add the hard and soft clauses of a leakage with its bits renamed by `rename`, numbering its
+    /// auxiliary variables after those of the leakages added before.
+    pub fn add_leakage<F: Fn(&str) -> String>(&mut self, mut leakage: Leakage, rename: F) {
+        self.aux = leakage.renumber(self.aux);
+        let (hard, soft) = leakage.into_parts();
+        for clause in hard.into_iter() {
+            self.add_clause(clause.rename(&rename));
+        }
+        for (weight, clause) in soft.into_iter() {
+            self.add_soft_clause(weight, clause.rename(&rename));
+        }
+    }
+
+    /// The hard clauses over variable numbers, like `clauses` without consuming the instance.
+    pub fn numbered(&self) -> Result<Vec<NumberedClause>, UnknownVariable> {
+        self.equations
+            .iter()
+            .map(|clause| Ok((clause.clause_xor, self.literals(clause)?)))
+            .collect()
+    }
+
+    /// The soft clauses over variable numbers, with their weights.
+    pub fn numbered_soft(&self) -> Result<Vec<WeightedClause>, UnknownVariable> {
+        self.soft
+            .iter()
+            .map(|(weight, clause)| Ok((*weight, self.literals(clause)?)))
+            .collect()
+    }
+
+    fn literals(&self, clause: &Clause) -> Result<Vec<(u32, bool)>, UnknownVariable> {
+        clause
+            .value
+            .iter()
+            .map(|lit| match self.variables.get(&lit.name) {
+                Some(&var) => Ok((var, lit.value)),
+                None => Err(UnknownVariable(lit.name.clone())),
+            })
+            .collect()
+    }
+
     pub fn add_variable(&mut self, name: &str) {
-        if self.variables.get(name).is_none() {
-            self.variables
-                .insert(name.to_string(), (self.variables.len() + 1) as u32);
+        if !self.variables.contains_key(name) {
+            self.insert_variable(name, (self.variables.len() + 1) as u32);
+        }
+    }
+
+    /// Give `name` the number `var`, returning false if it already has one.
+    pub(crate) fn insert_variable(&mut self, name: &str, var: u32) -> bool {
+        if self.variables.insert(name.to_string(), var).is_some() {
+            return false;
+        }
+        if let Some(id) = VarId::of_name(name) {
+            self.bits.insert(id, var);
         }
+        true
     }
 
     pub fn add_variables(&mut self, names: Vec<&str>) {
@@ -76,15 +299,14 @@ impl Instance {
         }
     }
 
-    pub fn to_cnf(&self) -> String {
+    pub fn to_cnf(&self) -> Result<String, UnknownVariable> {
         let mut cnf_string = "".to_string();
         for clause in self.equations.iter() {
             if clause.clause_xor {
                 cnf_string.push_str("x ");
             }
-            for lit in clause.value.iter() {
-                let var = self.variables.get(&lit.name).unwrap();
-                if lit.value {
+            for (var, value) in self.literals(clause)?.into_iter() {
+                if value {
                     cnf_string.push_str(&format!("{} ", var))
                 } else {
                     cnf_string.push_str(&format!("-{} ", var))
@@ -92,6 +314,104 @@ impl Instance {
             }
             cnf_string.push_str(&"0\n".to_string());
         }
-        cnf_string
+        Ok(cnf_string)
+    }
+
+    /// Write the instance as DIMACS CNF, with XOR clauses as `x` lines like CryptoMiniSat reads
+    /// them. A `c var N name` comment gives the name of every variable, which `Instance::from_str`
+    /// reads back. Soft clauses are left out.
+    pub fn to_dimacs(&self) -> Result<String, UnknownVariable> {
+        let mut names: Vec<(&u32, &String)> = self.variables.iter().map(|(k, v)| (v, k)).collect();
+        names.sort();
+        let mut dimacs = format!("p cnf {} {}\n", self.variables.len(), self.equations.len());
+        for (var, name) in names.into_iter() {
+            dimacs.push_str(&format!("c var {} {}\n", var, name));
+        }
+        dimacs.push_str(&self.to_cnf()?);
+        Ok(dimacs)
+    }
+
+    /// Name the values of a solver's model.
+    pub fn assignment(&self, output: &SolverOutput) -> HashMap<String, bool> {
+        self.variables
+            .iter()
+            .filter_map(|(name, var)| Some((name.clone(), *output.values.get(var)?)))
+            .collect()
+    }
+
+    /// Write the instance in the WCNF format of MaxSAT solvers, hard clauses having the weight
+    /// `top` of the header. XOR clauses are expanded to CNF, cutting the long ones into pieces of
+    /// at most four variables with new variables after those of the instance.
+    pub fn to_wcnf(&self) -> Result<String, UnknownVariable> {
+        let top: u64 = self.soft.iter().map(|(weight, _)| weight).sum::<u64>() + 1;
+        let mut vars = self.variables.len() as u32;
+        let mut lines: Vec<(u64, Vec<(u32, bool)>)> = vec![];
+        for (xor, lits) in self.numbered()?.into_iter() {
+            match xor {
+                true => lines.extend(xor_to_cnf(&lits, &mut vars).into_iter().map(|c| (top, c))),
+                false => lines.push((top, lits)),
+            }
+        }
+        lines.extend(self.numbered_soft()?);
+
+        let mut wcnf = format!("p wcnf {} {} {}\n", vars, lines.len(), top);
+        for (weight, lits) in lines.iter() {
+            wcnf.push_str(&weight.to_string());
+            for (var, value) in lits.iter() {
+                if *value {
+                    wcnf.push_str(&format!(" {}", var));
+                } else {
+                    wcnf.push_str(&format!(" -{}", var));
+                }
+            }
+            wcnf.push_str(" 0\n");
+        }
+        Ok(wcnf)
+    }
+}
+
+impl SolverOutput {
+    /// The values as a model for `vars` variables, where `model[v - 1]` is the value of `v`, and
+    /// variables left out by the solver are false.
+    pub fn model(&self, vars: usize) -> Vec<bool> {
+        (1..=vars as u32)
+            .map(|var| self.values.get(&var).cloned().unwrap_or(false))
+            .collect()
+    }
+}
+
+/// The CNF clauses of an XOR clause over `lits`. Long ones are cut into pieces of at most four
+/// variables, chained by new variables numbered after `vars`, which counts them.
+pub fn xor_to_cnf(lits: &[(u32, bool)], vars: &mut u32) -> Vec<Vec<(u32, bool)>> {
+    // The XOR of the variables equals `rhs`.
+    let mut rhs = true;
+    let mut chain: Vec<u32> = vec![];
+    for &(var, value) in lits.iter() {
+        rhs ^= !value;
+        chain.push(var);
+    }
+    let mut clauses = vec![];
+    while chain.len() > 4 {
+        *vars += 1;
+        let mut piece: Vec<u32> = chain.drain(..3).collect();
+        piece.push(*vars);
+        block_parity(&piece, false, &mut clauses);
+        chain.push(*vars);
+    }
+    block_parity(&chain, rhs, &mut clauses);
+    clauses
+}
+
+/// Forbid every assignment of `vars` whose XOR is not `rhs`, one clause each.
+fn block_parity(vars: &[u32], rhs: bool, clauses: &mut Vec<Vec<(u32, bool)>>) {
+    for assignment in 0..1u32 << vars.len() {
+        if (assignment.count_ones() % 2 == 1) != rhs {
+            let lits = vars
+                .iter()
+                .enumerate()
+                .map(|(i, &var)| (var, (assignment >> i) & 1 == 0))
+                .collect();
+            clauses.push(lits);
+        }
     }
 }
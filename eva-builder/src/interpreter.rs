@@ -0,0 +1,227 @@
+//! Evaluation of cipher descriptions on concrete bits.
+//!
+//! The interpreter follows the same naming and unrolling as the compiler, but computes the value
+//! of every bit instead of emitting clauses. Each bit remembers the operation which wrote it, so a
+//! wrong intermediate value points straight at the line and round of the description at fault.
+use super::compiler::{bit_name, monomials, unroll, CompileError, Layout};
+use super::constant::Constant;
+use super::description::{Description, Opcode, Operation};
+use std::collections::HashMap;
+
+/// The operation which wrote a bit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub line: usize,
+    pub round: u32,
+    pub opcode: Opcode,
+}
+
+/// Values of the bits of one or more descriptions run over shared constants.
+pub struct Interpreter {
+    layout: Layout,
+    values: HashMap<String, bool>,
+    origins: HashMap<String, Step>,
+}
+
+impl Interpreter {
+    pub fn new(constants: Vec<Constant>) -> Self {
+        Interpreter {
+            layout: Layout::new(constants),
+            values: HashMap::new(),
+            origins: HashMap::new(),
+        }
+    }
+
+    /// Give a word its value, such as a plaintext or a key.
+    pub fn set(&mut self, name: &str, round: i32, index: i32, bits: &[bool]) {
+        for (i, &bit) in bits.iter().enumerate() {
+            self.values
+                .insert(bit_name(name, round, index, i as u32 + 1), bit);
+        }
+    }
+
+    /// Value of a word, if its width is known and all its bits have been computed.
+    pub fn get(&self, name: &str, round: i32, index: i32) -> Option<Vec<bool>> {
+        let width = *self.layout.widths.get(&(name.to_string(), index))?;
+        (1..=width)
+            .map(|bit| self.values.get(&bit_name(name, round, index, bit)).cloned())
+            .collect()
+    }
+
+    /// The operation which wrote bit `bit` of a word, none for the bits given with `set`.
+    pub fn origin(&self, name: &str, round: i32, index: i32, bit: u32) -> Option<Step> {
+        self.origins
+            .get(&bit_name(name, round, index, bit))
+            .cloned()
+    }
+
+    /// Unroll the rounds of `desc` and evaluate them in order.
+    ///
+    /// Reading a bit which has no value yet, or writing a different value to a bit which has
+    /// one, is an error at the line of the operation.
+    pub fn run(&mut self, desc: &Description) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            self.run_operation(*round, op)?;
+        }
+        Ok(())
+    }
+
+    /// Check a word against its expected value, reporting the first bit which differs along
+    /// with the operation which wrote it.
+    pub fn compare(
+        &self,
+        name: &str,
+        round: i32,
+        index: i32,
+        expected: &[bool],
+    ) -> Result<(), String> {
+        let word = format!("{}#{}#{}#", name, round, index);
+        let actual = self
+            .get(name, round, index)
+            .ok_or_else(|| format!("{} has not been computed", word))?;
+        if actual.len() != expected.len() {
+            return Err(format!(
+                "{} has {} bits instead of {}",
+                word,
+                actual.len(),
+                expected.len()
+            ));
+        }
+        match (0..actual.len()).find(|&i| actual[i] != expected[i]) {
+            None => Ok(()),
+            Some(i) => {
+                let bit = i as u32 + 1;
+                let written = match self.origin(name, round, index, bit) {
+                    Some(step) => format!(
+                        "written by {} at line {} in round {}",
+                        step.opcode, step.line, step.round
+                    ),
+                    None => "given as input".to_string(),
+                };
+                Err(format!(
+                    "{} bit {} is {} instead of {}, {}",
+                    word, bit, actual[i] as u8, expected[i] as u8, written
+                ))
+            }
+        }
+    }
+
+    fn run_operation(&mut self, round: u32, op: &Operation) -> Result<(), CompileError> {
+        let step = Step {
+            line: op.line,
+            round,
+            opcode: op.opcode,
+        };
+        let mut operands = vec![];
+        for var in op.operands.iter() {
+            operands.push(self.layout.bits(var, round, op.line)?);
+        }
+        let output = operands.pop().unwrap();
+        let values = match op.opcode {
+            Opcode::Constant => {
+                let width = output.len();
+                let slice: Vec<bool> = self
+                    .layout
+                    .round_constant(round, width, op.line)?
+                    .iter()
+                    .map(|&b| b != 0)
+                    .collect();
+                self.write(&operands[1], &slice, step)?;
+                let input = self.read(&operands[0], op.line)?;
+                input.iter().zip(slice.iter()).map(|(a, b)| a ^ b).collect()
+            }
+            Opcode::Xor | Opcode::Assign => {
+                let mut values = vec![false; output.len()];
+                for bits in operands.iter() {
+                    for (value, bit) in values.iter_mut().zip(self.read(bits, op.line)?) {
+                        *value ^= bit;
+                    }
+                }
+                values
+            }
+            Opcode::Permutation(n) => {
+                let input = self.read(&operands[0], op.line)?;
+                let perm = self.layout.permutation(n, input.len(), op.line)?;
+                perm.iter().map(|&p| input[p as usize]).collect()
+            }
+            Opcode::Matrix(n) => {
+                let input = self.read(&operands[0], op.line)?;
+                let rows = self.layout.matrix(&format!("m{}", n), op.line)?;
+                let mut values = vec![];
+                for row in rows.iter() {
+                    let mut value = false;
+                    for &i in row.iter() {
+                        value ^= input.get(i as usize).ok_or_else(|| {
+                            CompileError::new(op.line, &format!("m{} refers to bit {}", n, i))
+                        })?;
+                    }
+                    values.push(value);
+                }
+                values
+            }
+            Opcode::Sbox => {
+                let input = self.read(&operands[0], op.line)?;
+                let mut values = vec![];
+                let mut offset = 0;
+                for name in op.params.iter() {
+                    let n = self.layout.sbox_size(name, op.line)?;
+                    let anf = self.layout.array(name, op.line)?;
+                    values.extend(sbox(anf, &input[offset..offset + n]));
+                    offset += n;
+                }
+                values
+            }
+        };
+        self.write(&output, &values, step)
+    }
+
+    fn read(&self, bits: &[String], line: usize) -> Result<Vec<bool>, CompileError> {
+        bits.iter()
+            .map(|name| {
+                self.values
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| CompileError::new(line, &format!("{} has no value", name)))
+            })
+            .collect()
+    }
+
+    fn write(&mut self, bits: &[String], values: &[bool], step: Step) -> Result<(), CompileError> {
+        for (name, &value) in bits.iter().zip(values.iter()) {
+            match self.values.get(name) {
+                Some(&known) if known != value => {
+                    return Err(CompileError::new(
+                        step.line,
+                        &format!("{} is already {}", name, known as u8),
+                    ))
+                }
+                Some(_) => (),
+                None => {
+                    self.values.insert(name.clone(), value);
+                    self.origins.insert(name.clone(), step);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluate an S-box given as one ANF vector per output bit, in the order of `monomials`.
+pub(crate) fn sbox(anf: &[u8], input: &[bool]) -> Vec<bool> {
+    let n = input.len();
+    let terms: Vec<bool> = monomials(n)
+        .iter()
+        .map(|monomial| monomial.iter().all(|&i| input[i]))
+        .collect();
+    (0..n)
+        .map(|i| {
+            let coefficients = &anf[i << n..(i + 1) << n];
+            terms
+                .iter()
+                .zip(coefficients.iter())
+                .fold(false, |acc, (&term, &c)| acc ^ (term && c != 0))
+        })
+        .collect()
+}
@@ -1,8 +1,37 @@
+//! Side-channel and fault leakage, as clauses over the bit names of a compiled description.
+//!
+//! A Hamming weight is encoded by counting the leaking bits in unary: output `j` of the counter
+//! is true exactly when at least `j` bits are set, so a weight `w` observed within a tolerance
+//! `t` fixes output `w - t` to true and output `w + t + 1` to false. The counters are encoded
+//! as equivalences rather than one-sided implications, which lets unit propagation evaluate them
+//! once their inputs are known.
+//!
+//! Template leakage gives a probability to every value of a word. Each value `v` of probability
+//! `p` becomes the soft clause "the word is not `v`" of weight `-ln p`, so that the cheapest
+//! assignment of a MaxSAT solver is the most likely one. Weights are in thousandths, relative
+//! to the most likely value, and impossible values are excluded by hard clauses.
+//!
+//! A fault is the XOR of a correct word with a difference, whose bits the fault model restricts.
+//! The faulty run itself is compiled by `Compiler::compile_fault`.
+//!
+//! A leakage specification lists observations of bits named like the words of a description,
+//! with absolute rounds, one per line:
+//!
+//! ```text
+//! collision,A4#1#3#1#8#,A4#1#3#9#16#,
+//! bits,A4#1#3#1#4#,1011,
+//! ```
+use super::description::VarRef;
 use super::generic::Clause;
+use std::fmt;
 
 pub struct Leakage {
     leakage_type: LeakageType,
     clauses: Vec<Clause>,
+    soft: Vec<(u64, Clause)>,
+    encoding: Encoding,
+    tolerance: u32,
+    aux: usize,
 }
 
 pub enum LeakageType {
@@ -13,11 +42,388 @@ pub enum LeakageType {
     Fault,
 }
 
+/// Cardinality encodings for Hamming weight leakage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    /// Sinz's sequential counter, `O(n k)` clauses for a bound `k`.
+    SequentialCounter,
+    /// Batcher's odd-even merge sort, `O(n log^2 n)` comparators.
+    SortingNetwork,
+    /// Bailleux and Boufkhad's totalizer, a tree of unary adders.
+    Totalizer,
+}
+
+/// What a fault does to the bits it hits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultModel {
+    /// Exactly one bit of the range flips.
+    BitFlip,
+    /// The byte in the range takes a random value, different from the correct one.
+    RandomByte,
+    /// The nibble in the range takes a random value, different from the correct one.
+    RandomNibble,
+}
+
+/// A fault on bits `lo..=hi` (from 1) of word `index` of `name` in round `round`, right after
+/// the operation which writes the word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub name: String,
+    pub round: i32,
+    pub index: i32,
+    pub bits: (u32, u32),
+    pub model: FaultModel,
+}
+
+/// A parsed leakage specification.
+#[derive(Debug, PartialEq)]
+pub struct LeakageSpec {
+    pub observations: Vec<Observation>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Observation {
+    /// `collision,A,B,`: two words of the same width are equal.
+    Collision { a: VarRef, b: VarRef, line: usize },
+    /// `bits,A,0110,`: the bits of a word, most significant first.
+    PartialBit {
+        var: VarRef,
+        values: Vec<bool>,
+        line: usize,
+    },
+}
+
+/// Template probabilities whose number is not that of the values of the word.
+#[derive(Debug, PartialEq)]
+pub struct InvalidTemplate {
+    pub bits: usize,
+    pub probabilities: usize,
+}
+
+impl fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} probabilities for a word of {} bits",
+            self.probabilities, self.bits
+        )
+    }
+}
+
+impl std::error::Error for InvalidTemplate {}
+
+fn aux_name(n: usize) -> String {
+    format!("hw#{}#", n)
+}
+
+/// `n` for the auxiliary variable `hw#n#`.
+fn aux_index(name: &str) -> Option<usize> {
+    name.strip_prefix("hw#")?.strip_suffix('#')?.parse().ok()
+}
+
+/// A bit of a counter, either known in advance or a named variable.
+#[derive(Debug, Clone, PartialEq)]
+enum Bit {
+    Const(bool),
+    Var(String),
+}
+
 impl Leakage {
     pub fn new(leakage_type: LeakageType) -> Self {
         Leakage {
             leakage_type,
             clauses: vec![],
+            soft: vec![],
+            encoding: Encoding::SequentialCounter,
+            tolerance: 0,
+            aux: 0,
+        }
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Accept weights off by up to `tolerance`, for noisy classifications.
+    pub fn with_tolerance(mut self, tolerance: u32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Constrain the number of true bits among `bits` to `weight`.
+    ///
+    /// Auxiliary variables are named `hw#n#`, numbered from 1 in each leakage. Adding several
+    /// leakages to one instance takes `renumber`, which the `leak` methods do.
+    pub fn add_hamming_weight(&mut self, bits: &[String], weight: u32) {
+        assert!(
+            matches!(self.leakage_type, LeakageType::HammingWeight),
+            "not a Hamming weight leakage"
+        );
+        let inputs: Vec<Bit> = bits.iter().map(|name| Bit::Var(name.clone())).collect();
+        let bound = (weight + self.tolerance + 1) as usize;
+        let count = match self.encoding {
+            Encoding::SequentialCounter => self.sequential_counter(&inputs, bound),
+            Encoding::SortingNetwork => self.sorting_network(&inputs),
+            Encoding::Totalizer => self.totalizer(&inputs),
+        };
+        // `at_least(j)` is output `j` of the counter, with `at_least(0)` true.
+        let at_least = |j: usize| match j {
+            0 => Bit::Const(true),
+            _ => count.get(j - 1).cloned().unwrap_or(Bit::Const(false)),
+        };
+        let lower = weight.saturating_sub(self.tolerance) as usize;
+        self.clause(&[(at_least(lower), true)]);
+        self.clause(&[(at_least(bound), false)]);
+    }
+
+    /// Constrain the difference `bits` between a faulty and a correct word to `model`.
+    pub fn add_fault(&mut self, bits: &[String], model: FaultModel) {
+        assert!(
+            matches!(self.leakage_type, LeakageType::Fault),
+            "not a fault leakage"
+        );
+        let bits: Vec<Bit> = bits.iter().map(|name| Bit::Var(name.clone())).collect();
+        // Some bit of the difference is set under every model.
+        let any: Vec<(Bit, bool)> = bits.iter().map(|bit| (bit.clone(), true)).collect();
+        self.clause(&any);
+        if model == FaultModel::BitFlip {
+            for (i, a) in bits.iter().enumerate() {
+                for b in bits[i + 1..].iter() {
+                    self.clause(&[(a.clone(), false), (b.clone(), false)]);
+                }
+            }
+        }
+    }
+
+    /// Constrain two words to be equal, such as the inputs of two colliding S-boxes.
+    pub fn add_collision(&mut self, a: &[String], b: &[String]) {
+        assert!(
+            matches!(self.leakage_type, LeakageType::Collision),
+            "not a collision leakage"
+        );
+        for (a, b) in a.iter().zip(b.iter()) {
+            // a ^ !b is true when both are equal.
+            self.clauses
+                .push(Clause::new(true, vec![(a, true), (b, false)]));
+        }
+    }
+
+    /// Fix some bits to known values.
+    pub fn add_partial_bits(&mut self, bits: &[String], values: &[bool]) {
+        assert!(
+            matches!(self.leakage_type, LeakageType::PartialBit),
+            "not a partial bit leakage"
+        );
+        for (bit, &value) in bits.iter().zip(values.iter()) {
+            self.clauses.push(Clause::new(false, vec![(bit, value)]));
+        }
+    }
+
+    /// Weigh the values of the word `bits` by their probabilities, such as the output of a
+    /// template classifier, where `probabilities[v]` is for the value `v` of the bits read most
+    /// significant first. There must be exactly one probability per value.
+    pub fn add_template(
+        &mut self,
+        bits: &[String],
+        probabilities: &[f64],
+    ) -> Result<(), InvalidTemplate> {
+        assert!(
+            matches!(self.leakage_type, LeakageType::Template),
+            "not a template leakage"
+        );
+        if bits.len() >= usize::BITS as usize || probabilities.len() != 1 << bits.len() {
+            return Err(InvalidTemplate {
+                bits: bits.len(),
+                probabilities: probabilities.len(),
+            });
+        }
+        let best = probabilities.iter().cloned().fold(0.0, f64::max);
+        let n = bits.len();
+        for (value, &p) in probabilities.iter().enumerate() {
+            // Any bit differing from `value` satisfies the clause.
+            let lits: Vec<(&str, bool)> = bits
+                .iter()
+                .enumerate()
+                .map(|(i, bit)| (bit.as_str(), (value >> (n - 1 - i)) & 1 == 0))
+                .collect();
+            if p <= 0.0 {
+                self.clauses.push(Clause::new(false, lits));
+                continue;
+            }
+            let weight = ((best / p).ln() * 1000.0).round() as u64;
+            if weight > 0 {
+                self.soft.push((weight, Clause::new(false, lits)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    /// Soft clauses with their weights, only produced by templates.
+    pub fn soft_clauses(&self) -> &[(u64, Clause)] {
+        &self.soft
+    }
+
+    /// The hard clauses, see `into_parts` to keep the soft ones.
+    pub fn into_clauses(self) -> Vec<Clause> {
+        self.clauses
+    }
+
+    /// The hard and the soft clauses.
+    pub fn into_parts(self) -> (Vec<Clause>, Vec<(u64, Clause)>) {
+        (self.clauses, self.soft)
+    }
+
+    /// Number the auxiliary variables after the `used` ones of an instance, returning how many
+    /// the instance uses with these.
+    pub fn renumber(&mut self, used: usize) -> usize {
+        let rename = |name: &str| match aux_index(name) {
+            Some(n) => aux_name(n + used),
+            None => name.to_string(),
+        };
+        if used > 0 {
+            let clauses = std::mem::take(&mut self.clauses);
+            self.clauses = clauses.into_iter().map(|c| c.rename(rename)).collect();
+            let soft = std::mem::take(&mut self.soft);
+            self.soft = soft
+                .into_iter()
+                .map(|(w, c)| (w, c.rename(rename)))
+                .collect();
+        }
+        used + self.aux
+    }
+
+    fn fresh(&mut self) -> Bit {
+        self.aux += 1;
+        Bit::Var(aux_name(self.aux))
+    }
+
+    /// Add a clause where `(bit, true)` is a positive literal. Constant literals are folded.
+    fn clause(&mut self, lits: &[(Bit, bool)]) {
+        let mut names = vec![];
+        for (bit, value) in lits.iter() {
+            match bit {
+                Bit::Const(b) if b == value => return,
+                Bit::Const(_) => (),
+                Bit::Var(name) => names.push((name.as_str(), *value)),
+            }
+        }
+        self.clauses.push(Clause::new(false, names));
+    }
+
+    fn or(&mut self, a: &Bit, b: &Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(true), _) | (_, Bit::Const(true)) => Bit::Const(true),
+            (Bit::Const(false), x) | (x, Bit::Const(false)) => x.clone(),
+            _ => {
+                let out = self.fresh();
+                self.clause(&[(a.clone(), false), (out.clone(), true)]);
+                self.clause(&[(b.clone(), false), (out.clone(), true)]);
+                self.clause(&[(out.clone(), false), (a.clone(), true), (b.clone(), true)]);
+                out
+            }
+        }
+    }
+
+    fn and(&mut self, a: &Bit, b: &Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), x) | (x, Bit::Const(true)) => x.clone(),
+            _ => {
+                let out = self.fresh();
+                self.clause(&[(out.clone(), false), (a.clone(), true)]);
+                self.clause(&[(out.clone(), false), (b.clone(), true)]);
+                self.clause(&[(out.clone(), true), (a.clone(), false), (b.clone(), false)]);
+                out
+            }
+        }
+    }
+
+    /// Unary count of `inputs` up to `bound`, with `s[j] = s[j] | (x & s[j - 1])` for each `x`.
+    fn sequential_counter(&mut self, inputs: &[Bit], bound: usize) -> Vec<Bit> {
+        let bound = bound.min(inputs.len());
+        let mut count = vec![Bit::Const(false); bound];
+        for x in inputs.iter() {
+            for j in (0..bound).rev() {
+                let carry = match j {
+                    0 => x.clone(),
+                    _ => {
+                        let below = count[j - 1].clone();
+                        self.and(x, &below)
+                    }
+                };
+                let current = count[j].clone();
+                count[j] = self.or(&current, &carry);
+            }
+        }
+        count
+    }
+
+    /// Sort `inputs` in decreasing order with comparators, padded to a power of two.
+    fn sorting_network(&mut self, inputs: &[Bit]) -> Vec<Bit> {
+        let n = inputs.len().next_power_of_two();
+        let mut wires = inputs.to_vec();
+        wires.resize(n, Bit::Const(false));
+        let mut p = 1;
+        while p < n {
+            let mut k = p;
+            while k >= 1 {
+                let mut j = k % p;
+                while j + k < n {
+                    for i in 0..k.min(n - j - k) {
+                        if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                            let (a, b) = (wires[i + j].clone(), wires[i + j + k].clone());
+                            wires[i + j] = self.or(&a, &b);
+                            wires[i + j + k] = self.and(&a, &b);
+                        }
+                    }
+                    j += 2 * k;
+                }
+                k /= 2;
+            }
+            p *= 2;
+        }
+        wires.truncate(inputs.len());
+        wires
+    }
+
+    /// Unary count of `inputs` as a balanced tree of unary adders.
+    fn totalizer(&mut self, inputs: &[Bit]) -> Vec<Bit> {
+        if inputs.len() <= 1 {
+            return inputs.to_vec();
+        }
+        let (left, right) = inputs.split_at(inputs.len() / 2);
+        let a = self.totalizer(left);
+        let b = self.totalizer(right);
+        let sum: Vec<Bit> = (0..a.len() + b.len()).map(|_| self.fresh()).collect();
+        // With `a[0]`, `b[0]` and `sum[0]` standing for a count of at least 0, and counts past
+        // the end being false: a[i] & b[j] -> sum[i + j] and sum[i + j + 1] -> a[i + 1] | b[j + 1].
+        let get = |v: &[Bit], i: usize| match i {
+            0 => Bit::Const(true),
+            _ => v.get(i - 1).cloned().unwrap_or(Bit::Const(false)),
+        };
+        for i in 0..=a.len() {
+            for j in 0..=b.len() {
+                if i + j > 0 {
+                    self.clause(&[
+                        (get(&a, i), false),
+                        (get(&b, j), false),
+                        (get(&sum, i + j), true),
+                    ]);
+                }
+                if i + j < sum.len() {
+                    self.clause(&[
+                        (get(&a, i + 1), true),
+                        (get(&b, j + 1), true),
+                        (get(&sum, i + j + 1), false),
+                    ]);
+                }
+            }
         }
+        sum
     }
 }
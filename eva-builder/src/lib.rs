@@ -1,4 +1,13 @@
+pub mod anf;
+pub mod compiler;
 pub mod constant;
+pub mod description;
 pub mod generic;
+pub mod interpreter;
 pub mod leakage;
+pub mod maxsat;
 pub mod parser;
+pub mod preprocess;
+pub mod sasca;
+pub mod smt;
+pub mod symbolic;
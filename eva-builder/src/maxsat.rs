@@ -0,0 +1,127 @@
+//! A small exact MaxSAT solver, standing in for a real one on toy instances.
+//!
+//! It is a depth-first branch and bound with unit propagation over the OR and XOR clauses, which
+//! branches on the variables in the order of their numbers. Instances whose first variables
+//! determine all the others, like a key byte followed by the cipher, stay tractable.
+use super::generic::{Instance, UnknownVariable};
+
+/// An assignment of least cost, where `model[v - 1]` is the value of variable `v`.
+#[derive(Debug, PartialEq)]
+pub struct Solution {
+    pub cost: u64,
+    pub model: Vec<bool>,
+}
+
+struct Search {
+    hard: Vec<(bool, Vec<(u32, bool)>)>,
+    soft: Vec<(u64, Vec<(u32, bool)>)>,
+    /// Hard clauses of each variable.
+    occurs: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    best: Option<Solution>,
+}
+
+/// Satisfy the hard clauses while violating soft clauses of least total weight, or return
+/// `None` if the hard clauses are unsatisfiable. A clause over a name which is not a variable of
+/// the instance is an error.
+pub fn solve(instance: &Instance) -> Result<Option<Solution>, UnknownVariable> {
+    let hard = instance.numbered()?;
+    let mut occurs = vec![vec![]; instance.variables.len()];
+    for (i, (_, lits)) in hard.iter().enumerate() {
+        for &(var, _) in lits.iter() {
+            occurs[var as usize - 1].push(i);
+        }
+    }
+    let mut search = Search {
+        hard,
+        soft: instance.numbered_soft()?,
+        occurs,
+        values: vec![None; instance.variables.len()],
+        best: None,
+    };
+    let mut trail = vec![];
+    let all: Vec<usize> = (0..search.hard.len()).collect();
+    if search.propagate(all, &mut trail) {
+        search.branch();
+    }
+    Ok(search.best)
+}
+
+impl Search {
+    fn branch(&mut self) {
+        let cost = self.cost();
+        if matches!(&self.best, Some(best) if best.cost <= cost) {
+            return;
+        }
+        let var = match self.values.iter().position(|v| v.is_none()) {
+            Some(var) => var,
+            None => {
+                let model = self.values.iter().map(|v| v.unwrap()).collect();
+                self.best = Some(Solution { cost, model });
+                return;
+            }
+        };
+        for &value in [false, true].iter() {
+            let mut trail = vec![var];
+            self.values[var] = Some(value);
+            if self.propagate(self.occurs[var].clone(), &mut trail) {
+                self.branch();
+            }
+            for &var in trail.iter() {
+                self.values[var] = None;
+            }
+        }
+    }
+
+    /// Weight of the soft clauses which are already false.
+    fn cost(&self) -> u64 {
+        self.soft
+            .iter()
+            .filter(|(_, lits)| {
+                lits.iter()
+                    .all(|&(var, value)| self.values[var as usize - 1] == Some(!value))
+            })
+            .map(|(weight, _)| weight)
+            .sum()
+    }
+
+    /// Propagate from the clauses `pending`, recording assigned variables in `trail`. Returns
+    /// false on a conflict.
+    fn propagate(&mut self, mut pending: Vec<usize>, trail: &mut Vec<usize>) -> bool {
+        while let Some(i) = pending.pop() {
+            let (xor, lits) = &self.hard[i];
+            let mut unknown = None;
+            let mut unknowns = 0;
+            let mut parity = false;
+            let mut satisfied = false;
+            for &(var, value) in lits.iter() {
+                match self.values[var as usize - 1] {
+                    Some(v) => {
+                        parity ^= v == value;
+                        satisfied |= v == value;
+                    }
+                    None => {
+                        unknowns += 1;
+                        unknown = Some((var, value));
+                    }
+                }
+            }
+            let implied = match (xor, unknowns, unknown) {
+                (false, _, _) if satisfied => None,
+                (false, 0, _) => return false,
+                (false, 1, Some((var, value))) => Some((var, value)),
+                (true, 0, _) if !parity => return false,
+                // The literal must make the parity true.
+                (true, 1, Some((var, value))) => Some((var, value != parity)),
+                _ => None,
+            };
+            if let Some((var, value)) = implied {
+                let var = var as usize - 1;
+                self.values[var] = Some(value);
+                trail.push(var);
+                pending.extend(self.occurs[var].iter().cloned());
+            }
+        }
+        true
+    }
+}
@@ -1,4 +1,19 @@
-use super::constant::{Constant, ConstantArr, ConstantMat};
+use super::constant::{Constant, ConstantArr, ConstantMat, ConstantTable};
+use super::description::{Description, Opcode, Operation, Statement, VarRef};
+use super::generic::{Clause, Instance, SolverOutput, VarId};
+use super::leakage::{LeakageSpec, Observation};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An error in a description, at a 1-based line and column.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
 
 impl Constant {
     pub fn new(line: &str) -> Self {
@@ -45,3 +60,658 @@ impl From<&str> for ConstantMat {
         }
     }
 }
+
+impl ParseError {
+    fn new(line: usize, column: usize, message: &str) -> Self {
+        ParseError {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+impl FromStr for Description {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let mut statements = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let fields = split_fields(line, i + 1)?;
+            if fields.is_empty() {
+                continue;
+            }
+            let statement = parse_statement(&fields, i + 1)?;
+            if let Statement::Repeat(_) = statement {
+                match statements.last() {
+                    Some(Statement::Operation(_)) => (),
+                    _ => return Err(ParseError::new(i + 1, 1, "CP must follow a round")),
+                }
+            }
+            statements.push(statement);
+        }
+        Ok(Description { statements })
+    }
+}
+
+impl FromStr for VarRef {
+    type Err = ParseError;
+
+    /// Errors are reported on line 1, with columns relative to the reference.
+    fn from_str(field: &str) -> Result<Self, ParseError> {
+        parse_var(field, 1, 1)
+    }
+}
+
+impl FromStr for VarId {
+    type Err = ParseError;
+
+    /// Read a bit name `NAME#ROUND#INDEX#BIT#`, whose name may be that of a faulty run.
+    fn from_str(field: &str) -> Result<Self, ParseError> {
+        let parts: Vec<&str> = field.split('#').collect();
+        match parts[..] {
+            [name, round, index, bit, ""] if !name.is_empty() => {
+                let column = name.len() + 2;
+                let round = parse_number((column, round), 1)?;
+                let column = column + parts[1].len() + 1;
+                let index = parse_number((column, index), 1)?;
+                let column = column + parts[2].len() + 1;
+                let bit = parse_number((column, bit), 1)?;
+                Ok(VarId::new(name, round, index, bit))
+            }
+            _ => Err(ParseError::new(1, 1, "expected `NAME#ROUND#INDEX#BIT#`")),
+        }
+    }
+}
+
+/// Split a line at commas into trimmed fields with their columns. A trailing comma is allowed.
+fn split_fields(line: &str, line_no: usize) -> Result<Vec<(usize, &str)>, ParseError> {
+    if line.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let mut fields = vec![];
+    let mut column = 1;
+    for field in line.split(',') {
+        let leading = field.len() - field.trim_start().len();
+        fields.push((column + leading, field.trim()));
+        column += field.chars().count() + 1;
+    }
+    if let Some((_, "")) = fields.last() {
+        fields.pop();
+    }
+    match fields.iter().find(|(_, f)| f.is_empty()) {
+        Some(&(column, _)) => Err(ParseError::new(line_no, column, "empty field")),
+        None => Ok(fields),
+    }
+}
+
+fn parse_number<T: FromStr>(field: (usize, &str), line: usize) -> Result<T, ParseError> {
+    field
+        .1
+        .parse()
+        .map_err(|_| ParseError::new(line, field.0, &format!("invalid number `{}`", field.1)))
+}
+
+fn parse_statement(fields: &[(usize, &str)], line: usize) -> Result<Statement, ParseError> {
+    if fields[0].1 == "CP" {
+        return match fields.len() {
+            2 => Ok(Statement::Repeat(parse_number(fields[1], line)?)),
+            1 => Err(ParseError::new(line, fields[0].0, "CP needs a count")),
+            _ => Err(ParseError::new(
+                line,
+                fields[2].0,
+                "unexpected field after CP",
+            )),
+        };
+    }
+    let round = parse_number(fields[0], line)?;
+    let (column, name) = *fields
+        .get(1)
+        .ok_or_else(|| ParseError::new(line, fields[0].0, "missing opcode"))?;
+    let opcode = parse_opcode(name)
+        .ok_or_else(|| ParseError::new(line, column, &format!("unknown opcode `{}`", name)))?;
+
+    let mut operands = vec![];
+    let mut params = vec![];
+    for &(col, field) in fields[2..].iter() {
+        if field.contains('#') {
+            if !params.is_empty() {
+                return Err(ParseError::new(line, col, "operand after parameters"));
+            }
+            operands.push(parse_var(field, line, col)?);
+        } else {
+            params.extend(field.split(';').filter(|p| !p.is_empty()).map(String::from));
+        }
+    }
+
+    let arity_ok = match opcode {
+        Opcode::Xor => operands.len() >= 2,
+        Opcode::Constant => operands.len() == 3,
+        _ => operands.len() == 2,
+    };
+    if !arity_ok {
+        return Err(ParseError::new(
+            line,
+            column,
+            &format!("wrong number of operands for `{}`", name),
+        ));
+    }
+    if (opcode == Opcode::Sbox) == params.is_empty() {
+        let message = match opcode {
+            Opcode::Sbox => "missing S-box names",
+            _ => "unexpected parameters",
+        };
+        return Err(ParseError::new(line, column, message));
+    }
+    Ok(Statement::Operation(Operation {
+        round,
+        opcode,
+        operands,
+        params,
+        line,
+    }))
+}
+
+fn parse_opcode(name: &str) -> Option<Opcode> {
+    let number = |prefix: &str| name[prefix.len()..].parse().ok();
+    match name {
+        "x" => Some(Opcode::Xor),
+        "s" => Some(Opcode::Sbox),
+        "rx" => Some(Opcode::Assign),
+        "c" => Some(Opcode::Constant),
+        _ if name.starts_with('p') => number("p").map(Opcode::Permutation),
+        _ if name.starts_with('m') => number("m").map(Opcode::Matrix),
+        _ => None,
+    }
+}
+
+fn parse_var(field: &str, line: usize, column: usize) -> Result<VarRef, ParseError> {
+    let invalid = |offset: usize, message: &str| ParseError::new(line, column + offset, message);
+    if !field.ends_with('#') {
+        return Err(invalid(field.len(), "variable must end with `#`"));
+    }
+    let parts: Vec<&str> = field[..field.len() - 1].split('#').collect();
+    if parts.len() != 3 && parts.len() != 5 {
+        return Err(invalid(
+            0,
+            "expected `NAME#ROUND#INDEX#` or `NAME#ROUND#INDEX#LO#HI#`",
+        ));
+    }
+    let name = parts[0];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(invalid(0, &format!("invalid variable name `{}`", name)));
+    }
+    let mut numbers = vec![];
+    let mut offset = name.len() + 1;
+    for part in parts[1..].iter() {
+        numbers.push(parse_number::<i32>((column + offset, part), line)?);
+        offset += part.len() + 1;
+    }
+    let bits = match numbers[2..] {
+        [lo, hi] if lo >= 1 && lo <= hi => Some((lo as u32, hi as u32)),
+        [_, _] => {
+            return Err(invalid(
+                name.len() + parts[1].len() + parts[2].len() + 3,
+                "invalid bit range",
+            ))
+        }
+        _ => None,
+    };
+    Ok(VarRef {
+        name: name.to_string(),
+        round: numbers[0],
+        index: numbers[1],
+        bits,
+    })
+}
+
+impl FromStr for LeakageSpec {
+    type Err = ParseError;
+
+    /// Read lines `collision,A,B,` and `bits,A,0110,`, where rounds are absolute. `#` starts a
+    /// comment only at the beginning of a line, since variable names contain it.
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let mut observations = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let fields = split_fields(line, line_no)?;
+            if fields.is_empty() {
+                continue;
+            }
+            let (column, kind) = fields[0];
+            if fields.len() != 3 {
+                return Err(ParseError::new(line_no, column, "expected 2 fields"));
+            }
+            let var = |(column, field): (usize, &str)| -> Result<VarRef, ParseError> {
+                let var = parse_var(field, line_no, column)?;
+                if var.round < 0 {
+                    return Err(ParseError::new(line_no, column, "rounds are absolute"));
+                }
+                Ok(var)
+            };
+            let observation = match kind {
+                "collision" => Observation::Collision {
+                    a: var(fields[1])?,
+                    b: var(fields[2])?,
+                    line: line_no,
+                },
+                "bits" => {
+                    let var = var(fields[1])?;
+                    let (column, digits) = fields[2];
+                    let mut values = vec![];
+                    for (j, c) in digits.chars().enumerate() {
+                        match c {
+                            '0' | '1' => values.push(c == '1'),
+                            _ => {
+                                return Err(ParseError::new(line_no, column + j, "expected a bit"))
+                            }
+                        }
+                    }
+                    if let Some((lo, hi)) = var.bits {
+                        if values.len() != (hi - lo + 1) as usize {
+                            return Err(ParseError::new(
+                                line_no,
+                                column,
+                                "bits do not match the range",
+                            ));
+                        }
+                    }
+                    Observation::PartialBit {
+                        var,
+                        values,
+                        line: line_no,
+                    }
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        line_no,
+                        column,
+                        &format!("unknown leakage `{}`", kind),
+                    ))
+                }
+            };
+            observations.push(observation);
+        }
+        Ok(LeakageSpec { observations })
+    }
+}
+
+impl FromStr for Instance {
+    type Err = ParseError;
+
+    /// Read DIMACS CNF with CryptoMiniSat's `x` lines for XOR clauses, such as written by
+    /// `Instance::to_dimacs`. Variables keep their numbers and the names of their `c var N name`
+    /// comments, the others being named `vN`.
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let mut header: Option<(usize, u32, usize)> = None;
+        let mut names: HashMap<u32, (usize, String)> = HashMap::new();
+        let mut clauses: Vec<(bool, Vec<(u32, bool)>)> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let mut words = split_words(line);
+            match words.first() {
+                None => continue,
+                Some(&(_, "c")) => {
+                    if let [_, (_, "var"), var, (_, name)] = words[..] {
+                        names.insert(parse_number(var, line_no)?, (line_no, name.to_string()));
+                    }
+                    continue;
+                }
+                Some(&(column, "p")) => {
+                    match words[..] {
+                        [_, (_, "cnf"), vars, count] if header.is_none() => {
+                            header = Some((
+                                line_no,
+                                parse_number(vars, line_no)?,
+                                parse_number(count, line_no)?,
+                            ));
+                        }
+                        _ => return Err(ParseError::new(line_no, column, "invalid header")),
+                    }
+                    continue;
+                }
+                Some(&(column, _)) if header.is_none() => {
+                    return Err(ParseError::new(line_no, column, "missing header"));
+                }
+                _ => (),
+            }
+            let vars = header.map(|(_, vars, _)| vars).unwrap();
+            let xor = words[0].1.starts_with('x');
+            if words[0].1 == "x" {
+                words.remove(0);
+            } else if xor {
+                words[0] = (words[0].0 + 1, &words[0].1[1..]);
+            }
+            if words.is_empty() {
+                return Err(ParseError::new(line_no, line.len() + 1, "missing final 0"));
+            }
+            clauses.push(parse_clause(xor, &words, vars, line_no)?);
+        }
+        let (line, vars, count) = header.ok_or_else(|| ParseError::new(1, 1, "missing header"))?;
+        if clauses.len() != count {
+            return Err(ParseError::new(
+                line,
+                1,
+                &format!("{} clauses instead of {}", clauses.len(), count),
+            ));
+        }
+
+        let mut instance = Instance::new();
+        let mut by_number = vec![];
+        for var in 1..=vars {
+            let (line, name) = names
+                .remove(&var)
+                .unwrap_or_else(|| (line, format!("v{}", var)));
+            if !instance.insert_variable(&name, var) {
+                return Err(ParseError::new(
+                    line,
+                    1,
+                    &format!("variable `{}` is named twice", name),
+                ));
+            }
+            by_number.push(name);
+        }
+        if let Some((&var, &(line, _))) = names.iter().next() {
+            return Err(ParseError::new(
+                line,
+                1,
+                &format!("variable {} beyond the header", var),
+            ));
+        }
+        for (xor, lits) in clauses.into_iter() {
+            let lits = lits
+                .iter()
+                .map(|&(var, value)| (by_number[var as usize - 1].as_str(), value))
+                .collect();
+            instance.add_clause(Clause::new(xor, lits));
+        }
+        Ok(instance)
+    }
+}
+
+/// Split a line at whitespace into words with their columns.
+fn split_words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                words.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    words
+}
+
+/// Read the literals of a DIMACS clause up to its final 0, from at least one word.
+fn parse_clause(
+    xor: bool,
+    words: &[(usize, &str)],
+    vars: u32,
+    line: usize,
+) -> Result<(bool, Vec<(u32, bool)>), ParseError> {
+    let mut lits = vec![];
+    for (i, &word) in words.iter().enumerate() {
+        let lit: i64 = parse_number(word, line)?;
+        if lit == 0 {
+            return match words.get(i + 1) {
+                Some(&(column, _)) => Err(ParseError::new(line, column, "text after the final 0")),
+                None => Ok((xor, lits)),
+            };
+        }
+        if lit.unsigned_abs() > vars as u64 {
+            return Err(ParseError::new(
+                line,
+                word.0,
+                &format!("variable {} beyond the header", lit.unsigned_abs()),
+            ));
+        }
+        lits.push((lit.unsigned_abs() as u32, lit > 0));
+    }
+    let (column, word) = words[words.len() - 1];
+    Err(ParseError::new(
+        line,
+        column + word.len(),
+        "missing final 0",
+    ))
+}
+
+impl FromStr for SolverOutput {
+    type Err = ParseError;
+
+    /// Read the `s` and `v` lines of a solver's output, ignoring all others.
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let mut output = SolverOutput {
+            satisfiable: None,
+            values: HashMap::new(),
+        };
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let words = split_words(line);
+            match words.split_first() {
+                Some((&(_, "s"), status)) => {
+                    let status: Vec<&str> = status.iter().map(|&(_, word)| word).collect();
+                    output.satisfiable = match status[..] {
+                        ["SATISFIABLE"] => Some(true),
+                        ["UNSATISFIABLE"] => Some(false),
+                        ["UNKNOWN"] => None,
+                        _ => return Err(ParseError::new(line_no, 3, "unknown status")),
+                    };
+                }
+                Some((&(_, "v"), values)) => {
+                    for &value in values.iter() {
+                        let lit: i64 = parse_number(value, line_no)?;
+                        if lit != 0 {
+                            output.values.insert(lit.unsigned_abs() as u32, lit > 0);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// A constant being read, with the position of every element.
+struct PendingConstant {
+    name: String,
+    line: usize,
+    matrix: bool,
+    rows: Vec<Vec<(usize, usize, u8)>>,
+    expect_element: bool,
+}
+
+impl FromStr for ConstantTable {
+    type Err = ParseError;
+
+    /// Read lines `name=[1,2,3]` or `name=[1,2;3,4]`. Values may span lines, and `#` starts a
+    /// comment.
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let mut constants = HashMap::new();
+        let mut pending: Option<PendingConstant> = None;
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap();
+            let mut start = 0;
+            if pending.is_none() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let eq = line
+                    .find('=')
+                    .ok_or_else(|| ParseError::new(line_no, 1, "expected `name=[...]`"))?;
+                let name = line[..eq].trim();
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(ParseError::new(line_no, 1, "invalid constant name"));
+                }
+                let open = eq + 1 + (line[eq + 1..].len() - line[eq + 1..].trim_start().len());
+                if !line[open..].starts_with('[') {
+                    return Err(ParseError::new(line_no, open + 1, "expected `[`"));
+                }
+                pending = Some(PendingConstant {
+                    name: name.to_string(),
+                    line: line_no,
+                    matrix: name.starts_with('m'),
+                    rows: vec![vec![]],
+                    expect_element: true,
+                });
+                start = open + 1;
+            }
+            let current = pending.as_mut().unwrap();
+            let mut chars = line.char_indices().skip(start).peekable();
+            while let Some((j, c)) = chars.next() {
+                let column = j + 1;
+                match c {
+                    '0'..='9' => {
+                        let mut end = j + 1;
+                        while chars.next_if(|(_, c)| c.is_ascii_digit()).is_some() {
+                            end += 1;
+                        }
+                        if !current.expect_element {
+                            return Err(ParseError::new(line_no, column, "expected `,`"));
+                        }
+                        let value = line[j..end].parse().map_err(|_| {
+                            ParseError::new(
+                                line_no,
+                                column,
+                                &format!("{} does not fit a byte", &line[j..end]),
+                            )
+                        })?;
+                        current
+                            .rows
+                            .last_mut()
+                            .unwrap()
+                            .push((line_no, column, value));
+                        current.expect_element = false;
+                    }
+                    ',' | ';' | ']' if current.expect_element => {
+                        return Err(ParseError::new(line_no, column, "missing element"));
+                    }
+                    ',' => current.expect_element = true,
+                    ';' => {
+                        current.matrix = true;
+                        current.rows.push(vec![]);
+                        current.expect_element = true;
+                    }
+                    ']' => {
+                        if let Some((k, _)) = chars.find(|(_, c)| !c.is_whitespace()) {
+                            return Err(ParseError::new(
+                                line_no,
+                                k + 1,
+                                "unexpected text after `]`",
+                            ));
+                        }
+                        let constant = validate(pending.take().unwrap())?;
+                        if constants.contains_key(constant.name()) {
+                            return Err(ParseError::new(
+                                line_no,
+                                1,
+                                &format!("{} is defined twice", constant.name()),
+                            ));
+                        }
+                        constants.insert(constant.name().to_string(), constant);
+                        break;
+                    }
+                    c if c.is_whitespace() => (),
+                    c => {
+                        return Err(ParseError::new(
+                            line_no,
+                            column,
+                            &format!("unexpected `{}`", c),
+                        ))
+                    }
+                }
+            }
+        }
+        match pending {
+            Some(constant) => Err(ParseError::new(
+                constant.line,
+                1,
+                &format!("{} is missing its `]`", constant.name),
+            )),
+            None => Ok(ConstantTable { constants }),
+        }
+    }
+}
+
+/// Check a constant against the kind given by its name: `p*` permutations, `s*` S-box ANF
+/// vectors, `c*` bits and `m*` matrices of input bit indices.
+fn validate(constant: PendingConstant) -> Result<Constant, ParseError> {
+    let PendingConstant {
+        name,
+        line,
+        matrix,
+        rows,
+        ..
+    } = constant;
+    let error = |(line, column): (usize, usize), message: String| ParseError {
+        line,
+        column,
+        message,
+    };
+    if matrix {
+        for row in rows.iter() {
+            let mut seen = vec![];
+            for &(l, c, index) in row.iter() {
+                if index as usize >= rows.len() {
+                    return Err(error(
+                        (l, c),
+                        format!("index {} outside of {}", index, name),
+                    ));
+                }
+                if seen.contains(&index) {
+                    return Err(error((l, c), format!("index {} repeated in a row", index)));
+                }
+                seen.push(index);
+            }
+        }
+        let value = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|(_, _, v)| v).collect())
+            .collect();
+        return Ok(Constant::Matrix(ConstantMat { name, value }));
+    }
+
+    let elements = &rows[0];
+    let len = elements.len();
+    if name.starts_with('p') {
+        let mut seen = vec![false; len];
+        for &(l, c, value) in elements.iter() {
+            match seen.get_mut(value as usize) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(error((l, c), format!("{} is not a permutation", name))),
+            }
+        }
+    }
+    if name.starts_with('s') && !(1..16).any(|n| n << n == len) {
+        return Err(error(
+            (line, 1),
+            format!("{} has {} elements, expected n * 2^n", name, len),
+        ));
+    }
+    if name.starts_with('s') || name.starts_with('c') {
+        if let Some(&(l, c, _)) = elements.iter().find(|(_, _, v)| *v > 1) {
+            return Err(error((l, c), format!("{} must only hold bits", name)));
+        }
+    }
+    let value = elements.iter().map(|&(_, _, v)| v).collect();
+    Ok(Constant::Array(ConstantArr { name, value }))
+}
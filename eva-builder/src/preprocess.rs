@@ -0,0 +1,388 @@
+//! Simplification of instances before they go to a solver.
+//!
+//! Units are propagated and variables equal to another variable or its negation are merged.
+//! Gaussian elimination over the XOR clauses finds further units and equivalences, and variables
+//! occurring only in a few XOR clauses are eliminated. Every removed variable is recorded as the
+//! XOR of a constant and remaining variables, which lifts models of the simplified instance back
+//! to the original one.
+use super::generic::{Clause, Instance, UnknownVariable};
+use std::collections::HashMap;
+
+/// A simplified instance with what it takes to lift its models back.
+pub struct Simplified {
+    pub instance: Instance,
+    /// Original number of each variable of `instance`, in the order of their numbers.
+    kept: Vec<u32>,
+    /// Removed variables in the order of removal, each the XOR of a constant and other variables.
+    steps: Vec<(u32, bool, Vec<u32>)>,
+    vars: usize,
+}
+
+impl Simplified {
+    /// A model of the original instance from one of the simplified instance, where
+    /// `model[v - 1]` is the value of variable `v`. Variables which were dropped with all their
+    /// clauses are false.
+    pub fn lift(&self, model: &[bool]) -> Vec<bool> {
+        let mut values = vec![false; self.vars];
+        for (&var, &value) in self.kept.iter().zip(model.iter()) {
+            values[var as usize - 1] = value;
+        }
+        for (var, constant, others) in self.steps.iter().rev() {
+            values[*var as usize - 1] = others
+                .iter()
+                .fold(*constant, |acc, &other| acc ^ values[other as usize - 1]);
+        }
+        values
+    }
+}
+
+/// How much Gaussian elimination may grow the XOR clauses.
+const FILL_IN: usize = 4;
+
+/// An XOR clause as the variables whose XOR is the right hand side, in increasing order.
+type Row = (Vec<u32>, bool);
+
+struct Simplifier {
+    or: Vec<Vec<(u32, bool)>>,
+    xor: Vec<Row>,
+    soft: Vec<(u64, Vec<(u32, bool)>)>,
+    /// Each merged variable as a constant XOR another variable, or a constant alone.
+    merged: Vec<Option<(bool, Option<u32>)>>,
+    steps: Vec<(u32, bool, Vec<u32>)>,
+}
+
+/// Simplify the instance, or return `None` if it turns out unsatisfiable. Soft clauses keep
+/// their weights, an empty one standing for a cost which cannot be avoided. A clause over a name
+/// which is not a variable of the instance is an error.
+pub fn simplify(instance: &Instance) -> Result<Option<Simplified>, UnknownVariable> {
+    let mut xor = vec![];
+    let mut or = vec![];
+    for (is_xor, lits) in instance.numbered()?.into_iter() {
+        if is_xor {
+            let rhs = lits.iter().fold(true, |acc, &(_, value)| acc ^ !value);
+            xor.push((lits.iter().map(|&(var, _)| var).collect(), rhs));
+        } else {
+            or.push(lits);
+        }
+    }
+    let simplifier = Simplifier {
+        or,
+        xor,
+        soft: instance.numbered_soft()?,
+        merged: vec![None; instance.variables.len()],
+        steps: vec![],
+    };
+    Ok(simplifier.run(instance))
+}
+
+impl Simplifier {
+    /// Simplify until nothing changes, or return `None` if the clauses turn out unsatisfiable.
+    fn run(mut self, instance: &Instance) -> Option<Simplified> {
+        loop {
+            self.rewrite();
+            if !self.merge_short()? {
+                continue;
+            }
+            if self.gauss()? {
+                continue;
+            }
+            if !self.eliminate() {
+                break;
+            }
+        }
+        Some(self.finish(instance))
+    }
+
+    /// The value of `var` as a constant XOR a remaining variable, or a constant alone.
+    fn resolve(&self, mut var: u32) -> (bool, Option<u32>) {
+        let mut parity = false;
+        while let Some((constant, other)) = self.merged[var as usize - 1] {
+            parity ^= constant;
+            match other {
+                Some(other) => var = other,
+                None => return (parity, None),
+            }
+        }
+        (parity, Some(var))
+    }
+
+    /// Require `a` XOR `b` to be `rhs`, with `b` false if `None`. Returns false on a conflict.
+    fn merge(&mut self, a: u32, b: Option<u32>, rhs: bool) -> bool {
+        let (pa, ra) = self.resolve(a);
+        let (pb, rb) = match b {
+            Some(b) => self.resolve(b),
+            None => (false, None),
+        };
+        let rhs = rhs ^ pa ^ pb;
+        let (var, other) = match (ra, rb) {
+            (None, None) => return !rhs,
+            (Some(a), Some(b)) if a == b => return !rhs,
+            (Some(var), other) | (other, Some(var)) => (var, other),
+        };
+        self.merged[var as usize - 1] = Some((rhs, other));
+        self.steps.push((var, rhs, other.into_iter().collect()));
+        true
+    }
+
+    /// Rewrite every clause over the remaining variables, dropping satisfied clauses.
+    fn rewrite(&mut self) {
+        self.or = self
+            .or
+            .iter()
+            .filter_map(|lits| self.rewrite_or(lits))
+            .collect();
+        self.xor = self
+            .xor
+            .iter()
+            .map(|row| self.rewrite_xor(row))
+            .filter(|(vars, rhs)| !vars.is_empty() || *rhs)
+            .collect();
+        self.soft = self
+            .soft
+            .iter()
+            .filter_map(|(weight, lits)| Some((*weight, self.rewrite_or(lits)?)))
+            .collect();
+    }
+
+    /// An OR clause over the remaining variables, or `None` if it is satisfied.
+    fn rewrite_or(&self, lits: &[(u32, bool)]) -> Option<Vec<(u32, bool)>> {
+        let mut rewritten = vec![];
+        for &(var, value) in lits.iter() {
+            match self.resolve(var) {
+                (parity, None) if parity == value => return None,
+                (_, None) => (),
+                (parity, Some(var)) => rewritten.push((var, value ^ parity)),
+            }
+        }
+        rewritten.sort_unstable();
+        rewritten.dedup();
+        if rewritten.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None;
+        }
+        Some(rewritten)
+    }
+
+    fn rewrite_xor(&self, (vars, rhs): &Row) -> Row {
+        let mut row = (vec![], *rhs);
+        for &var in vars.iter() {
+            let (parity, var) = self.resolve(var);
+            row.1 ^= parity;
+            row.0.extend(var);
+        }
+        row.0.sort_unstable();
+        let mut cancelled: Vec<u32> = vec![];
+        for var in row.0.into_iter() {
+            if cancelled.last() == Some(&var) {
+                cancelled.pop();
+            } else {
+                cancelled.push(var);
+            }
+        }
+        (cancelled, row.1)
+    }
+
+    /// Merge the variables of unit clauses and of XOR clauses over at most two variables.
+    /// Returns `Some(true)` if there were none, or `None` on a conflict.
+    fn merge_short(&mut self) -> Option<bool> {
+        if self.or.iter().any(|lits| lits.is_empty()) {
+            return None;
+        }
+        let mut short: Vec<(u32, Option<u32>, bool)> = vec![];
+        for lits in self.or.iter() {
+            if let [(var, value)] = lits[..] {
+                short.push((var, None, value));
+            }
+        }
+        for (vars, rhs) in self.xor.iter() {
+            match vars[..] {
+                [] => return None,
+                [var] => short.push((var, None, *rhs)),
+                [a, b] => short.push((a, Some(b), *rhs)),
+                _ => (),
+            }
+        }
+        for &(a, b, rhs) in short.iter() {
+            if !self.merge(a, b, rhs) {
+                return None;
+            }
+        }
+        Some(short.is_empty())
+    }
+
+    /// Bring the XOR clauses to reduced row echelon form and merge the variables of its short
+    /// rows. Returns `Some(true)` if there were any, or `None` if the XOR clauses contradict.
+    /// Elimination stops early once the rows grow to `FILL_IN` times their original size, which
+    /// the linear layers of a few rounds with an unknown key quickly reach.
+    fn gauss(&mut self) -> Option<bool> {
+        let limit = FILL_IN * self.xor.iter().map(|(vars, _)| vars.len()).sum::<usize>();
+        let mut size = 0;
+        let mut rows: Vec<Row> = vec![];
+        let mut pivots = HashMap::new();
+        for row in self.xor.iter() {
+            if size > limit {
+                break;
+            }
+            let mut row = row.clone();
+            let hits: Vec<usize> = row
+                .0
+                .iter()
+                .filter_map(|v| pivots.get(v).cloned())
+                .collect();
+            for i in hits.into_iter() {
+                add(&mut row, &rows[i]);
+            }
+            let pivot = match row.0.last() {
+                Some(&pivot) => pivot,
+                None if row.1 => return None,
+                None => continue,
+            };
+            for other in rows.iter_mut() {
+                if other.0.binary_search(&pivot).is_ok() {
+                    size -= other.0.len();
+                    add(other, &row);
+                    size += other.0.len();
+                }
+            }
+            size += row.0.len();
+            pivots.insert(pivot, rows.len());
+            rows.push(row);
+        }
+        let mut found = false;
+        for (vars, rhs) in rows.into_iter() {
+            if vars.len() <= 2 {
+                found = true;
+                if !self.merge(vars[0], vars.get(1).cloned(), rhs) {
+                    return None;
+                }
+            }
+        }
+        Some(found)
+    }
+
+    /// Eliminate variables occurring only in XOR clauses, by adding one of their clauses to the
+    /// others, when this does not make the clauses longer in total. Returns true if any were.
+    fn eliminate(&mut self) -> bool {
+        let vars = self.merged.len();
+        let mut in_or = vec![false; vars];
+        let soft = self.soft.iter().map(|(_, lits)| lits);
+        for lits in self.or.iter().chain(soft) {
+            for &(var, _) in lits.iter() {
+                in_or[var as usize - 1] = true;
+            }
+        }
+        let mut occurs: Vec<Vec<usize>> = vec![vec![]; vars];
+        for (i, (vars, _)) in self.xor.iter().enumerate() {
+            for &var in vars.iter() {
+                occurs[var as usize - 1].push(i);
+            }
+        }
+        let mut changed = false;
+        for var in 1..=vars as u32 {
+            let rows = occurs[var as usize - 1].clone();
+            if in_or[var as usize - 1] || rows.is_empty() {
+                continue;
+            }
+            let def = *rows.iter().min_by_key(|&&i| self.xor[i].0.len()).unwrap();
+            let len = self.xor[def].0.len();
+            if (rows.len() - 1) * len.saturating_sub(2) > len {
+                continue;
+            }
+            let row = std::mem::take(&mut self.xor[def]);
+            for &other in row.0.iter() {
+                occurs[other as usize - 1].retain(|&i| i != def);
+            }
+            for &i in rows.iter().filter(|&&i| i != def) {
+                add(&mut self.xor[i], &row);
+                for &other in row.0.iter() {
+                    let list = &mut occurs[other as usize - 1];
+                    if self.xor[i].0.binary_search(&other).is_err() {
+                        list.retain(|&j| j != i);
+                    } else if !list.contains(&i) {
+                        list.push(i);
+                    }
+                }
+            }
+            let others = row.0.into_iter().filter(|&other| other != var).collect();
+            self.steps.push((var, row.1, others));
+            changed = true;
+        }
+        changed
+    }
+
+    fn finish(self, instance: &Instance) -> Simplified {
+        let mut names = vec![""; instance.variables.len()];
+        for (name, &var) in instance.variables.iter() {
+            names[var as usize - 1] = name;
+        }
+        let mut used = vec![false; names.len()];
+        let soft = self.soft.iter().map(|(_, lits)| lits);
+        for &(var, _) in self.or.iter().chain(soft).flatten() {
+            used[var as usize - 1] = true;
+        }
+        for &var in self.xor.iter().flat_map(|(vars, _)| vars) {
+            used[var as usize - 1] = true;
+        }
+
+        let mut simplified = Instance::new();
+        let kept: Vec<u32> = (1..=names.len() as u32)
+            .filter(|&var| used[var as usize - 1])
+            .collect();
+        for &var in kept.iter() {
+            simplified.add_variable(names[var as usize - 1]);
+        }
+        let literals = |lits: &[(u32, bool)]| -> Vec<(&str, bool)> {
+            lits.iter()
+                .map(|&(var, value)| (names[var as usize - 1], value))
+                .collect()
+        };
+        for lits in self.or.iter() {
+            simplified.add_clause(Clause::new(false, literals(lits)));
+        }
+        for (vars, rhs) in self.xor.iter() {
+            let mut lits: Vec<(&str, bool)> = vars
+                .iter()
+                .map(|&var| (names[var as usize - 1], true))
+                .collect();
+            lits[0].1 = *rhs;
+            simplified.add_clause(Clause::new(true, lits));
+        }
+        for (weight, lits) in self.soft.iter() {
+            simplified.add_soft_clause(*weight, Clause::new(false, literals(lits)));
+        }
+        Simplified {
+            instance: simplified,
+            kept,
+            steps: self.steps,
+            vars: names.len(),
+        }
+    }
+}
+
+/// Add `other` to `row`, cancelling the variables they share.
+fn add(row: &mut Row, other: &Row) {
+    let mut sum = Vec::with_capacity(row.0.len() + other.0.len());
+    let (mut i, mut j) = (0, 0);
+    while i < row.0.len() || j < other.0.len() {
+        match (row.0.get(i), other.0.get(j)) {
+            (Some(a), Some(b)) if a == b => {
+                i += 1;
+                j += 1;
+            }
+            (Some(&a), Some(&b)) if a < b => {
+                sum.push(a);
+                i += 1;
+            }
+            (Some(&a), None) => {
+                sum.push(a);
+                i += 1;
+            }
+            (_, Some(&b)) => {
+                sum.push(b);
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    row.0 = sum;
+    row.1 ^= other.1;
+}
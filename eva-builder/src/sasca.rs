@@ -0,0 +1,429 @@
+//! Soft analytical side-channel attacks, by belief propagation on the factor graph of a
+//! description.
+//!
+//! Words are cut into cells of the size of the S-boxes, and every cell is a variable with a
+//! distribution over its values, read most significant bit first. S-boxes and bit permutations
+//! inside a cell become table factors, XORs become XOR factors, copies and permutations of
+//! whole cells merge variables, and binary matrices are split like MixColumns into a linear
+//! table per pair of cells followed by XORs. Leakage enters as priors on the cells, and loopy
+//! belief propagation turns them into marginals, e.g. of the key bytes.
+use super::compiler::{locate, unroll, CompileError, Layout};
+use super::constant::Constant;
+use super::description::{Description, Opcode, Operation, VarRef};
+use super::interpreter::sbox;
+use std::collections::HashMap;
+
+/// A cell `(name, round, index, cell)`, with cells numbered from 1.
+type CellName = (String, i32, i32, u32);
+
+enum Factor {
+    /// `output = table[input]`.
+    Table {
+        input: usize,
+        output: usize,
+        table: Vec<usize>,
+    },
+    /// `a ^ b = c`.
+    Xor { a: usize, b: usize, c: usize },
+}
+
+pub struct FactorGraph {
+    layout: Layout,
+    /// Bits per cell.
+    width: u32,
+    cells: HashMap<CellName, usize>,
+    /// Union-find over the variables, for copies.
+    parent: Vec<usize>,
+    priors: Vec<Vec<f64>>,
+    factors: Vec<Factor>,
+    /// Beliefs of the representatives of the variables after `propagate`.
+    beliefs: HashMap<usize, Vec<f64>>,
+}
+
+impl FactorGraph {
+    /// A graph with cells of `width` bits.
+    pub fn new(constants: Vec<Constant>, width: u32) -> Self {
+        FactorGraph {
+            layout: Layout::new(constants),
+            width,
+            cells: HashMap::new(),
+            parent: vec![],
+            priors: vec![],
+            factors: vec![],
+            beliefs: HashMap::new(),
+        }
+    }
+
+    /// Unroll the rounds of `desc` and add their factors.
+    pub fn add(&mut self, desc: &Description) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            self.add_operation(*round, op)?;
+        }
+        Ok(())
+    }
+
+    /// Multiply the prior of a cell by a leakage distribution over its values.
+    pub fn observe(&mut self, name: &str, round: i32, index: i32, cell: u32, likelihood: &[f64]) {
+        let var = self.cell((name.to_string(), round, index, cell));
+        for (p, l) in self.priors[var].iter_mut().zip(likelihood.iter()) {
+            *p *= l;
+        }
+    }
+
+    /// Fix the cells of a known word, such as a plaintext, given one value per cell.
+    pub fn fix(&mut self, name: &str, round: i32, index: i32, values: &[usize]) {
+        for (c, &value) in values.iter().enumerate() {
+            let mut likelihood = vec![0.0; 1 << self.width];
+            likelihood[value] = 1.0;
+            self.observe(name, round, index, c as u32 + 1, &likelihood);
+        }
+    }
+
+    /// Run `iterations` rounds of loopy belief propagation, flooding all messages each time.
+    pub fn propagate(&mut self, iterations: usize) {
+        let size = 1 << self.width;
+        let roots: Vec<usize> = (0..self.parent.len()).map(|v| self.find(v)).collect();
+        let mut priors: HashMap<usize, Vec<f64>> = HashMap::new();
+        for (v, prior) in self.priors.iter().enumerate() {
+            let merged = priors.entry(roots[v]).or_insert_with(|| vec![1.0; size]);
+            for (m, p) in merged.iter_mut().zip(prior.iter()) {
+                *m *= p;
+            }
+        }
+        let slots: Vec<Vec<usize>> = self
+            .factors
+            .iter()
+            .map(|factor| match factor {
+                Factor::Table { input, output, .. } => vec![roots[*input], roots[*output]],
+                Factor::Xor { a, b, c } => vec![roots[*a], roots[*b], roots[*c]],
+            })
+            .collect();
+        let mut edges: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (f, vars) in slots.iter().enumerate() {
+            for (s, &v) in vars.iter().enumerate() {
+                edges.entry(v).or_default().push((f, s));
+            }
+        }
+        let uniform = vec![1.0 / size as f64; size];
+        let mut to_var: Vec<Vec<Vec<f64>>> = slots
+            .iter()
+            .map(|s| vec![uniform.clone(); s.len()])
+            .collect();
+        let mut to_factor = to_var.clone();
+
+        for _ in 0..iterations {
+            for (v, incident) in edges.iter() {
+                for &(f, s) in incident.iter() {
+                    let mut message = priors[v].clone();
+                    for &(g, t) in incident.iter().filter(|&&e| e != (f, s)) {
+                        multiply(&mut message, &to_var[g][t]);
+                    }
+                    normalize(&mut message);
+                    to_factor[f][s] = message;
+                }
+            }
+            for (f, factor) in self.factors.iter().enumerate() {
+                let incoming = &to_factor[f];
+                to_var[f] = match factor {
+                    Factor::Table { table, .. } => {
+                        let mut forward = vec![0.0; size];
+                        for (x, &y) in table.iter().enumerate() {
+                            forward[y] += incoming[0][x];
+                        }
+                        let backward = table.iter().map(|&y| incoming[1][y]).collect();
+                        vec![backward, forward]
+                    }
+                    Factor::Xor { .. } => vec![
+                        xor_convolution(&incoming[1], &incoming[2]),
+                        xor_convolution(&incoming[0], &incoming[2]),
+                        xor_convolution(&incoming[0], &incoming[1]),
+                    ],
+                };
+                for message in to_var[f].iter_mut() {
+                    normalize(message);
+                }
+            }
+        }
+
+        self.beliefs = priors;
+        for (v, belief) in self.beliefs.iter_mut() {
+            for &(f, s) in edges.get(v).into_iter().flatten() {
+                multiply(belief, &to_var[f][s]);
+            }
+            normalize(belief);
+        }
+    }
+
+    /// Marginal distribution of a cell after `propagate`.
+    pub fn marginal(&self, name: &str, round: i32, index: i32, cell: u32) -> Option<&[f64]> {
+        let var = *self.cells.get(&(name.to_string(), round, index, cell))?;
+        self.beliefs.get(&self.find(var)).map(|b| b.as_slice())
+    }
+
+    fn add_operation(&mut self, round: u32, op: &Operation) -> Result<(), CompileError> {
+        let w = self.width as usize;
+        let mut operands = vec![];
+        for var in op.operands.iter() {
+            operands.push(self.operand(var, round, op.line)?);
+        }
+        let output = operands.pop().unwrap();
+        match op.opcode {
+            Opcode::Xor if operands.len() == 1 => self.copy(&operands[0], &output),
+            Opcode::Assign => self.copy(&operands[0], &output),
+            Opcode::Xor => {
+                for (c, &out) in output.iter().enumerate() {
+                    let inputs: Vec<usize> = operands.iter().map(|cells| cells[c]).collect();
+                    self.xor(&inputs, out);
+                }
+            }
+            Opcode::Permutation(n) => {
+                let perm = self
+                    .layout
+                    .permutation(n, operands[0].len() * w, op.line)?
+                    .to_vec();
+                for (c, &out) in output.iter().enumerate() {
+                    let sources = &perm[c * w..(c + 1) * w];
+                    let d = sources[0] as usize / w;
+                    if sources.iter().any(|&s| s as usize / w != d) {
+                        return Err(CompileError::new(op.line, &format!("p{} splits cells", n)));
+                    }
+                    let rows: Vec<Vec<usize>> =
+                        sources.iter().map(|&s| vec![s as usize - d * w]).collect();
+                    self.linear(&rows, operands[0][d], out);
+                }
+            }
+            Opcode::Matrix(n) => {
+                let rows = self.layout.matrix(&format!("m{}", n), op.line)?.to_vec();
+                for (c, &out) in output.iter().enumerate() {
+                    let mut terms = vec![];
+                    for (d, &input) in operands[0].iter().enumerate() {
+                        // The block of the matrix from input cell `d` to output cell `c`.
+                        let block: Vec<Vec<usize>> = rows[c * w..(c + 1) * w]
+                            .iter()
+                            .map(|row| {
+                                row.iter()
+                                    .map(|&i| i as usize)
+                                    .filter(|i| i / w == d)
+                                    .map(|i| i - d * w)
+                                    .collect()
+                            })
+                            .collect();
+                        if block.iter().any(|row| !row.is_empty()) {
+                            let term = self.fresh();
+                            self.linear(&block, input, term);
+                            terms.push(term);
+                        }
+                    }
+                    match terms.len() {
+                        0 => self.fix_var(out, 0),
+                        _ => self.xor(&terms, out),
+                    }
+                }
+            }
+            Opcode::Constant => {
+                let bits = self
+                    .layout
+                    .round_constant(round, output.len() * w, op.line)?
+                    .to_vec();
+                for (c, &out) in output.iter().enumerate() {
+                    let value = bits[c * w..(c + 1) * w]
+                        .iter()
+                        .fold(0, |acc, &b| (acc << 1) | b as usize);
+                    self.fix_var(operands[1][c], value);
+                    self.factors.push(Factor::Xor {
+                        a: operands[0][c],
+                        b: operands[1][c],
+                        c: out,
+                    });
+                }
+            }
+            Opcode::Sbox => {
+                if op.params.len() != output.len() {
+                    return Err(CompileError::new(op.line, "S-boxes do not match the cells"));
+                }
+                for (c, name) in op.params.iter().enumerate() {
+                    if self.layout.sbox_size(name, op.line)? != w {
+                        return Err(CompileError::new(
+                            op.line,
+                            &format!("{} does not match the cells", name),
+                        ));
+                    }
+                    let anf = self.layout.array(name, op.line)?;
+                    let table = (0..1 << w)
+                        .map(|x| from_bits(&sbox(anf, &to_bits(x, w))))
+                        .collect();
+                    self.factors.push(Factor::Table {
+                        input: operands[0][c],
+                        output: output[c],
+                        table,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Cells of an operand, which must cover whole cells.
+    fn operand(
+        &mut self,
+        var: &VarRef,
+        round: u32,
+        line: usize,
+    ) -> Result<Vec<usize>, CompileError> {
+        let (round, index) = locate(var, round, line)?;
+        let (lo, hi) = match var.bits {
+            Some(range) => range,
+            None => match self.layout.widths.get(&(var.name.clone(), index)) {
+                Some(&width) => (1, width),
+                None => {
+                    return Err(CompileError::new(
+                        line,
+                        &format!("cannot infer the width of {}", var.name),
+                    ))
+                }
+            },
+        };
+        if (lo - 1) % self.width != 0 || hi % self.width != 0 {
+            return Err(CompileError::new(
+                line,
+                &format!("bits of {} do not fall on cells", var.name),
+            ));
+        }
+        Ok(((lo - 1) / self.width + 1..=hi / self.width)
+            .map(|c| self.cell((var.name.clone(), round, index, c)))
+            .collect())
+    }
+
+    fn cell(&mut self, name: CellName) -> usize {
+        if let Some(&var) = self.cells.get(&name) {
+            return var;
+        }
+        let var = self.fresh();
+        self.cells.insert(name, var);
+        var
+    }
+
+    fn fresh(&mut self) -> usize {
+        self.parent.push(self.parent.len());
+        self.priors.push(vec![1.0; 1 << self.width]);
+        self.parent.len() - 1
+    }
+
+    fn find(&self, mut var: usize) -> usize {
+        while self.parent[var] != var {
+            var = self.parent[var];
+        }
+        var
+    }
+
+    fn copy(&mut self, input: &[usize], output: &[usize]) {
+        for (&a, &b) in input.iter().zip(output.iter()) {
+            let (a, b) = (self.find(a), self.find(b));
+            self.parent[b] = a;
+        }
+    }
+
+    fn fix_var(&mut self, var: usize, value: usize) {
+        for (x, p) in self.priors[var].iter_mut().enumerate() {
+            if x != value {
+                *p = 0.0;
+            }
+        }
+    }
+
+    /// `out` is the XOR of `inputs`, chained through new variables.
+    fn xor(&mut self, inputs: &[usize], out: usize) {
+        if inputs.len() == 1 {
+            return self.copy(inputs, &[out]);
+        }
+        let mut acc = inputs[0];
+        for (k, &input) in inputs[1..].iter().enumerate() {
+            let c = if k + 2 == inputs.len() {
+                out
+            } else {
+                self.fresh()
+            };
+            self.factors.push(Factor::Xor {
+                a: acc,
+                b: input,
+                c,
+            });
+            acc = c;
+        }
+    }
+
+    /// `out` is a linear map of `input`, where bit `k` of `out` is the XOR of the bits `rows[k]`
+    /// of `input`, counted from the most significant.
+    fn linear(&mut self, rows: &[Vec<usize>], input: usize, out: usize) {
+        let w = self.width as usize;
+        if rows.iter().enumerate().all(|(k, row)| row[..] == [k]) {
+            return self.copy(&[input], &[out]);
+        }
+        let table = (0..1 << w)
+            .map(|x| {
+                let bits = to_bits(x, w);
+                let out: Vec<bool> = rows
+                    .iter()
+                    .map(|row| row.iter().fold(false, |acc, &i| acc ^ bits[i]))
+                    .collect();
+                from_bits(&out)
+            })
+            .collect();
+        self.factors.push(Factor::Table {
+            input,
+            output: out,
+            table,
+        });
+    }
+}
+
+fn to_bits(x: usize, w: usize) -> Vec<bool> {
+    (0..w).map(|k| (x >> (w - 1 - k)) & 1 == 1).collect()
+}
+
+fn from_bits(bits: &[bool]) -> usize {
+    bits.iter().fold(0, |acc, &b| (acc << 1) | b as usize)
+}
+
+fn multiply(a: &mut [f64], b: &[f64]) {
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        *x *= y;
+    }
+}
+
+/// Scale to a sum of 1, or to the uniform distribution if everything vanished.
+fn normalize(a: &mut [f64]) {
+    let sum: f64 = a.iter().sum();
+    let n = a.len() as f64;
+    for x in a.iter_mut() {
+        *x = if sum > 0.0 { *x / sum } else { 1.0 / n };
+    }
+}
+
+/// `out[z]` is the sum of `f[x] g[x ^ z]`, through the Walsh-Hadamard transform.
+fn xor_convolution(f: &[f64], g: &[f64]) -> Vec<f64> {
+    let mut f = f.to_vec();
+    let mut g = g.to_vec();
+    walsh_hadamard(&mut f);
+    walsh_hadamard(&mut g);
+    multiply(&mut f, &g);
+    walsh_hadamard(&mut f);
+    let n = f.len() as f64;
+    f.iter().map(|x| (x / n).max(0.0)).collect()
+}
+
+fn walsh_hadamard(a: &mut [f64]) {
+    let mut h = 1;
+    while h < a.len() {
+        for i in (0..a.len()).step_by(2 * h) {
+            for j in i..i + h {
+                let (x, y) = (a[j], a[j + h]);
+                a[j] = x + y;
+                a[j + h] = x - y;
+            }
+        }
+        h *= 2;
+    }
+}
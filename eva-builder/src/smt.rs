@@ -0,0 +1,363 @@
+//! Export of cipher descriptions as SMT-LIB2 bit-vector problems.
+//!
+//! Every word `NAME#ROUND#INDEX#` becomes a bit-vector constant of its width, whose most
+//! significant bit is bit 1 of the compiler. XORs and copies become `bvxor` and equalities,
+//! permutations and matrices concatenations of extracted bits, and S-boxes either functions
+//! defined by a chain of `ite` or arrays holding their table. Clauses of a leakage are asserted
+//! over the extracted bits, their auxiliary variables being declared as Booleans.
+use super::compiler::{locate, unroll, CompileError, Layout};
+use super::constant::Constant;
+use super::description::{Description, Opcode, Operation, VarRef};
+use super::interpreter::sbox;
+use super::leakage::{Leakage, LeakageSpec, Observation};
+use std::collections::HashSet;
+
+/// How S-boxes are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SboxStyle {
+    /// A function defined by a chain of `ite` over all inputs, staying in QF_BV.
+    Ite,
+    /// An array constrained to the table, which takes QF_ABV.
+    Array,
+}
+
+/// Accumulates the assertions of one or more descriptions over shared constants.
+pub struct SmtWriter {
+    layout: Layout,
+    style: SboxStyle,
+    /// Declarations of words, Booleans and S-boxes, in order of first use.
+    declarations: Vec<String>,
+    declared: HashSet<String>,
+    assertions: Vec<String>,
+    /// Auxiliary variables of the leakages added so far.
+    aux: usize,
+}
+
+/// A bit-vector literal such as `#b0110`, most significant bit first.
+fn literal(bits: &[bool]) -> String {
+    let digits: String = bits.iter().map(|&b| if b { '1' } else { '0' }).collect();
+    format!("#b{}", digits)
+}
+
+/// Nest a binary operation over terms, which leaves a single term alone.
+fn nest(op: &str, terms: Vec<String>) -> String {
+    terms
+        .into_iter()
+        .reduce(|acc, term| format!("({} {} {})", op, acc, term))
+        .unwrap()
+}
+
+/// Bits `from` to `to` of a term of `width` bits, counting from 0 at the most significant bit.
+fn extract(term: &str, width: u32, from: u32, to: u32) -> String {
+    if from == 0 && to + 1 == width {
+        term.to_string()
+    } else {
+        format!(
+            "((_ extract {} {}) {})",
+            width - 1 - from,
+            width - 1 - to,
+            term
+        )
+    }
+}
+
+impl SmtWriter {
+    pub fn new(constants: Vec<Constant>) -> Self {
+        SmtWriter {
+            layout: Layout::new(constants),
+            style: SboxStyle::Ite,
+            declarations: vec![],
+            declared: HashSet::new(),
+            assertions: vec![],
+            aux: 0,
+        }
+    }
+
+    pub fn with_sbox_style(mut self, style: SboxStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Unroll the rounds of `desc` and add their assertions.
+    pub fn add(&mut self, desc: &Description) -> Result<(), CompileError> {
+        let ops = unroll(desc);
+        self.layout.infer_widths(&ops)?;
+        for (round, op) in ops.iter() {
+            self.add_operation(*round, op)?;
+        }
+        Ok(())
+    }
+
+    /// Fix the bits of a word, such as a known plaintext.
+    pub fn fix(&mut self, name: &str, round: i32, index: i32, bits: &[bool]) {
+        let word = self.word(name, round, index, bits.len() as u32);
+        self.assert_equal(&word, &literal(bits));
+    }
+
+    /// Assert the hard clauses of a leakage, whose bits are named like the bits of the
+    /// description. Soft clauses are left out.
+    pub fn leak(&mut self, mut leakage: Leakage) {
+        self.aux = leakage.renumber(self.aux);
+        for clause in leakage.into_clauses() {
+            let terms: Vec<String> = clause
+                .literals()
+                .into_iter()
+                .map(|(name, value)| {
+                    let term = self.boolean(name);
+                    if value {
+                        term
+                    } else {
+                        format!("(not {})", term)
+                    }
+                })
+                .collect();
+            let assertion = match (clause.is_xor(), terms.len()) {
+                (_, 0) => "false".to_string(),
+                (_, 1) => terms[0].clone(),
+                (true, _) => format!("(xor {})", terms.join(" ")),
+                (false, _) => format!("(or {})", terms.join(" ")),
+            };
+            self.assertions.push(format!("(assert {})", assertion));
+        }
+    }
+
+    /// Add the observations of a leakage specification.
+    pub fn observe(&mut self, spec: &LeakageSpec) -> Result<(), CompileError> {
+        for observation in spec.observations.iter() {
+            match observation {
+                Observation::Collision { a, b, line } => {
+                    let (a, width_a) = self.absolute(a, *line)?;
+                    let (b, width_b) = self.absolute(b, *line)?;
+                    if width_a != width_b {
+                        return Err(CompileError::new(*line, "words have different widths"));
+                    }
+                    self.assert_equal(&a, &b);
+                }
+                Observation::PartialBit { var, values, line } => {
+                    let (term, width) = self.absolute(var, *line)?;
+                    if width as usize != values.len() {
+                        return Err(CompileError::new(
+                            *line,
+                            &format!("expected {} bits", width),
+                        ));
+                    }
+                    self.assert_equal(&term, &literal(values));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The problem as an SMT-LIB2 script, which asks for a model of every word.
+    pub fn to_smt2(&self) -> String {
+        let logic = match self.style {
+            SboxStyle::Ite => "QF_BV",
+            SboxStyle::Array => "QF_ABV",
+        };
+        let mut smt = format!("(set-logic {})\n(set-option :produce-models true)\n", logic);
+        for line in self.declarations.iter().chain(self.assertions.iter()) {
+            smt.push_str(line);
+            smt.push('\n');
+        }
+        smt.push_str("(check-sat)\n(get-model)\n");
+        smt
+    }
+
+    fn add_operation(&mut self, round: u32, op: &Operation) -> Result<(), CompileError> {
+        let mut operands = vec![];
+        for var in op.operands.iter() {
+            operands.push(self.operand(var, round, op.line)?);
+        }
+        let (output, width) = operands.pop().unwrap();
+        let value = match op.opcode {
+            Opcode::Xor | Opcode::Assign => nest(
+                "bvxor",
+                operands.into_iter().map(|(term, _)| term).collect(),
+            ),
+            Opcode::Permutation(n) => {
+                let (input, input_width) = &operands[0];
+                let perm = self.layout.permutation(n, *input_width as usize, op.line)?;
+                let bits = perm
+                    .iter()
+                    .map(|&p| extract(input, *input_width, p as u32, p as u32))
+                    .collect();
+                nest("concat", bits)
+            }
+            Opcode::Matrix(n) => {
+                let (input, input_width) = &operands[0];
+                let rows = self.layout.matrix(&format!("m{}", n), op.line)?;
+                let mut bits = vec![];
+                for row in rows.iter() {
+                    let mut terms = vec![];
+                    for &i in row.iter() {
+                        if i as u32 >= *input_width {
+                            return Err(CompileError::new(
+                                op.line,
+                                &format!("m{} refers to bit {}", n, i),
+                            ));
+                        }
+                        terms.push(extract(input, *input_width, i as u32, i as u32));
+                    }
+                    if terms.is_empty() {
+                        terms.push("#b0".to_string());
+                    }
+                    bits.push(nest("bvxor", terms));
+                }
+                nest("concat", bits)
+            }
+            Opcode::Constant => {
+                let slice: Vec<bool> = self
+                    .layout
+                    .round_constant(round, width as usize, op.line)?
+                    .iter()
+                    .map(|&b| b != 0)
+                    .collect();
+                self.assert_equal(&operands[1].0, &literal(&slice));
+                nest("bvxor", vec![operands[0].0.clone(), operands[1].0.clone()])
+            }
+            Opcode::Sbox => {
+                let (input, input_width) = operands[0].clone();
+                let mut outputs = vec![];
+                let mut offset = 0;
+                for name in op.params.iter() {
+                    let n = self.layout.sbox_size(name, op.line)? as u32;
+                    self.declare_sbox(name, n, op.line)?;
+                    let bits = extract(&input, input_width, offset, offset + n - 1);
+                    outputs.push(match self.style {
+                        SboxStyle::Ite => format!("(|{}| {})", name, bits),
+                        SboxStyle::Array => format!("(select |{}| {})", name, bits),
+                    });
+                    offset += n;
+                }
+                nest("concat", outputs)
+            }
+        };
+        self.assert_equal(&output, &value);
+        Ok(())
+    }
+
+    fn assert_equal(&mut self, a: &str, b: &str) {
+        self.assertions.push(format!("(assert (= {} {}))", a, b));
+    }
+
+    /// The term of an operand made in `round`, with its width.
+    fn operand(
+        &mut self,
+        var: &VarRef,
+        round: u32,
+        line: usize,
+    ) -> Result<(String, u32), CompileError> {
+        let bits = self.layout.bits(var, round, line)?.len() as u32;
+        let (round, index) = locate(var, round, line)?;
+        let width = self.layout.widths[&(var.name.clone(), index)];
+        let word = self.word(&var.name, round, index, width);
+        match var.bits {
+            Some((lo, hi)) => Ok((extract(&word, width, lo - 1, hi - 1), bits)),
+            None => Ok((word, bits)),
+        }
+    }
+
+    /// The term of a reference with an absolute round, with its width.
+    fn absolute(&mut self, var: &VarRef, line: usize) -> Result<(String, u32), CompileError> {
+        let width = match self.layout.widths.get(&(var.name.clone(), var.index)) {
+            Some(&width) => width,
+            None => {
+                return Err(CompileError::new(
+                    line,
+                    &format!("unknown word {} {}", var.name, var.index),
+                ))
+            }
+        };
+        let word = self.word(&var.name, var.round, var.index, width);
+        match var.bits {
+            Some((lo, hi)) if hi <= width => {
+                Ok((extract(&word, width, lo - 1, hi - 1), hi - lo + 1))
+            }
+            Some(_) => Err(CompileError::new(line, "bit range beyond the width")),
+            None => Ok((word, width)),
+        }
+    }
+
+    /// The constant of a word, declared on first use.
+    fn word(&mut self, name: &str, round: i32, index: i32, width: u32) -> String {
+        let word = format!("|{}#{}#{}#|", name, round, index);
+        if self.declared.insert(word.clone()) {
+            self.declarations
+                .push(format!("(declare-const {} (_ BitVec {}))", word, width));
+        }
+        word
+    }
+
+    /// A bit of a leakage as a Boolean term: a bit of a word, or an auxiliary variable.
+    fn boolean(&mut self, name: &str) -> String {
+        let fields: Vec<&str> = name.split('#').collect();
+        if let [word, round, index, bit, ""] = fields[..] {
+            let parsed = (round.parse(), index.parse(), bit.parse::<u32>());
+            if let (Ok(round), Ok(index), Ok(bit)) = parsed {
+                let key = (word.to_string(), index);
+                if let Some(&width) = self.layout.widths.get(&key) {
+                    if bit >= 1 && bit <= width {
+                        let word = self.word(word, round, index, width);
+                        return format!("(= {} #b1)", extract(&word, width, bit - 1, bit - 1));
+                    }
+                }
+            }
+        }
+        let var = format!("|{}|", name);
+        if self.declared.insert(var.clone()) {
+            self.declarations
+                .push(format!("(declare-const {} Bool)", var));
+        }
+        var
+    }
+
+    /// Declare the S-box `name` over `n` bits from its ANF, unless it already is.
+    fn declare_sbox(&mut self, name: &str, n: u32, line: usize) -> Result<(), CompileError> {
+        let function = format!("|{}|", name);
+        if self.declared.contains(&function) {
+            return Ok(());
+        }
+        let anf = self.layout.array(name, line)?;
+        let table: Vec<(Vec<bool>, Vec<bool>)> = (0..1u32 << n)
+            .map(|x| {
+                let input: Vec<bool> = (0..n).map(|i| (x >> (n - 1 - i)) & 1 == 1).collect();
+                let output = sbox(anf, &input);
+                (input, output)
+            })
+            .collect();
+        match self.style {
+            SboxStyle::Ite => {
+                let (_, last) = table.last().unwrap();
+                let mut body = literal(last);
+                for (input, output) in table.iter().rev().skip(1) {
+                    body = format!(
+                        "(ite (= x {}) {} {})",
+                        literal(input),
+                        literal(output),
+                        body
+                    );
+                }
+                self.declarations.push(format!(
+                    "(define-fun {} ((x (_ BitVec {}))) (_ BitVec {}) {})",
+                    function, n, n, body
+                ));
+            }
+            SboxStyle::Array => {
+                self.declarations.push(format!(
+                    "(declare-const {} (Array (_ BitVec {}) (_ BitVec {})))",
+                    function, n, n
+                ));
+                for (input, output) in table.iter() {
+                    self.declarations.push(format!(
+                        "(assert (= (select {} {}) {}))",
+                        function,
+                        literal(input),
+                        literal(output)
+                    ));
+                }
+            }
+        }
+        self.declared.insert(function);
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+//! Symbolic execution of the eva-crypto ciphers.
+//!
+//! A `Word` stands in for a `u8` of a cipher state. Its bits are constants or literals of an
+//! instance being traced, so running a cipher on words written by `trace` records the equations
+//! of the cipher. Constants fold away, XORs become XOR clauses and ANDs become AND gates, and
+//! S-boxes are decomposed into their ANF.
+//!
+//! The key schedules of AES, PRESENT and SKINNY run on words through their `expand_key`, and LED
+//! uses its key directly, so the master key can be symbolic as well as the plaintext.
+//!
+//! ```
+//! use eva_builder::symbolic::{trace, Word};
+//! use eva_crypto::aes;
+//!
+//! let (_, instance) = trace(|| {
+//!     let key: Vec<Word> = (0..16).map(|i| Word::variable(&format!("K#{}", i), 8)).collect();
+//!     let plaintext = vec![Word::from(0); 16];
+//!     let ciphertext = aes::encrypt_block(&plaintext, &aes::expand_key(&key), &aes::SBOX);
+//!     for (i, word) in ciphertext.iter().enumerate() {
+//!         word.bind(&format!("C#{}", i));
+//!     }
+//! });
+//! assert!(instance.variables.contains_key("C#0#8#"));
+//! ```
+use super::generic::{Clause, Instance};
+use eva_crypto::generic::{Cell, Field, Ops, Permutation};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// A bit of a word, a constant or a literal of variable `v` which is negated when `false`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bit {
+    Const(bool),
+    Lit(u32, bool),
+}
+
+/// A symbolic byte, with the most significant bit first as in `expand_bits`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Word {
+    bits: [Bit; 8],
+}
+
+#[derive(Default)]
+struct Tracer {
+    instance: Instance,
+    names: Vec<String>,
+    gates: HashMap<(Bit, Bit), Bit>,
+}
+
+thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, collecting the equations produced by the words it handles into an instance.
+///
+/// Creating words, or combining words which are not constant, outside of `trace` panics.
+pub fn trace<R, F: FnOnce() -> R>(f: F) -> (R, Instance) {
+    TRACER.with(|t| *t.borrow_mut() = Some(Tracer::default()));
+    let result = f();
+    let tracer = TRACER.with(|t| t.borrow_mut().take()).unwrap();
+    (result, tracer.instance)
+}
+
+fn with_tracer<R, F: FnOnce(&mut Tracer) -> R>(f: F) -> R {
+    TRACER.with(|t| {
+        f(t.borrow_mut()
+            .as_mut()
+            .expect("symbolic words are only usable inside `trace`"))
+    })
+}
+
+impl Tracer {
+    fn variable(&mut self, name: &str) -> Bit {
+        self.instance.add_variable(name);
+        let var = self.instance.variables[name];
+        if var as usize > self.names.len() {
+            self.names.push(name.to_string());
+        }
+        Bit::Lit(var, true)
+    }
+
+    fn fresh(&mut self) -> Bit {
+        let name = format!("t#{}#", self.names.len() + 1);
+        self.variable(&name)
+    }
+
+    fn add_clause(&mut self, xor: bool, lits: &[(u32, bool)]) {
+        let names = &self.names;
+        let lits = lits
+            .iter()
+            .map(|&(var, value)| (names[var as usize - 1].as_str(), value))
+            .collect();
+        self.instance.add_clause(Clause::new(xor, lits));
+    }
+
+    /// XOR of `bits`, with a new variable for it unless it is a constant or a literal.
+    fn xor(&mut self, bits: &[Bit]) -> Bit {
+        let mut rhs = false;
+        let mut vars: Vec<u32> = vec![];
+        for &bit in bits.iter() {
+            match bit {
+                Bit::Const(value) => rhs ^= value,
+                Bit::Lit(var, value) => {
+                    rhs ^= !value;
+                    match vars.iter().position(|&v| v == var) {
+                        Some(i) => {
+                            vars.remove(i);
+                        }
+                        None => vars.push(var),
+                    }
+                }
+            }
+        }
+        match vars.len() {
+            0 => Bit::Const(rhs),
+            1 => Bit::Lit(vars[0], !rhs),
+            _ => {
+                let out = self.fresh();
+                if let Bit::Lit(var, _) = out {
+                    // out ^ vars = rhs, where a clause states that its XOR is true.
+                    let mut lits = vec![(var, rhs)];
+                    lits.extend(vars.iter().map(|&v| (v, true)));
+                    self.add_clause(true, &lits);
+                }
+                out
+            }
+        }
+    }
+
+    fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), x) | (x, Bit::Const(true)) => x,
+            (Bit::Lit(u, p), Bit::Lit(v, q)) if u == v => {
+                if p == q {
+                    a
+                } else {
+                    Bit::Const(false)
+                }
+            }
+            (Bit::Lit(u, p), Bit::Lit(v, q)) => {
+                let key = if u < v { (a, b) } else { (b, a) };
+                if let Some(&gate) = self.gates.get(&key) {
+                    return gate;
+                }
+                let gate = self.fresh();
+                if let Bit::Lit(g, _) = gate {
+                    self.add_clause(false, &[(g, false), (u, p)]);
+                    self.add_clause(false, &[(g, false), (v, q)]);
+                    self.add_clause(false, &[(g, true), (u, !p), (v, !q)]);
+                }
+                self.gates.insert(key, gate);
+                gate
+            }
+        }
+    }
+}
+
+impl Word {
+    /// A word of `width` new variables named `name#1#` to `name#width#`, in the low bits.
+    pub fn variable(name: &str, width: u8) -> Word {
+        let mut word = Word::from(0);
+        with_tracer(|t| {
+            for i in 0..width as usize {
+                word.bits[8 - width as usize + i] = t.variable(&format!("{}#{}#", name, i + 1));
+            }
+        });
+        word
+    }
+
+    pub fn bits(&self) -> &[Bit; 8] {
+        &self.bits
+    }
+
+    /// The value of the word if all its bits are constants.
+    pub fn value(&self) -> Option<u8> {
+        self.bits.iter().try_fold(0, |acc, bit| match bit {
+            Bit::Const(b) => Some((acc << 1) | *b as u8),
+            Bit::Lit(_, _) => None,
+        })
+    }
+
+    /// Give the bits of the word the names `name#1#` to `name#8#`, most significant first.
+    pub fn bind(&self, name: &str) {
+        with_tracer(|t| {
+            for (i, &bit) in self.bits.iter().enumerate() {
+                if let Bit::Lit(var, _) = t.variable(&format!("{}#{}#", name, i + 1)) {
+                    match bit {
+                        Bit::Const(value) => t.add_clause(false, &[(var, value)]),
+                        Bit::Lit(v, p) if v != var => t.add_clause(true, &[(var, !p), (v, true)]),
+                        Bit::Lit(_, _) => (),
+                    }
+                }
+            }
+        })
+    }
+
+    fn map2<F: FnMut(&mut Tracer, Bit, Bit) -> Bit>(&self, rhs: &Self, mut f: F) -> Self {
+        let mut out = *self;
+        match (self.value(), rhs.value()) {
+            (Some(_), Some(_)) => {
+                // No new variables, so no tracer needed.
+                let mut tracer = Tracer::default();
+                for i in 0..8 {
+                    out.bits[i] = f(&mut tracer, self.bits[i], rhs.bits[i]);
+                }
+            }
+            _ => with_tracer(|t| {
+                for i in 0..8 {
+                    out.bits[i] = f(t, self.bits[i], rhs.bits[i]);
+                }
+            }),
+        }
+        out
+    }
+}
+
+impl From<u8> for Word {
+    fn from(value: u8) -> Self {
+        let mut bits = [Bit::Const(false); 8];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = Bit::Const((value >> (7 - i)) & 1 == 1);
+        }
+        Word { bits }
+    }
+}
+
+impl Ops for Word {
+    fn lrot(&self) -> Self {
+        let mut out = *self;
+        out.bits.rotate_left(1);
+        out
+    }
+
+    fn rrot(&self) -> Self {
+        let mut out = *self;
+        out.bits.rotate_right(1);
+        out
+    }
+
+    fn xor(&self, rhs: &Self) -> Self {
+        self.map2(rhs, |t, a, b| t.xor(&[a, b]))
+    }
+
+    fn and(&self, rhs: &Self) -> Self {
+        self.map2(rhs, |t, a, b| t.and(a, b))
+    }
+
+    /// Multiplication in `field`, by shifts and additions. It stays linear when one operand is
+    /// a constant, and costs an AND gate per pair of bits otherwise.
+    fn gmul(&self, rhs: &Self, field: Field) -> Self {
+        let (a, b) = match (self.value(), rhs.value()) {
+            (Some(x), Some(y)) => return Word::from(x.gmul(&y, field)),
+            (Some(_), _) => (*rhs, *self),
+            _ => (*self, *rhs),
+        };
+        let n = field.bits() as usize;
+        let poly = Word::from(field.poly());
+        with_tracer(|t| {
+            let mut a = a.bits[8 - n..].to_vec();
+            let mut p = vec![Bit::Const(false); n];
+            for i in 0..n {
+                let multiplier = b.bits[7 - i];
+                for (sum, &bit) in p.iter_mut().zip(a.iter()) {
+                    let term = t.and(bit, multiplier);
+                    *sum = t.xor(&[*sum, term]);
+                }
+                // Multiply by x, reducing the bit shifted out by the polynomial.
+                let hi_bit = a[0];
+                a.rotate_left(1);
+                a[n - 1] = Bit::Const(false);
+                for (bit, &c) in a.iter_mut().zip(poly.bits[8 - n..].iter()) {
+                    if c == Bit::Const(true) {
+                        *bit = t.xor(&[*bit, hi_bit]);
+                    }
+                }
+            }
+            let mut out = Word::from(0);
+            out.bits[8 - n..].copy_from_slice(&p);
+            out
+        })
+    }
+}
+
+impl Permutation for Word {
+    /// An S-box of `2^n` entries applied to the low `n` bits, through its ANF.
+    fn sub_sbox(&self, sbox: &[u8]) -> Self {
+        if let Some(value) = self.value() {
+            return Word::from(sbox[value as usize]);
+        }
+        let n = sbox.len().trailing_zeros() as usize;
+        let inputs = &self.bits[8 - n..];
+        with_tracer(|t| {
+            let mut products: HashMap<usize, Bit> = HashMap::new();
+            products.insert(0, Bit::Const(true));
+            let mut out = Word::from(0);
+            for j in 0..8 {
+                // Moebius transform of output bit `j`, where bit `i` of a monomial is input `i`
+                // counted from the least significant bit.
+                let mut anf: Vec<bool> = sbox.iter().map(|&y| (y >> (7 - j)) & 1 == 1).collect();
+                for i in 0..n {
+                    for x in 0..anf.len() {
+                        if (x >> i) & 1 == 1 {
+                            anf[x] ^= anf[x ^ (1 << i)];
+                        }
+                    }
+                }
+                let mut terms = vec![];
+                for (monomial, _) in anf.iter().enumerate().filter(|(_, &c)| c) {
+                    terms.push(product(t, &mut products, inputs, monomial));
+                }
+                out.bits[j] = t.xor(&terms);
+            }
+            out
+        })
+    }
+}
+
+/// AND of the inputs in `monomial`, sharing the gates of its sub-products.
+fn product(
+    t: &mut Tracer,
+    products: &mut HashMap<usize, Bit>,
+    inputs: &[Bit],
+    monomial: usize,
+) -> Bit {
+    if let Some(&bit) = products.get(&monomial) {
+        return bit;
+    }
+    let low = monomial.trailing_zeros() as usize;
+    let rest = product(t, products, inputs, monomial & (monomial - 1));
+    let bit = t.and(rest, inputs[inputs.len() - 1 - low]);
+    products.insert(monomial, bit);
+    bit
+}
+
+impl Cell for Word {
+    fn shl(&self, n: u32) -> Self {
+        let mut out = Word::from(0);
+        for i in n as usize..8 {
+            out.bits[i - n as usize] = self.bits[i];
+        }
+        out
+    }
+
+    fn shr(&self, n: u32) -> Self {
+        let mut out = Word::from(0);
+        for i in n as usize..8 {
+            out.bits[i] = self.bits[i - n as usize];
+        }
+        out
+    }
+}
@@ -0,0 +1,93 @@
+use eva_builder::anf::*;
+use eva_builder::constant::{Constant, ConstantTable};
+use eva_builder::description::Description;
+use eva_builder::leakage::{Leakage, LeakageType};
+use eva_crypto::aes::AES;
+use eva_crypto::generic::{expand_bits, restore_data};
+use std::fs;
+
+#[cfg(test)]
+#[test]
+fn polynomials_of_a_toy_cipher() {
+    let mut system = AnfSystem::new(vec![
+        Constant::new("s1=[0,0,1,0,0,1,0,1]"),
+        Constant::new("p1=[1,0]"),
+    ]);
+    let desc: Description = "1,x,A#-1#1#,K#0#0#,A#-1#2#,\n1,s,A#-1#2#,A#-1#3#,s1;\n\
+                             1,p1,A#-1#3#,A#1#2#,"
+        .parse()
+        .unwrap();
+    system.add(&desc).unwrap();
+    system.fix("A", 1, 1, &[true, false]);
+    let mut leakage = Leakage::new(LeakageType::Collision);
+    leakage.add_collision(&["K#0#0#1#".to_string()], &["K#0#0#2#".to_string()]);
+    system.leak(leakage);
+    assert_eq!(
+        system.to_anf(),
+        "A#1#1#1# + K#0#0#1# + A#1#2#1#\n\
+         A#1#1#2# + K#0#0#2# + A#1#2#2#\n\
+         A#1#2#2# + A#1#3#1#\n\
+         A#1#2#1#*A#1#2#2# + A#1#2#1# + A#1#3#2#\n\
+         A#1#3#2# + A#2#1#1#\n\
+         A#1#3#1# + A#2#1#2#\n\
+         A#1#1#1# + 1\n\
+         A#1#1#2#\n\
+         K#0#0#1# + K#0#0#2#\n"
+    );
+}
+
+#[test]
+fn permutation_out_of_range() {
+    let mut system = AnfSystem::new(vec![Constant::new("p1=[2,0]")]);
+    let desc: Description = "1,p1,A#-1#1#,A#-1#2#,".parse().unwrap();
+    assert_eq!(system.add(&desc).unwrap_err().message, "p1 refers to bit 2");
+}
+
+#[test]
+fn elimlin_needs_linearization() {
+    let mut system = AnfSystem::new(vec![]);
+    let x = system.variable("x");
+    let y = system.variable("y");
+    // xy + x = 0 and xy + y + 1 = 0 sum to x + y + 1 = 0, and then x = 0.
+    system.polynomials = vec![
+        Polynomial::from_monomials(vec![vec![x, y], vec![x]]),
+        Polynomial::from_monomials(vec![vec![x, y], vec![y], vec![]]),
+    ];
+    let values = system.elimlin().unwrap();
+    assert!(!values["x"]);
+    assert!(values["y"]);
+    assert!(system.polynomials.is_empty());
+
+    system.polynomials = vec![
+        Polynomial::from_monomials(vec![vec![x, y], vec![]]),
+        Polynomial::from_monomials(vec![vec![x, y], vec![x], vec![y]]),
+    ];
+    // xy = 1 forces x = y = 1, which the second contradicts, but only after substitution.
+    assert_eq!(system.elimlin(), None);
+}
+
+#[test]
+fn elimlin_evaluates_aes() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut system = AnfSystem::new(table.into_constants());
+    for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+        let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+        system.add(&desc).unwrap();
+    }
+    system.fix("K1", 0, 0, &expand_bits(&key, 0));
+    system.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+    let values = system.elimlin().unwrap();
+    assert!(system.polynomials.is_empty());
+    let ciphertext: Vec<bool> = (1..=128)
+        .map(|bit| values[&format!("A4#10#5#{}#", bit)])
+        .collect();
+    assert_eq!(
+        restore_data(&ciphertext, 0),
+        AES::new(&key).encrypt(&plaintext)
+    );
+}
@@ -0,0 +1,48 @@
+use eva_builder::generic::Instance;
+
+/// Unit propagation, enough to evaluate a circuit from its inputs.
+pub fn propagate(instance: &Instance) -> Vec<Option<bool>> {
+    let clauses: Vec<(bool, Vec<(u32, bool)>)> = instance
+        .to_cnf()
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let lits = line
+                .split_whitespace()
+                .filter_map(|lit| lit.parse::<i64>().ok())
+                .take_while(|&lit| lit != 0)
+                .map(|lit| (lit.unsigned_abs() as u32, lit > 0))
+                .collect();
+            (line.starts_with('x'), lits)
+        })
+        .collect();
+    let mut values: Vec<Option<bool>> = vec![None; instance.variables.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (xor, lits) in clauses.iter() {
+            let unknown: Vec<&(u32, bool)> = lits
+                .iter()
+                .filter(|(v, _)| values[*v as usize - 1].is_none())
+                .collect();
+            let value = |&(v, b): &(u32, bool)| values[v as usize - 1].unwrap() == b;
+            if unknown.len() != 1 {
+                continue;
+            }
+            let (var, polarity) = *unknown[0];
+            let known = lits.iter().filter(|(v, _)| *v != var);
+            let assigned = if *xor {
+                Some(known.fold(true, |acc, lit| acc ^ value(lit)) == polarity)
+            } else if known.clone().all(|lit| !value(lit)) {
+                Some(polarity)
+            } else {
+                None
+            };
+            if assigned.is_some() {
+                values[var as usize - 1] = assigned;
+                changed = true;
+            }
+        }
+    }
+    values
+}
@@ -0,0 +1,136 @@
+use eva_builder::compiler::*;
+use eva_builder::constant::{Constant, ConstantTable};
+use eva_builder::description::{Description, VarRef};
+use eva_builder::preprocess::simplify;
+use eva_crypto::aes::AES;
+use eva_crypto::generic::{expand_bits, restore_data};
+use std::fs;
+
+mod common;
+
+fn compile_aes() -> Compiled {
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut compiler = Compiler::new(table.into_constants());
+    for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+        let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+        compiler.compile(&desc).unwrap();
+    }
+    compiler.finish()
+}
+
+#[cfg(test)]
+#[test]
+fn compile_aes_matches_reference() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let mut compiled = compile_aes();
+    assert_eq!(compiled.width("A4", 1), Some(128));
+    assert_eq!(compiled.width("H8", 9), Some(32));
+    compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+    compiled.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+
+    let model: Vec<bool> = common::propagate(&compiled.instance)
+        .into_iter()
+        .map(|v| v.unwrap_or(false))
+        .collect();
+    let ciphertext = compiled.read(&model, "A4", 10, 5).unwrap();
+    assert_eq!(
+        restore_data(&ciphertext, 0),
+        AES::new(&key).encrypt(&plaintext)
+    );
+    let last_key = compiled.read(&model, "K1", 10, 0).unwrap();
+    assert_eq!(
+        restore_data(&last_key, 0),
+        [
+            0x13, 0x11, 0x1d, 0x7f, 0xe3, 0x94, 0x4a, 0x17, 0xf3, 0x07, 0xa7, 0x8b, 0x4d, 0x2b,
+            0x30, 0xc5
+        ]
+    );
+}
+
+#[test]
+fn compile_errors() {
+    let mut compiler = Compiler::new(vec![Constant::new("p1=[1,0]")]);
+    let desc: Description = "1,p1,A#-1#1#,A#-1#2#,\n1,x,A#-1#2#,B#-1#1#1#3#,A#-1#3#,"
+        .parse()
+        .unwrap();
+    assert_eq!(compiler.compile(&desc).unwrap_err().line, 2);
+    let desc: Description = "1,m1,A#-1#1#,A#-1#2#,".parse().unwrap();
+    assert_eq!(
+        compiler.compile(&desc).unwrap_err().message,
+        "unknown constant m1"
+    );
+
+    let mut compiler = Compiler::new(vec![Constant::new("p1=[2,0]"), Constant::new("c1=[1,0]")]);
+    let desc: Description = "1,p1,A#-1#1#,A#-1#2#,".parse().unwrap();
+    assert_eq!(
+        compiler.compile(&desc).unwrap_err().message,
+        "p1 refers to bit 2"
+    );
+    let desc: Description = "0,c,A#-1#1#1#2#,C#-1#1#1#2#,A#-1#2#1#2#,".parse().unwrap();
+    assert_eq!(
+        compiler.compile(&desc).unwrap_err().message,
+        "no round constant for round 0"
+    );
+}
+
+#[test]
+fn pairs_share_the_key() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintexts: Vec<Vec<u8>> = vec![(0..16).map(|x| x * 0x11).collect(), vec![0; 16]];
+    let compile = |ciphertexts: Vec<Option<Vec<bool>>>| {
+        let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut compiler = Compiler::new(table.into_constants());
+        let key: Description = fs::read_to_string("ciphers/aes/key")
+            .unwrap()
+            .parse()
+            .unwrap();
+        compiler.compile(&key).unwrap();
+        let enc: Description = fs::read_to_string("ciphers/aes/enc")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let pairs = plaintexts
+            .iter()
+            .zip(ciphertexts)
+            .map(|(p, c)| Pair {
+                plaintext: expand_bits(p, 0),
+                ciphertext: c,
+                leakage: vec![],
+            })
+            .collect();
+        let plaintext: VarRef = "A4#1#1#".parse().unwrap();
+        let ciphertext: VarRef = "A4#10#5#".parse().unwrap();
+        compiler
+            .compile_pairs(&enc, &["K1"], &plaintext, &ciphertext, pairs)
+            .unwrap()
+    };
+
+    let mut compiled = compile(vec![None, None]);
+    assert!(compiled.variable("P1_K1#0#0#1#").is_none());
+    compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+    let model: Vec<bool> = common::propagate(&compiled.instance)
+        .into_iter()
+        .map(|v| v.unwrap_or(false))
+        .collect();
+    for (p, text) in plaintexts.iter().enumerate() {
+        let name = pair_name("A4", p as u32 + 1);
+        let bits = compiled.read(&model, &name, 10, 5).unwrap();
+        assert_eq!(restore_data(&bits, 0), AES::new(&key).encrypt(text));
+    }
+
+    let mut ciphertexts: Vec<Option<Vec<bool>>> = plaintexts
+        .iter()
+        .map(|p| Some(expand_bits(&AES::new(&key).encrypt(p), 0)))
+        .collect();
+    ciphertexts[1].as_mut().unwrap()[0] ^= true;
+    let mut compiled = compile(ciphertexts);
+    compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+    assert!(simplify(&compiled.instance).unwrap().is_none());
+}
@@ -1,4 +1,5 @@
-use eva_builder::constant::{Constant, ConstantArr, ConstantMat};
+use eva_builder::constant::{Constant, ConstantArr, ConstantMat, ConstantTable};
+use eva_builder::parser::ParseError;
 
 #[cfg(test)]
 #[test]
@@ -36,6 +37,49 @@ fn parse_const() {
     );
 }
 
+#[test]
+fn load_constant_table() {
+    let text = std::fs::read_to_string("ciphers/aes/const").unwrap();
+    let table: ConstantTable = text.parse().unwrap();
+    assert_eq!(table.constants.len(), 5);
+    for line in text.lines() {
+        let constant = Constant::new(line);
+        assert_eq!(table.get(constant.name()), Some(&constant));
+    }
+
+    let table: ConstantTable = "# A toy cipher\np1=[1,\n  2,0] # rotation\nm1=[0,1;\n1]\n"
+        .parse()
+        .unwrap();
+    assert_eq!(table.get("p1"), Some(&Constant::new("p1=[1,2,0]")));
+    assert_eq!(table.get("m1"), Some(&Constant::new("m1=[0,1;1]")));
+}
+
+#[test]
+fn constant_table_errors() {
+    let error = |text: &str| text.parse::<ConstantTable>().unwrap_err();
+    assert_eq!(
+        error("p1=[0,1]\np2=[0,0,1]"),
+        ParseError {
+            line: 2,
+            column: 7,
+            message: "p2 is not a permutation".to_string(),
+        }
+    );
+    let e = error("c1=[0,1,x]");
+    assert_eq!((e.line, e.column), (1, 9));
+    let e = error("m1=[0,1;\n1,2]");
+    assert_eq!((e.line, e.column), (2, 3));
+    let e = error("s1=[0,1,0]");
+    assert_eq!(e.message, "s1 has 3 elements, expected n * 2^n");
+    let e = error("c1=[0,1,,0]");
+    assert_eq!(e.column, 9);
+    let e = error("p1=[0,1\n");
+    assert_eq!(e.message, "p1 is missing its `]`");
+    let e = error("p1 [0,1]");
+    assert_eq!(e.line, 1);
+    assert!(error("c1=[0,300]").message.contains("byte"));
+}
+
 #[test]
 fn test_sbox() {
     let sbox: [u8; 16] = [
@@ -0,0 +1,72 @@
+use eva_builder::description::*;
+use eva_builder::parser::ParseError;
+use std::fs;
+
+#[cfg(test)]
+#[test]
+fn parse_aes_enc() {
+    let text = fs::read_to_string("ciphers/aes/enc").unwrap();
+    let desc: Description = text.parse().unwrap();
+    assert_eq!(desc.statements.len(), 10);
+    assert_eq!(desc.statements[5], Statement::Repeat(8));
+    assert_eq!(
+        desc.statements[1],
+        Statement::Operation(Operation {
+            round: 1,
+            opcode: Opcode::Sbox,
+            operands: vec!["A4#-1#2#".parse().unwrap(), "A4#-1#3#".parse().unwrap()],
+            params: vec!["s1".to_string(); 16],
+            line: 2,
+        })
+    );
+    match &desc.statements[2] {
+        Statement::Operation(op) => assert_eq!(op.opcode, Opcode::Permutation(1)),
+        _ => panic!("expected an operation"),
+    }
+}
+
+#[test]
+fn parse_aes_key() {
+    let text = fs::read_to_string("ciphers/aes/key").unwrap();
+    let desc: Description = text.parse().unwrap();
+    match &desc.statements[0] {
+        Statement::Operation(op) => {
+            assert_eq!(op.opcode, Opcode::Assign);
+            assert_eq!(
+                op.operands[0],
+                VarRef {
+                    name: "K1".to_string(),
+                    round: 0,
+                    index: 0,
+                    bits: Some((1, 32)),
+                }
+            );
+            assert_eq!(op.operands[1].round, -1);
+        }
+        _ => panic!("expected an operation"),
+    }
+    assert!(desc.statements.contains(&Statement::Repeat(9)));
+}
+
+#[test]
+fn parse_errors() {
+    let error = |text: &str| text.parse::<Description>().unwrap_err();
+    assert_eq!(
+        error("1,x,A4#-1#1#,A4#-1#2#,\n1,y,A4#-1#1#,"),
+        ParseError {
+            line: 2,
+            column: 3,
+            message: "unknown opcode `y`".to_string(),
+        }
+    );
+    let e = error("1,x,A4#-1#1#,A4#a#2#,");
+    assert_eq!((e.line, e.column), (1, 17));
+    let e = error("1,rx,K1#0#0#32#1#,H8#-1#1#1#32#,");
+    assert_eq!((e.line, e.column), (1, 13));
+    let e = error("1,s,A4#-1#2#,A4#-1#3#,");
+    assert_eq!(e.message, "missing S-box names");
+    let e = error("CP,2");
+    assert_eq!((e.line, e.column), (1, 1));
+    let e = error("1,x,,A4#-1#2#,");
+    assert_eq!((e.line, e.column), (1, 5));
+}
@@ -12,7 +12,7 @@ fn test_instance() {
     ins.add_clause(Clause::new(true, vec![("d", true), ("c", false)]));
     ins.add_clause(Clause::new(true, vec![("e", true), ("d", false)]));
     assert_eq!(
-        ins.to_cnf(),
+        ins.to_cnf().unwrap(),
         r#"x 1 -2 0
 x 2 -1 0
 3 -2 0
@@ -21,3 +21,153 @@ x 5 -4 0
 "#
     )
 }
+
+#[test]
+fn test_wcnf() {
+    let mut ins = Instance::new();
+    ins.add_variables(vec!["a", "b", "c"]);
+    ins.add_clause(Clause::new(true, vec![("a", true), ("b", false)]));
+    ins.add_soft_clause(3, Clause::new(false, vec![("c", true)]));
+    assert_eq!(
+        ins.to_wcnf().unwrap(),
+        r#"p wcnf 3 3 4
+4 -1 2 0
+4 1 -2 0
+3 3 0
+"#
+    );
+
+    // A long XOR is cut into pieces with a new variable.
+    let mut ins = Instance::new();
+    let names = vec!["a", "b", "c", "d", "e"];
+    ins.add_variables(names.clone());
+    ins.add_clause(Clause::new(
+        true,
+        names.iter().map(|n| (*n, true)).collect(),
+    ));
+    let wcnf = ins.to_wcnf().unwrap();
+    assert!(wcnf.starts_with("p wcnf 6 12 1\n"));
+    assert!(wcnf.contains("\n1 1 2 3 -6 0\n"));
+}
+
+#[test]
+fn test_xor_to_cnf() {
+    // The XOR of the literals is true for exactly the assignments which extend to the new
+    // variables.
+    for len in 1..=7u32 {
+        let lits: Vec<(u32, bool)> = (1..=len).map(|var| (var, var % 3 != 0)).collect();
+        let mut vars = len;
+        let clauses = xor_to_cnf(&lits, &mut vars);
+        assert_eq!(vars, len + len.saturating_sub(3) / 2);
+        for assignment in 0..1u32 << len {
+            let xor = lits.iter().fold(false, |acc, &(var, value)| {
+                acc ^ ((assignment >> (var - 1)) & 1 == 1) ^ !value
+            });
+            let extends = (0..1u32 << (vars - len)).any(|extra| {
+                let values = assignment | extra << len;
+                clauses.iter().all(|clause| {
+                    clause
+                        .iter()
+                        .any(|&(var, value)| ((values >> (var - 1)) & 1 == 1) == value)
+                })
+            });
+            assert_eq!(
+                extends, xor,
+                "{} literals, assignment {:b}",
+                len, assignment
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dimacs() {
+    let mut ins = Instance::new();
+    ins.add_variables(vec!["A#1#0#1#", "A#1#0#2#", "b"]);
+    ins.add_clause(Clause::new(true, vec![("A#1#0#1#", true), ("b", false)]));
+    ins.add_clause(Clause::new(false, vec![("A#1#0#2#", false)]));
+    let dimacs = ins.to_dimacs().unwrap();
+    assert_eq!(
+        dimacs,
+        r#"p cnf 3 2
+c var 1 A#1#0#1#
+c var 2 A#1#0#2#
+c var 3 b
+x 1 -3 0
+-2 0
+"#
+    );
+    let read: Instance = dimacs.parse().unwrap();
+    assert_eq!(read.variables, ins.variables);
+    assert_eq!(read.state("A", 1), ins.state("A", 1));
+    assert_eq!(read.variable(&VarId::new("A", 1, 0, 2)), Some(2));
+    assert_eq!(read.to_dimacs().unwrap(), dimacs);
+
+    // Unnamed variables get a name from their number.
+    let read: Instance = "p cnf 2 1\nx1 2 0\n".parse().unwrap();
+    assert_eq!(read.variables["v2"], 2);
+    assert_eq!(read.to_cnf().unwrap(), "x 1 2 0\n");
+
+    let error = |text: &str| text.parse::<Instance>().err().unwrap();
+    assert_eq!(error("1 0\n").message, "missing header");
+    assert_eq!(error("p cnf 2 1\n1 3 0\n").column, 3);
+    assert_eq!(error("p cnf 2 1\n1 -2\n").message, "missing final 0");
+    assert_eq!(error("p cnf 2 1\n1 a 0\n").column, 3);
+    assert_eq!(error("p cnf 2 2\n1 0\n").message, "1 clauses instead of 2");
+}
+
+#[test]
+fn test_solver_output() {
+    let mut ins = Instance::new();
+    ins.add_variables(vec!["a", "b", "c"]);
+    let output: SolverOutput = "c comment\ns SATISFIABLE\nv 1 -2\nv 3 0\n".parse().unwrap();
+    assert_eq!(output.satisfiable, Some(true));
+    assert_eq!(output.model(4), vec![true, false, true, false]);
+    let assignment = ins.assignment(&output);
+    assert_eq!(assignment.len(), 3);
+    assert!(assignment["a"] && !assignment["b"]);
+
+    let output: SolverOutput = "s UNSATISFIABLE\n".parse().unwrap();
+    assert_eq!(output.satisfiable, Some(false));
+    assert_eq!("s MAYBE\n".parse::<SolverOutput>().unwrap_err().line, 1);
+}
+
+#[test]
+fn test_var_ids() {
+    let id: VarId = "A4'1#10#5#128#".parse().unwrap();
+    assert_eq!(id, VarId::new("A4'1", 10, 5, 128));
+    assert_eq!(id.to_string(), "A4'1#10#5#128#");
+    assert_eq!("A4#1#x#1#".parse::<VarId>().unwrap_err().column, 6);
+    assert!("A4#1#2#".parse::<VarId>().is_err());
+
+    let mut ins = Instance::new();
+    for id in VarId::word("A", 2, 1, 1..=2).iter().rev() {
+        ins.add_variable(&id.to_string());
+    }
+    ins.add_variables(vec!["A#1#1#1#", "AB#2#1#1#", "A#2#0#3#", "and#1#"]);
+    let state: Vec<(String, u32)> = ins
+        .state("A", 2)
+        .into_iter()
+        .map(|(id, var)| (id.to_string(), var))
+        .collect();
+    assert_eq!(
+        state,
+        vec![
+            ("A#2#0#3#".to_string(), 5),
+            ("A#2#1#1#".to_string(), 2),
+            ("A#2#1#2#".to_string(), 1),
+        ]
+    );
+    assert_eq!(ins.variable(&VarId::new("A", 2, 1, 2)), Some(1));
+
+    // Clauses added with `add_clause` make their variables, others are reported.
+    ins.add_clause(Clause::new(false, vec![("b", true)]));
+    assert_eq!(ins.variables["b"], 7);
+    ins.equations
+        .push(Clause::new(true, vec![("A#1#1#1#", true), ("c", false)]));
+    let unknown = UnknownVariable("c".to_string());
+    assert_eq!(ins.numbered().unwrap_err(), unknown);
+    assert_eq!(ins.to_dimacs().unwrap_err(), unknown);
+    assert_eq!(ins.to_wcnf().unwrap_err(), unknown);
+    assert_eq!(ins.clauses().unwrap_err(), unknown);
+}
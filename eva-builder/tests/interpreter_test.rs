@@ -0,0 +1,114 @@
+use eva_builder::constant::ConstantTable;
+use eva_builder::description::Description;
+use eva_builder::interpreter::*;
+use eva_crypto::aes::{self, AES};
+use eva_crypto::generic::*;
+use std::fs;
+
+fn run_aes(enc: &str, key: &[u8], plaintext: &[u8]) -> Interpreter {
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut interpreter = Interpreter::new(table.into_constants());
+    interpreter.set("K1", 0, 0, &expand_bits(key, 0));
+    interpreter.set("A4", 1, 1, &expand_bits(plaintext, 0));
+    let desc: Description = fs::read_to_string("ciphers/aes/key")
+        .unwrap()
+        .parse()
+        .unwrap();
+    interpreter.run(&desc).unwrap();
+    interpreter.run(&enc.parse().unwrap()).unwrap();
+    interpreter
+}
+
+fn bits(state: &u8x4x4) -> Vec<bool> {
+    expand_bits(&create_u8x16(state), 0)
+}
+
+/// Compare every word of every round with the same steps taken by eva-crypto.
+fn check_aes(interpreter: &Interpreter, key: &[u8], plaintext: &[u8]) -> Result<(), String> {
+    let round_keys = AES::new(key).round_keys;
+    let mix: u8x4x4 = [
+        [0x02, 0x01, 0x01, 0x03],
+        [0x03, 0x02, 0x01, 0x01],
+        [0x01, 0x03, 0x02, 0x01],
+        [0x01, 0x01, 0x03, 0x02],
+    ];
+    let mut state = create_u8x4x4(plaintext);
+    for round in 1..=10 {
+        interpreter.compare("K1", round - 1, 0, &bits(&round_keys[round as usize - 1]))?;
+        interpreter.compare("A4", round, 1, &bits(&state))?;
+        state = state.xor(&round_keys[round as usize - 1]);
+        interpreter.compare("A4", round, 2, &bits(&state))?;
+        state = state.sub_sbox(&aes::SBOX);
+        interpreter.compare("A4", round, 3, &bits(&state))?;
+        state = transpose(&transpose(&state).lrot());
+        interpreter.compare("A4", round, 4, &bits(&state))?;
+        state = if round < 10 {
+            mix.gmul(&state, Field::GF256)
+        } else {
+            state.xor(&round_keys[10])
+        };
+        interpreter.compare("A4", round, 5, &bits(&state))?;
+    }
+    interpreter.compare("K1", 10, 0, &bits(&round_keys[10]))?;
+    interpreter.compare(
+        "A4",
+        10,
+        5,
+        &expand_bits(&AES::new(key).encrypt(plaintext), 0),
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn interpret_aes_matches_reference() {
+    let enc = fs::read_to_string("ciphers/aes/enc").unwrap();
+    for seed in 0..4u8 {
+        let key: Vec<u8> = (0..16).map(|x| x * 7 + seed).collect();
+        let plaintext: Vec<u8> = (0..16).map(|x| (x * 0x11) ^ seed).collect();
+        let interpreter = run_aes(&enc, &key, &plaintext);
+        assert_eq!(check_aes(&interpreter, &key, &plaintext), Ok(()));
+    }
+}
+
+#[test]
+fn interpret_reports_divergence() {
+    // Forget ShiftRows: the first wrong word is written by line 3 in round 1.
+    let enc = fs::read_to_string("ciphers/aes/enc")
+        .unwrap()
+        .replace("1,p1,", "1,rx,");
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let interpreter = run_aes(&enc, &key, &plaintext);
+    let err = check_aes(&interpreter, &key, &plaintext).unwrap_err();
+    assert!(err.starts_with("A4#1#4# bit "));
+    assert!(err.ends_with("written by rx at line 3 in round 1"));
+    assert_eq!(
+        interpreter.origin("A4", 4, 4, 1).unwrap(),
+        Step {
+            line: 3,
+            round: 4,
+            opcode: eva_builder::description::Opcode::Assign
+        }
+    );
+}
+
+#[test]
+fn interpret_errors() {
+    let mut interpreter = Interpreter::new(vec![]);
+    let desc: Description = "1,x,A#-1#1#1#2#,A#-1#2#1#2#,".parse().unwrap();
+    let err = interpreter.run(&desc).unwrap_err();
+    assert_eq!(
+        (err.line, err.message.as_str()),
+        (1, "A#1#1#1# has no value")
+    );
+
+    interpreter.set("A", 1, 1, &[true, false]);
+    interpreter.set("A", 1, 2, &[false, false]);
+    assert_eq!(
+        interpreter.run(&desc).unwrap_err().message,
+        "A#1#2#1# is already 0"
+    );
+}
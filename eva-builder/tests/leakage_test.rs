@@ -0,0 +1,371 @@
+use eva_builder::compiler::*;
+use eva_builder::constant::{Constant, ConstantTable};
+use eva_builder::description::{Description, VarRef};
+use eva_builder::generic::{Clause, Instance};
+use eva_builder::leakage::*;
+use eva_crypto::aes::{self, AES};
+use eva_crypto::generic::*;
+use std::fs;
+
+mod common;
+
+/// Whether propagation assigned every variable without breaking any clause.
+fn satisfied(instance: &Instance) -> bool {
+    let values = common::propagate(instance);
+    if values.iter().any(|v| v.is_none()) {
+        return false;
+    }
+    instance.to_cnf().unwrap().lines().all(|line| {
+        let mut lits = line
+            .split_whitespace()
+            .filter_map(|lit| lit.parse::<i64>().ok())
+            .take_while(|&lit| lit != 0)
+            .map(|lit| values[lit.unsigned_abs() as usize - 1] == Some(lit > 0));
+        if line.starts_with('x') {
+            lits.fold(false, |acc, value| acc ^ value)
+        } else {
+            lits.any(|value| value)
+        }
+    })
+}
+
+/// All 6-bit inputs with a weight leakage, which should hold exactly within the tolerance.
+fn check_encoding(encoding: Encoding) {
+    let bits: Vec<String> = (1..=6).map(|i| format!("x#{}#", i)).collect();
+    for tolerance in 0..2 {
+        for weight in 0..=7 {
+            for x in 0..64u8 {
+                let mut instance = Instance::new();
+                for (i, name) in bits.iter().enumerate() {
+                    instance.add_variable(name);
+                    instance.add_clause(Clause::new(false, vec![(name, (x >> i) & 1 == 1)]));
+                }
+                let mut leakage = Leakage::new(LeakageType::HammingWeight)
+                    .with_encoding(encoding)
+                    .with_tolerance(tolerance);
+                leakage.add_hamming_weight(&bits, weight);
+                for clause in leakage.into_clauses() {
+                    for name in clause.names() {
+                        instance.add_variable(name);
+                    }
+                    instance.add_clause(clause);
+                }
+                let expected = (x.count_ones() as i64 - weight as i64).abs() <= tolerance as i64;
+                assert_eq!(
+                    satisfied(&instance),
+                    expected,
+                    "{:?} weight {} tolerance {} input {:06b}",
+                    encoding,
+                    weight,
+                    tolerance,
+                    x
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn sequential_counter() {
+    check_encoding(Encoding::SequentialCounter);
+}
+
+#[test]
+fn sorting_network() {
+    check_encoding(Encoding::SortingNetwork);
+}
+
+#[test]
+fn totalizer() {
+    check_encoding(Encoding::Totalizer);
+}
+
+#[test]
+fn hamming_weight_of_sbox_outputs() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let sbox_out: Vec<u8> = plaintext
+        .iter()
+        .zip(key.iter())
+        .map(|(p, k)| aes::SBOX[(p ^ k) as usize])
+        .collect();
+
+    for error in 0..2 {
+        let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut compiler = Compiler::new(table.into_constants());
+        for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+            let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+            compiler.compile(&desc).unwrap();
+        }
+        let mut compiled = compiler.finish();
+        compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+        compiled.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+
+        let mut leakage =
+            Leakage::new(LeakageType::HammingWeight).with_encoding(Encoding::Totalizer);
+        for (i, byte) in sbox_out.iter().enumerate() {
+            let bits: Vec<String> = (1..=8)
+                .map(|b| bit_name("A4", 1, 3, (i * 8 + b) as u32))
+                .collect();
+            leakage.add_hamming_weight(&bits, byte.count_ones() + error);
+        }
+        compiled.leak(leakage);
+        assert_eq!(satisfied(&compiled.instance), error == 0);
+    }
+}
+
+#[test]
+fn several_hamming_weight_leakages() {
+    let bits: Vec<String> = (1..=8).map(|i| format!("x#{}#", i)).collect();
+    let x = 0b1011_0010u8;
+    for error in 0..2 {
+        let mut compiled = Compiler::new(vec![]).finish();
+        for (i, name) in bits.iter().enumerate() {
+            let value = (x >> i) & 1 == 1;
+            compiled
+                .instance
+                .add_clause(Clause::new(false, vec![(name, value)]));
+        }
+        // One leakage per nibble, each with its own counter.
+        for (nibble, extra) in [(0, 0), (1, error)].iter() {
+            let mut leakage = Leakage::new(LeakageType::HammingWeight);
+            let weight = ((x >> (4 * nibble)) & 0xf).count_ones() + extra;
+            leakage.add_hamming_weight(&bits[4 * nibble..4 * nibble + 4], weight);
+            compiled.leak(leakage);
+        }
+        assert_eq!(satisfied(&compiled.instance), error == 0);
+    }
+}
+
+#[test]
+fn hamming_weight_leakages_of_pairs() {
+    let desc: Description = "1,x,A#-1#1#,K#0#0#,A#-1#2#,\n1,s,A#-1#2#,A#-1#3#,s1;s1;\n\
+                             1,p1,A#-1#3#,A#1#2#,"
+        .parse()
+        .unwrap();
+    let ciphertext: VarRef = "A#2#1#".parse().unwrap();
+    let compile = |plaintext: &str, pairs: Vec<Pair>| {
+        let plaintext: VarRef = plaintext.parse().unwrap();
+        let compiler = Compiler::new(vec![
+            Constant::new("s1=[0,0,1,0,0,1,0,1]"),
+            Constant::new("p1=[1,2,3,0]"),
+        ]);
+        compiler.compile_pairs(&desc, &["K"], &plaintext, &ciphertext, pairs)
+    };
+    let texts = [0b0110u8, 0b1101];
+    for error in 0..2 {
+        // Two leakages per pair, on the halves of the plaintext.
+        let pairs = texts
+            .iter()
+            .map(|&p| Pair {
+                plaintext: (0..4).map(|b| (p >> (3 - b)) & 1 == 1).collect(),
+                ciphertext: None,
+                leakage: (0..2)
+                    .map(|half| {
+                        let bits: Vec<String> = (1..=2)
+                            .map(|b| format!("A#1#1#{}#", 2 * half + b))
+                            .collect();
+                        let weight = ((p >> (2 - 2 * half)) & 3).count_ones() + error * half;
+                        let mut leakage = Leakage::new(LeakageType::HammingWeight);
+                        leakage.add_hamming_weight(&bits, weight);
+                        leakage
+                    })
+                    .collect(),
+            })
+            .collect();
+        let mut compiled = compile("A#1#1#", pairs).unwrap();
+        compiled.fix("K", 0, 0, &[true, false, false, true]);
+        assert_eq!(satisfied(&compiled.instance), error == 0);
+    }
+
+    let pair = |bits: usize| Pair {
+        plaintext: vec![false; bits],
+        ciphertext: None,
+        leakage: vec![],
+    };
+    assert_eq!(
+        compile("A#1#1#", vec![pair(4), pair(3)])
+            .err()
+            .unwrap()
+            .message,
+        "A has 4 bits, 3 given"
+    );
+    assert_eq!(
+        compile("B#1#1#", vec![pair(4)]).err().unwrap().message,
+        "unknown word B 1"
+    );
+}
+
+#[test]
+fn fault_models() {
+    let bits: Vec<String> = (1..=8).map(|i| format!("d#{}#", i)).collect();
+    for model in [FaultModel::BitFlip, FaultModel::RandomByte].iter() {
+        for d in 0..=255u8 {
+            let mut instance = Instance::new();
+            for (i, name) in bits.iter().enumerate() {
+                instance.add_variable(name);
+                instance.add_clause(Clause::new(false, vec![(name, (d >> i) & 1 == 1)]));
+            }
+            let mut leakage = Leakage::new(LeakageType::Fault);
+            leakage.add_fault(&bits, *model);
+            for clause in leakage.into_clauses() {
+                instance.add_clause(clause);
+            }
+            let expected = match model {
+                FaultModel::BitFlip => d.count_ones() == 1,
+                _ => d != 0,
+            };
+            assert_eq!(satisfied(&instance), expected, "{:?} {:08b}", model, d);
+        }
+    }
+}
+
+/// The AES state entering round `to`, from the state entering round `from`.
+fn aes_rounds(mut state: u8x4x4, round_keys: &[u8x4x4], from: usize, to: usize) -> u8x4x4 {
+    let mix: u8x4x4 = [
+        [0x02, 0x01, 0x01, 0x03],
+        [0x03, 0x02, 0x01, 0x01],
+        [0x01, 0x03, 0x02, 0x01],
+        [0x01, 0x01, 0x03, 0x02],
+    ];
+    for round in from..to {
+        state = state.xor(&round_keys[round - 1]).sub_sbox(&aes::SBOX);
+        state = transpose(&transpose(&state).lrot());
+        state = match round {
+            10 => state.xor(&round_keys[10]),
+            _ => mix.gmul(&state, Field::GF256),
+        };
+    }
+    state
+}
+
+#[test]
+fn fault_in_round_nine() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let round_keys = AES::new(&key).round_keys;
+    let delta = 0x5a;
+    let mut state = aes_rounds(create_u8x4x4(&plaintext), &round_keys, 1, 9);
+    state[0][0] ^= delta;
+    let faulty = create_u8x16(&aes_rounds(state, &round_keys, 9, 11));
+
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut compiler = Compiler::new(table.into_constants());
+    let key_desc: Description = fs::read_to_string("ciphers/aes/key")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let enc: Description = fs::read_to_string("ciphers/aes/enc")
+        .unwrap()
+        .parse()
+        .unwrap();
+    compiler.compile(&key_desc).unwrap();
+    compiler.compile(&enc).unwrap();
+    let fault = Fault {
+        name: "A4".to_string(),
+        round: 9,
+        index: 1,
+        bits: (1, 8),
+        model: FaultModel::RandomByte,
+    };
+    compiler.compile_fault(&enc, &fault, 1).unwrap();
+    let wrong = Fault {
+        model: FaultModel::RandomNibble,
+        ..fault.clone()
+    };
+    assert_eq!(compiler.compile_fault(&enc, &wrong, 2).unwrap_err().line, 5);
+    let mut compiled = compiler.finish();
+    // The rejected run left the layout alone.
+    assert_eq!(compiled.width(&faulty_name("A4", 1), 1), Some(128));
+    assert_eq!(compiled.width(&faulty_name("A4", 2), 1), None);
+
+    // Both runs share the last round key, so only the affected bytes get new variables.
+    assert_eq!(compiled.variable("K1'1#10#0#1#"), None);
+    compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+    compiled.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+    compiled.fix("fault", 0, 1, &expand_bits(&[delta], 0));
+    let values = common::propagate(&compiled.instance);
+    let model: Vec<bool> = values.iter().map(|v| v.unwrap_or(false)).collect();
+    let ciphertext = compiled.read(&model, &faulty_name("A4", 1), 10, 5).unwrap();
+    assert_eq!(restore_data(&ciphertext, 0), faulty);
+    assert!(satisfied(&compiled.instance));
+}
+
+#[test]
+fn parse_leakage_spec() {
+    let spec: LeakageSpec =
+        "# first round\ncollision,A4#1#2#1#8#,A4#1#2#9#16#,\n\nbits,K1#0#0#1#4#,1011,"
+            .parse()
+            .unwrap();
+    assert_eq!(spec.observations.len(), 2);
+    assert_eq!(
+        spec.observations[1],
+        Observation::PartialBit {
+            var: "K1#0#0#1#4#".parse().unwrap(),
+            values: vec![true, false, true, true],
+            line: 4,
+        }
+    );
+
+    let error = |text: &str| text.parse::<LeakageSpec>().unwrap_err();
+    assert_eq!(error("bits,A#1#1#1#2#,101,").column, 17);
+    assert_eq!(error("bits,A#1#1#,10a,").column, 15);
+    assert_eq!(
+        error("collision,A#-1#1#,A#1#2#,").message,
+        "rounds are absolute"
+    );
+    assert_eq!(error("\nhw,A#1#1#,3,").line, 2);
+}
+
+#[test]
+fn collision_determines_key_byte() {
+    let key: Vec<u8> = (0..16).map(|x| x * 3 + 1).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let known: String = expand_bits(&key[1..], 0)
+        .iter()
+        .map(|&b| if b { '1' } else { '0' })
+        .collect();
+    let spec: LeakageSpec = format!(
+        "collision,A4#1#2#1#8#,A4#1#2#9#16#,\nbits,K1#0#0#9#128#,{},",
+        known
+    )
+    .parse()
+    .unwrap();
+
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut compiler = Compiler::new(table.into_constants());
+    for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+        let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+        compiler.compile(&desc).unwrap();
+    }
+    let mut compiled = compiler.finish();
+    compiled.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+    compiled.observe(&spec).unwrap();
+
+    // Without the collision, the first key byte would be unknown.
+    let values = common::propagate(&compiled.instance);
+    let model: Vec<bool> = values.iter().map(|v| v.unwrap_or(false)).collect();
+    let mut expected = key.clone();
+    expected[0] = plaintext[0] ^ plaintext[1] ^ key[1];
+    assert_eq!(
+        restore_data(&compiled.read(&model, "K1", 0, 0).unwrap(), 0),
+        expected
+    );
+    assert!(satisfied(&compiled.instance));
+
+    let spec: LeakageSpec = "bits,K1#0#0#,1,".parse().unwrap();
+    assert_eq!(
+        compiled.observe(&spec).unwrap_err().message,
+        "expected 128 bits"
+    );
+}
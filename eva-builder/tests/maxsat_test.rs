@@ -0,0 +1,84 @@
+use eva_builder::generic::{Clause, Instance};
+use eva_builder::leakage::{InvalidTemplate, Leakage, LeakageType};
+use eva_builder::maxsat::*;
+use eva_builder::symbolic::{trace, Word};
+use eva_crypto::aes::SBOX;
+use eva_crypto::generic::{Ops, Permutation};
+
+#[cfg(test)]
+#[test]
+fn template_finds_most_likely_key() {
+    let key = 0x3c;
+    let plaintexts = [0x00, 0x5a, 0xf1];
+    let (_, mut instance) = trace(|| {
+        let k = Word::variable("K", 8);
+        for (t, &p) in plaintexts.iter().enumerate() {
+            Word::from(p)
+                .xor(&k)
+                .sub_sbox(&SBOX)
+                .bind(&format!("Y#{}", t));
+        }
+    });
+
+    // The first trace peaks on a wrong value, which the other two outweigh.
+    let mut leakage = Leakage::new(LeakageType::Template);
+    for (t, &p) in plaintexts.iter().enumerate() {
+        let y = SBOX[(p ^ key) as usize] as usize;
+        let mut probabilities = vec![0.4 / 255.0; 256];
+        probabilities[y] = 0.6;
+        if t == 0 {
+            probabilities = vec![0.1 / 254.0; 256];
+            probabilities[y] = 0.3;
+            probabilities[y ^ 1] = 0.6;
+        }
+        let bits: Vec<String> = (1..=8).map(|i| format!("Y#{}#{}#", t, i)).collect();
+        leakage.add_template(&bits, &probabilities).unwrap();
+    }
+    instance.add_leakage(leakage, |name| name.to_string());
+    assert_eq!(instance.soft.len(), 3 * 255);
+
+    let solution = solve(&instance).unwrap().unwrap();
+    assert_eq!(solution.cost, 693);
+    let k = (1..=8).fold(0, |acc, i| {
+        let var = instance.variables[&format!("K#{}#", i)];
+        (acc << 1) | solution.model[var as usize - 1] as u8
+    });
+    assert_eq!(k, key);
+}
+
+#[test]
+fn impossible_values_are_hard() {
+    let mut instance = Instance::new();
+    instance.add_variables(vec!["a", "b"]);
+    let bits = vec!["a".to_string(), "b".to_string()];
+    let mut leakage = Leakage::new(LeakageType::Template);
+    leakage.add_template(&bits, &[0.0, 0.5, 0.0, 0.25]).unwrap();
+    instance.add_leakage(leakage, |name| name.to_string());
+    let solution = solve(&instance).unwrap().unwrap();
+    assert_eq!(
+        solution,
+        Solution {
+            cost: 0,
+            model: vec![false, true]
+        }
+    );
+
+    instance.add_clause(Clause::new(false, vec![("b", false)]));
+    assert_eq!(solve(&instance).unwrap(), None);
+}
+
+#[test]
+fn template_size_is_checked() {
+    let mut leakage = Leakage::new(LeakageType::Template);
+    let bits = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(
+        leakage.add_template(&bits, &[0.5, 0.5]),
+        Err(InvalidTemplate {
+            bits: 2,
+            probabilities: 2
+        })
+    );
+    let bits: Vec<String> = (1..=64).map(|i| format!("x#{}#", i)).collect();
+    assert!(leakage.add_template(&bits, &[1.0]).is_err());
+    assert!(leakage.clauses().is_empty() && leakage.soft_clauses().is_empty());
+}
@@ -0,0 +1,125 @@
+use eva_builder::compiler::*;
+use eva_builder::constant::ConstantTable;
+use eva_builder::description::Description;
+use eva_builder::generic::{Clause, Instance};
+use eva_builder::leakage::{Leakage, LeakageType};
+use eva_builder::maxsat::solve;
+use eva_builder::preprocess::*;
+use eva_builder::symbolic::{trace, Word};
+use eva_crypto::aes::{AES, SBOX};
+use eva_crypto::generic::{expand_bits, restore_data, Ops, Permutation};
+use std::fs;
+
+/// Whether the model satisfies every hard clause of the instance.
+fn satisfies(instance: &Instance, model: &[bool]) -> bool {
+    instance.numbered().unwrap().iter().all(|(xor, lits)| {
+        let mut values = lits
+            .iter()
+            .map(|&(var, value)| model[var as usize - 1] == value);
+        if *xor {
+            values.fold(false, |acc, value| acc ^ value)
+        } else {
+            values.any(|value| value)
+        }
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn units_equivalences_and_elimination() {
+    let mut instance = Instance::new();
+    instance.add_variables(vec!["a", "b", "c", "d", "e", "f", "g"]);
+    instance.add_clause(Clause::new(false, vec![("a", true)]));
+    // b = !c, so the OR clause becomes a unit on c once a is known.
+    instance.add_clause(Clause::new(true, vec![("b", true), ("c", true)]));
+    instance.add_clause(Clause::new(false, vec![("a", false), ("b", true)]));
+    // Together these give e = !g, which Gaussian elimination finds, and f is eliminated.
+    instance.add_clause(Clause::new(
+        true,
+        vec![("d", true), ("e", true), ("f", true)],
+    ));
+    instance.add_clause(Clause::new(
+        true,
+        vec![("d", true), ("f", true), ("g", false)],
+    ));
+    instance.add_clause(Clause::new(false, vec![("e", true), ("g", true)]));
+    instance.add_clause(Clause::new(false, vec![("d", false), ("g", false)]));
+
+    let simplified = simplify(&instance).unwrap().unwrap();
+    assert_eq!(simplified.instance.variables.len(), 2);
+    assert_eq!(simplified.instance.equations.len(), 1);
+    for model in [[false, false], [false, true], [true, false]].iter() {
+        let lifted = simplified.lift(model);
+        assert!(satisfies(&simplified.instance, model));
+        assert!(satisfies(&instance, &lifted), "{:?}", lifted);
+    }
+
+    instance.add_clause(Clause::new(true, vec![("e", true), ("g", false)]));
+    assert!(simplify(&instance).unwrap().is_none());
+}
+
+#[test]
+fn known_key_and_plaintext_leave_nothing() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut compiler = Compiler::new(table.into_constants());
+    for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+        let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+        compiler.compile(&desc).unwrap();
+    }
+    let mut compiled = compiler.finish();
+    compiled.fix("A4", 1, 1, &expand_bits(&plaintext, 0));
+    // The plaintext makes the first round keys and states equal up to constants.
+    let simplified = simplify(&compiled.instance).unwrap().unwrap();
+    let removed = compiled.instance.variables.len() - simplified.instance.variables.len();
+    assert!(removed >= 128 * 4);
+
+    compiled.fix("K1", 0, 0, &expand_bits(&key, 0));
+    let simplified = simplify(&compiled.instance).unwrap().unwrap();
+    assert!(simplified.instance.variables.is_empty());
+    let model = simplified.lift(&[]);
+    let ciphertext = compiled.read(&model, "A4", 10, 5).unwrap();
+    assert_eq!(
+        restore_data(&ciphertext, 0),
+        AES::new(&key).encrypt(&plaintext)
+    );
+}
+
+#[test]
+fn soft_clauses_keep_their_cost() {
+    let plaintexts = [0x00u8, 0x5a];
+    let (_, mut instance) = trace(|| {
+        let k = Word::variable("K", 8);
+        for (t, &p) in plaintexts.iter().enumerate() {
+            Word::from(p)
+                .xor(&k)
+                .sub_sbox(&SBOX)
+                .bind(&format!("Y#{}", t));
+        }
+    });
+    let mut leakage = Leakage::new(LeakageType::Template);
+    for (t, &p) in plaintexts.iter().enumerate() {
+        let mut probabilities = vec![0.5 / 255.0; 256];
+        probabilities[SBOX[(p ^ 0x3c) as usize] as usize] = 0.5;
+        let bits: Vec<String> = (1..=8).map(|i| format!("Y#{}#{}#", t, i)).collect();
+        leakage.add_template(&bits, &probabilities).unwrap();
+    }
+    instance.add_leakage(leakage, |name| name.to_string());
+
+    let simplified = simplify(&instance).unwrap().unwrap();
+    assert!(simplified.instance.variables.len() < instance.variables.len());
+    let expected = solve(&instance).unwrap().unwrap();
+    let solution = solve(&simplified.instance).unwrap().unwrap();
+    assert_eq!(solution.cost, expected.cost);
+    let model = simplified.lift(&solution.model);
+    assert!(satisfies(&instance, &model));
+    let k = (1..=8).fold(0, |acc, i| {
+        let var = instance.variables[&format!("K#{}#", i)];
+        (acc << 1) | model[var as usize - 1] as u8
+    });
+    assert_eq!(k, 0x3c);
+}
@@ -0,0 +1,118 @@
+use eva_builder::constant::{Constant, ConstantTable};
+use eva_builder::description::Description;
+use eva_builder::interpreter::Interpreter;
+use eva_builder::sasca::*;
+use eva_crypto::generic::expand_bits;
+use std::fs;
+
+/// The rounds of a description before its `CP`, repeated `more` times.
+fn first_round(file: &str, more: u32) -> Description {
+    let text = fs::read_to_string(file).unwrap();
+    let mut lines: Vec<&str> = text.lines().take_while(|l| !l.starts_with("CP")).collect();
+    let repeat = format!("CP,{}", more);
+    if more > 0 {
+        lines.push(&repeat);
+    }
+    lines.join("\n").parse().unwrap()
+}
+
+/// A deterministic stream of standard normal samples.
+struct Noise(u64);
+
+impl Noise {
+    fn uniform(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    fn normal(&mut self) -> f64 {
+        let (u, v) = (self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+fn constants() -> ConstantTable {
+    fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[cfg(test)]
+#[test]
+fn recover_aes_key_from_noisy_hamming_weights() {
+    let key: Vec<u8> = (0..16u8).map(|x| x.wrapping_mul(29) ^ 7).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let descriptions = [
+        first_round("ciphers/aes/key", 0),
+        first_round("ciphers/aes/enc", 1),
+    ];
+
+    let mut interpreter = Interpreter::new(constants().into_constants());
+    interpreter.set("K1", 0, 0, &expand_bits(&key, 0));
+    interpreter.set("A4", 1, 1, &expand_bits(&plaintext, 0));
+    let mut graph = FactorGraph::new(constants().into_constants(), 8);
+    for desc in descriptions.iter() {
+        interpreter.run(desc).unwrap();
+        graph.add(desc).unwrap();
+    }
+    graph.fix(
+        "A4",
+        1,
+        1,
+        &plaintext.iter().map(|&p| p as usize).collect::<Vec<_>>(),
+    );
+
+    let sigma = 0.3;
+    let mut noise = Noise(1);
+    let mut leak = |graph: &mut FactorGraph, name: &str, round: i32, index: i32| {
+        let bits = interpreter.get(name, round, index).unwrap();
+        for (c, cell) in bits.chunks(8).enumerate() {
+            let hw = cell.iter().filter(|&&b| b).count() as f64;
+            let observed = hw + sigma * noise.normal();
+            let likelihood: Vec<f64> = (0..256u32)
+                .map(|v| {
+                    (-(v.count_ones() as f64 - observed).powi(2) / (2.0 * sigma * sigma)).exp()
+                })
+                .collect();
+            graph.observe(name, round, index, c as u32 + 1, &likelihood);
+        }
+    };
+    for round in 1..=2 {
+        for index in 2..=5 {
+            leak(&mut graph, "A4", round, index);
+        }
+    }
+    for index in 5..=12 {
+        leak(&mut graph, "H8", 1, index);
+    }
+    graph.propagate(20);
+
+    let mut recovered = 0;
+    for (c, &k) in key.iter().enumerate() {
+        let marginal = graph.marginal("K1", 0, 0, c as u32 + 1).unwrap();
+        let best = (0..256)
+            .max_by(|&a, &b| marginal[a].partial_cmp(&marginal[b]).unwrap())
+            .unwrap();
+        recovered += (best == k as usize) as usize;
+    }
+    assert_eq!(recovered, 16);
+}
+
+#[test]
+fn cells_must_stay_whole() {
+    let desc: Description = "1,p1,A#-1#1#,A#-1#2#,".parse().unwrap();
+    let mut graph = FactorGraph::new(vec![Constant::new("p1=[0,1,2,4,3,5,6,7]")], 4);
+    assert_eq!(graph.add(&desc).unwrap_err().message, "p1 splits cells");
+
+    // A permutation inside the cells is a table.
+    let mut graph = FactorGraph::new(vec![Constant::new("p1=[3,2,1,0,4,5,6,7]")], 4);
+    graph.add(&desc).unwrap();
+    graph.fix("A", 1, 1, &[0b0001, 0b0110]);
+    graph.propagate(2);
+    assert_eq!(graph.marginal("A", 1, 2, 1).unwrap()[0b1000], 1.0);
+    assert_eq!(graph.marginal("A", 1, 2, 2).unwrap()[0b0110], 1.0);
+}
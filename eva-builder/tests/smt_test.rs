@@ -0,0 +1,215 @@
+use eva_builder::constant::{Constant, ConstantTable};
+use eva_builder::description::Description;
+use eva_builder::interpreter::Interpreter;
+use eva_builder::leakage::{Leakage, LeakageSpec, LeakageType};
+use eva_builder::smt::*;
+use std::collections::HashMap;
+use std::fs;
+
+/// Two 2-bit S-boxes between a key addition and a rotation, over 4-bit words.
+fn toy() -> (Vec<Constant>, Description) {
+    let constants = vec![
+        Constant::new("s1=[0,0,1,0,0,1,0,1]"),
+        Constant::new("p1=[1,2,3,0]"),
+    ];
+    let desc = "1,x,A#-1#1#,K#0#0#,A#-1#2#,\n1,s,A#-1#2#,A#-1#3#,s1;s1;\n1,p1,A#-1#3#,A#1#2#,"
+        .parse()
+        .unwrap();
+    (constants, desc)
+}
+
+/// An S-expression of a script.
+#[derive(Debug, Clone)]
+enum Expr {
+    Atom(String),
+    List(Vec<Expr>),
+}
+
+fn parse(tokens: &mut std::iter::Peekable<std::vec::IntoIter<String>>) -> Expr {
+    let token = tokens.next().unwrap();
+    if token != "(" {
+        return Expr::Atom(token);
+    }
+    let mut items = vec![];
+    while tokens.peek().unwrap() != ")" {
+        items.push(parse(tokens));
+    }
+    tokens.next();
+    Expr::List(items)
+}
+
+/// The top-level expressions of a script, for the commands written by `SmtWriter`.
+fn script(smt: &str) -> Vec<Expr> {
+    let spaced = smt.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<String> = spaced.split_whitespace().map(String::from).collect();
+    let mut tokens = tokens.into_iter().peekable();
+    let mut exprs = vec![];
+    while tokens.peek().is_some() {
+        exprs.push(parse(&mut tokens));
+    }
+    exprs
+}
+
+/// Value of a term as bits, most significant first, where a Boolean is a single bit.
+fn eval(expr: &Expr, env: &HashMap<String, Vec<bool>>, funs: &HashMap<String, Expr>) -> Vec<bool> {
+    let items = match expr {
+        Expr::Atom(atom) if atom.starts_with("#b") => {
+            return atom[2..].chars().map(|c| c == '1').collect()
+        }
+        Expr::Atom(atom) => return env[atom].clone(),
+        Expr::List(items) => items,
+    };
+    let arg = |i: usize| eval(&items[i], env, funs);
+    match &items[0] {
+        Expr::List(head) => {
+            // ((_ extract hi lo) term), counting bits from 0 at the least significant.
+            let bound = |i: usize| match &head[i] {
+                Expr::Atom(n) => n.parse::<usize>().unwrap(),
+                _ => panic!("bad extract"),
+            };
+            let term = arg(1);
+            term[term.len() - 1 - bound(2)..term.len() - bound(3)].to_vec()
+        }
+        Expr::Atom(op) => match op.as_str() {
+            "=" => vec![arg(1) == arg(2)],
+            "bvxor" => arg(1).iter().zip(arg(2)).map(|(a, b)| a ^ b).collect(),
+            "concat" => [arg(1), arg(2)].concat(),
+            "ite" if arg(1)[0] => arg(2),
+            "ite" => arg(3),
+            "not" => vec![!arg(1)[0]],
+            function => {
+                let mut env = env.clone();
+                env.insert("x".to_string(), arg(1));
+                eval(&funs[function], &env, funs)
+            }
+        },
+    }
+}
+
+/// Whether all assertions of a script hold for the values of its constants.
+fn holds(smt: &str, env: &HashMap<String, Vec<bool>>) -> bool {
+    let mut funs = HashMap::new();
+    let mut holds = true;
+    for expr in script(smt).iter() {
+        if let Expr::List(items) = expr {
+            match items.as_slice() {
+                [Expr::Atom(command), Expr::Atom(name), _, _, body] if command == "define-fun" => {
+                    funs.insert(name.clone(), body.clone());
+                }
+                [Expr::Atom(command), term] if command == "assert" => {
+                    holds &= eval(term, env, &funs) == [true];
+                }
+                _ => (),
+            }
+        }
+    }
+    holds
+}
+
+fn nibble(x: u8) -> Vec<bool> {
+    (0..4).map(|i| (x >> (3 - i)) & 1 == 1).collect()
+}
+
+#[cfg(test)]
+#[test]
+fn toy_cipher() {
+    let (constants, desc) = toy();
+    let mut writer = SmtWriter::new(constants);
+    writer.add(&desc).unwrap();
+    writer.fix("A", 1, 1, &[false, true, true, false]);
+    let spec: LeakageSpec = "bits,A#1#3#1#2#,10,".parse().unwrap();
+    writer.observe(&spec).unwrap();
+    let mut leakage = Leakage::new(LeakageType::HammingWeight);
+    leakage.add_hamming_weight(&["K#0#0#1#".to_string()], 1);
+    writer.leak(leakage);
+    let smt = writer.to_smt2();
+    let lines: Vec<&str> = smt.lines().collect();
+    assert_eq!(
+        lines[..9],
+        [
+            "(set-logic QF_BV)",
+            "(set-option :produce-models true)",
+            "(declare-const |A#1#1#| (_ BitVec 4))",
+            "(declare-const |K#0#0#| (_ BitVec 4))",
+            "(declare-const |A#1#2#| (_ BitVec 4))",
+            "(declare-const |A#1#3#| (_ BitVec 4))",
+            "(define-fun |s1| ((x (_ BitVec 2))) (_ BitVec 2) \
+             (ite (= x #b00) #b00 (ite (= x #b01) #b10 (ite (= x #b10) #b01 #b10))))",
+            "(declare-const |A#2#1#| (_ BitVec 4))",
+            "(assert (= |A#1#2#| (bvxor |A#1#1#| |K#0#0#|)))",
+        ]
+    );
+    assert_eq!(
+        lines[9],
+        "(assert (= |A#1#3#| (concat (|s1| ((_ extract 3 2) |A#1#2#|)) \
+         (|s1| ((_ extract 1 0) |A#1#2#|)))))"
+    );
+    assert!(lines[10]
+        .starts_with("(assert (= |A#2#1#| (concat (concat (concat ((_ extract 2 2) |A#1#3#|)"));
+    assert_eq!(lines[11], "(assert (= |A#1#1#| #b0110))");
+    assert_eq!(lines[12], "(assert (= ((_ extract 3 2) |A#1#3#|) #b10))");
+    assert_eq!(lines[13], "(assert (= ((_ extract 3 3) |K#0#0#|) #b1))");
+    assert!(smt.ends_with("(check-sat)\n(get-model)\n"));
+
+    let spec: LeakageSpec = "bits,A#1#3#,10,".parse().unwrap();
+    assert_eq!(
+        writer.observe(&spec).unwrap_err().message,
+        "expected 4 bits"
+    );
+}
+
+#[test]
+fn toy_cipher_matches_interpreter() {
+    for key in 0..16 {
+        let (constants, desc) = toy();
+        let mut interpreter = Interpreter::new(constants);
+        interpreter.set("A", 1, 1, &nibble(0b0110));
+        interpreter.set("K", 0, 0, &nibble(key));
+        interpreter.run(&desc).unwrap();
+        let mut env = HashMap::new();
+        for &(name, round, index) in [
+            ("A", 1, 1),
+            ("K", 0, 0),
+            ("A", 1, 2),
+            ("A", 1, 3),
+            ("A", 2, 1),
+        ]
+        .iter()
+        {
+            let value = interpreter.get(name, round, index).unwrap();
+            env.insert(format!("|{}#{}#{}#|", name, round, index), value);
+        }
+
+        let (constants, desc) = toy();
+        let mut writer = SmtWriter::new(constants);
+        writer.add(&desc).unwrap();
+        writer.fix("A", 1, 1, &nibble(0b0110));
+        let smt = writer.to_smt2();
+        assert!(holds(&smt, &env), "key {:x}", key);
+        let output = env.get_mut("|A#2#1#|").unwrap();
+        output[0] = !output[0];
+        assert!(!holds(&smt, &env), "key {:x}", key);
+    }
+
+    let mut writer = SmtWriter::new(vec![Constant::new("p1=[4,0,1,2]")]);
+    let desc: Description = "1,p1,A#-1#1#,A#-1#2#,".parse().unwrap();
+    assert_eq!(writer.add(&desc).unwrap_err().message, "p1 refers to bit 4");
+}
+
+#[test]
+fn aes_with_sbox_arrays() {
+    let table: ConstantTable = fs::read_to_string("ciphers/aes/const")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut writer = SmtWriter::new(table.into_constants()).with_sbox_style(SboxStyle::Array);
+    for file in ["ciphers/aes/key", "ciphers/aes/enc"].iter() {
+        let desc: Description = fs::read_to_string(file).unwrap().parse().unwrap();
+        writer.add(&desc).unwrap();
+    }
+    let smt = writer.to_smt2();
+    assert!(smt.starts_with("(set-logic QF_ABV)\n"));
+    assert!(smt.contains("(declare-const |K1#0#0#| (_ BitVec 128))\n"));
+    assert!(smt.contains("(assert (= (select |s1| #b00000000) #b01100011))\n"));
+    assert_eq!(smt.matches("(select |s1| #b").count(), 256);
+}
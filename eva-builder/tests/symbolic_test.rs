@@ -0,0 +1,162 @@
+use eva_builder::generic::{Clause, Instance};
+use eva_builder::symbolic::{trace, Word};
+use eva_crypto::generic::{Cell, Field, Ops};
+use eva_crypto::{aes, led, present, skinny};
+
+mod common;
+
+fn words(name: &str, count: usize, width: u8) -> Vec<Word> {
+    (0..count)
+        .map(|i| Word::variable(&format!("{}#{}", name, i), width))
+        .collect()
+}
+
+fn fix(instance: &mut Instance, name: &str, values: &[u8], width: u8) {
+    for (i, &value) in values.iter().enumerate() {
+        for b in 0..width {
+            let bit = (value >> (width - 1 - b)) & 1 == 1;
+            let var = format!("{}#{}#{}#", name, i, b + 1);
+            instance.add_clause(Clause::new(false, vec![(&var, bit)]));
+        }
+    }
+}
+
+/// Evaluate the traced instance on the fixed inputs, and read back the bound outputs.
+fn evaluate(instance: &Instance, name: &str, count: usize) -> Vec<u8> {
+    let values = common::propagate(instance);
+    (0..count)
+        .map(|i| {
+            (1..=8).fold(0, |acc, b| {
+                let var = instance.variables[&format!("{}#{}#{}#", name, i, b)];
+                (acc << 1) | values[var as usize - 1].unwrap() as u8
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn symbolic_constants_fold() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let (ciphertext, instance) = trace(|| {
+        let key: Vec<Word> = key.iter().map(|&k| Word::from(k)).collect();
+        let plaintext: Vec<Word> = plaintext.iter().map(|&p| Word::from(p)).collect();
+        aes::encrypt_block(&plaintext, &aes::expand_key(&key), &aes::SBOX)
+    });
+    let ciphertext: Vec<u8> = ciphertext.iter().map(|w| w.value().unwrap()).collect();
+    assert_eq!(ciphertext, aes::AES::new(&key).encrypt(&plaintext));
+    assert!(instance.equations.is_empty());
+}
+
+#[test]
+fn symbolic_aes() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let (_, mut instance) = trace(|| {
+        let round_keys = aes::expand_key(&words("K", 16, 8));
+        let ciphertext = aes::encrypt_block(&words("P", 16, 8), &round_keys, &aes::SBOX);
+        for (i, word) in ciphertext.iter().enumerate() {
+            word.bind(&format!("C#{}", i));
+        }
+    });
+    fix(&mut instance, "K", &key, 8);
+    fix(&mut instance, "P", &plaintext, 8);
+    assert_eq!(
+        evaluate(&instance, "C", 16),
+        aes::AES::new(&key).encrypt(&plaintext)
+    );
+}
+
+#[test]
+fn symbolic_nibble_ciphers() {
+    let key: Vec<u8> = (0..16).map(|x| (x * 7) % 16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| (x * 5 + 3) % 16).collect();
+
+    let (_, mut instance) = trace(|| {
+        let ciphertext = led::encrypt_block(&words("P", 16, 4), &words("K", 16, 4), &led::SBOX);
+        for (i, word) in ciphertext.iter().enumerate() {
+            word.bind(&format!("C#{}", i));
+        }
+    });
+    fix(&mut instance, "K", &key, 4);
+    fix(&mut instance, "P", &plaintext, 4);
+    assert_eq!(
+        evaluate(&instance, "C", 16),
+        led::LED::new(&key).encrypt(&plaintext)
+    );
+
+    let mut key = key;
+    key.extend_from_slice(&[0xa, 0xb, 0xc, 0xd]);
+    let (_, mut instance) = trace(|| {
+        let round_keys = present::expand_key(&words("K", 20, 4), 32);
+        let ciphertext = present::encrypt_block(&words("P", 16, 4), &round_keys, &present::SBOX);
+        for (i, word) in ciphertext.iter().enumerate() {
+            word.bind(&format!("C#{}", i));
+        }
+    });
+    fix(&mut instance, "K", &key, 4);
+    fix(&mut instance, "P", &plaintext, 4);
+    assert_eq!(
+        evaluate(&instance, "C", 16),
+        present::PRESENT::new(&key).encrypt(&plaintext).to_vec()
+    );
+}
+
+#[test]
+fn symbolic_skinny() {
+    for &(cell_size, sbox) in [(4, &skinny::SBOX_4[..]), (8, &skinny::SBOX_8[..])].iter() {
+        let mask = 0xffu8 >> (8 - cell_size);
+        let key: Vec<u8> = (0..16).map(|x| (x * 7 + 1) & mask).collect();
+        let plaintext: Vec<u8> = (0..16).map(|x| (x * 13 + 3) & mask).collect();
+        let (_, mut instance) = trace(|| {
+            let round_keys = skinny::expand_key(&words("K", 16, cell_size), cell_size);
+            let plaintext = words("P", 16, cell_size);
+            let ciphertext = skinny::encrypt_block(&plaintext, &round_keys, sbox, cell_size);
+            for (i, word) in ciphertext.iter().enumerate() {
+                word.bind(&format!("C#{}", i));
+            }
+        });
+        fix(&mut instance, "K", &key, cell_size);
+        fix(&mut instance, "P", &plaintext, cell_size);
+        assert_eq!(
+            evaluate(&instance, "C", 16),
+            skinny::SKINNY::new(&key, cell_size)
+                .encrypt(&plaintext)
+                .to_vec()
+        );
+    }
+}
+
+#[test]
+fn symbolic_shifts() {
+    let (word, _) = trace(|| Word::variable("x", 4).shl(1).shr(2));
+    assert_eq!(word.value(), None);
+    assert_eq!(Word::from(0b1001_0110).shl(2).value(), Some(0b0101_1000));
+}
+
+#[test]
+fn symbolic_gmul() {
+    for &(field, width) in [(Field::GF16, 4), (Field::GF256, 8)].iter() {
+        let pairs: Vec<(u8, u8)> = (0..16u32)
+            .map(|i| {
+                (
+                    (i * 37 + 5) as u8 >> (8 - width),
+                    (i * 91 + 2) as u8 >> (8 - width),
+                )
+            })
+            .collect();
+        let (_, mut instance) = trace(|| {
+            for i in 0..pairs.len() {
+                let a = Word::variable(&format!("A#{}", i), width);
+                let b = Word::variable(&format!("B#{}", i), width);
+                a.gmul(&b, field).bind(&format!("C#{}", i));
+            }
+        });
+        let (a, b): (Vec<u8>, Vec<u8>) = pairs.iter().cloned().unzip();
+        fix(&mut instance, "A", &a, width);
+        fix(&mut instance, "B", &b, width);
+        let expected: Vec<u8> = pairs.iter().map(|(x, y)| x.gmul(y, field)).collect();
+        assert_eq!(evaluate(&instance, "C", pairs.len()), expected);
+    }
+}
@@ -27,7 +27,7 @@ fn main() -> io::Result<()> {
         for x in 0..4 {
             for y in 0..4 {
                 let e = state[x]
-                    .gmul(&RMDS[y], 4)
+                    .gmul(&RMDS[y], Field::GF16)
                     .iter()
                     .fold(0x00, |res, i| res ^ i);
                 stats[x * 4 + y as usize][e as usize] += 1;
@@ -37,7 +37,7 @@ fn main() -> io::Result<()> {
 
     let mut kc = [0u8; 16];
     let mut size = 1.0f32;
-    let fault_state = create_u8x4x4(&cipher.key[0..16]).gmul(&RMDS, 4);
+    let fault_state = create_u8x4x4(&cipher.key[0..16]).gmul(&RMDS, Field::GF16);
     let faults = create_u8x4x4(&[0xcu8; 16]);
 
     for i in 0..16 {
@@ -54,7 +54,7 @@ fn main() -> io::Result<()> {
     println!("Target: {:?}", create_u8x4x4(&cipher.key));
     println!(
         "Result: {:?}",
-        transpose(&create_u8x4x4(&kc)).xor(&faults).gmul(&MDS, 4)
+        transpose(&create_u8x4x4(&kc)).xor(&faults).gmul(&MDS, Field::GF16)
     );
     println!("Residue Entropy: {}", size.log2());
 
@@ -1,4 +1,4 @@
-use super::generic::{create_u8x4x4, transpose, u8x4x4, Ops, Permutation};
+use super::generic::{create_u8x4x4, lift, transpose, u8x4x4, Cell, Field, Ops, Permutation};
 
 pub struct AES {
     pub round_keys: Vec<AESstate>,
@@ -11,12 +11,8 @@ type AESstate = u8x4x4;
 impl AES {
     /// Initialize an AES cipher.
     pub fn new(key: &[u8]) -> AES {
-        let rounds = 10 + (key.len() / 4) - 4;
-        let mut round_keys = vec![[[0; 4]; 4]; rounds + 1];
-        key_expansion(key, &mut round_keys);
-
         AES {
-            round_keys,
+            round_keys: expand_key(key),
             sbox: SBOX,
             rsbox: RSBOX,
         }
@@ -24,22 +20,7 @@ impl AES {
 
     /// Encrypt a block.
     pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
-        let rounds = self.round_keys.len() - 1;
-        let mut state = create_u8x4x4(data);
-        state = add_round_key(&state, &self.round_keys[0]);
-
-        for i in 1..rounds {
-            state = sub_bytes(&state, &self.sbox);
-            state = shift_rows(&state);
-            state = mix_columns(&state);
-            state = add_round_key(&state, &self.round_keys[i]);
-        }
-
-        state = sub_bytes(&state, &self.sbox);
-        state = shift_rows(&state);
-        state = add_round_key(&state, &self.round_keys[rounds]);
-
-        state.concat()
+        encrypt_block(data, &self.round_keys, &self.sbox)
     }
 
     /// Decrypt a block.
@@ -89,7 +70,35 @@ impl AES {
     }
 }
 
-fn key_expansion(key: &[u8], round_keys: &mut [AESstate]) {
+/// Encrypt a block of any cell type, e.g. symbolic bytes.
+pub fn encrypt_block<T: Cell>(data: &[T], round_keys: &[[[T; 4]; 4]], sbox: &[u8]) -> Vec<T> {
+    let rounds = round_keys.len() - 1;
+    let mut state = create_u8x4x4(data);
+    state = add_round_key(&state, &round_keys[0]);
+
+    for round_key in round_keys[1..rounds].iter() {
+        state = sub_bytes(&state, sbox);
+        state = shift_rows(&state);
+        state = mix_columns(&state);
+        state = add_round_key(&state, round_key);
+    }
+
+    state = sub_bytes(&state, sbox);
+    state = shift_rows(&state);
+    state = add_round_key(&state, &round_keys[rounds]);
+
+    state.concat()
+}
+
+/// Expand a key of 16, 24 or 32 cells into the round keys.
+pub fn expand_key<T: Cell>(key: &[T]) -> Vec<[[T; 4]; 4]> {
+    let rounds = 10 + (key.len() / 4) - 4;
+    let mut round_keys = vec![[[T::from(0); 4]; 4]; rounds + 1];
+    key_expansion(key, &mut round_keys);
+    round_keys
+}
+
+fn key_expansion<T: Cell>(key: &[T], round_keys: &mut [[[T; 4]; 4]]) {
     let key_words = key.len() / 4;
     debug_assert!(match key_words {
         4 | 6 | 8 => true,
@@ -107,7 +116,7 @@ fn key_expansion(key: &[u8], round_keys: &mut [AESstate]) {
         let mut tmp = round_keys[(i - 1) / 4][(i - 1) % 4];
         if i % key_words == 0 {
             tmp = tmp.lrot().sub_sbox(&SBOX);
-            tmp[0] ^= RCON[i / key_words - 1];
+            tmp[0] = tmp[0].xor(&T::from(RCON[i / key_words - 1]));
         } else if key_words > 6 && i % key_words == 4 {
             tmp = tmp.sub_sbox(&SBOX);
         };
@@ -115,29 +124,29 @@ fn key_expansion(key: &[u8], round_keys: &mut [AESstate]) {
     }
 }
 
-fn add_round_key(state: &AESstate, round_key: &AESstate) -> AESstate {
+fn add_round_key<T: Cell>(state: &[[T; 4]; 4], round_key: &[[T; 4]; 4]) -> [[T; 4]; 4] {
     state.xor(round_key)
 }
-fn sub_bytes(state: &AESstate, sbox: &[u8]) -> AESstate {
+fn sub_bytes<T: Cell>(state: &[[T; 4]; 4], sbox: &[u8]) -> [[T; 4]; 4] {
     state.sub_sbox(sbox)
 }
 fn inv_sub_bytes(state: &AESstate, rsbox: &[u8]) -> AESstate {
     state.sub_sbox(rsbox)
 }
-fn shift_rows(state: &AESstate) -> AESstate {
+fn shift_rows<T: Cell>(state: &[[T; 4]; 4]) -> [[T; 4]; 4] {
     transpose(&transpose(state).lrot())
 }
 fn inv_shift_rows(state: &AESstate) -> AESstate {
     transpose(&transpose(state).rrot())
 }
-fn mix_columns(state: &AESstate) -> AESstate {
-    [
+fn mix_columns<T: Cell>(state: &[[T; 4]; 4]) -> [[T; 4]; 4] {
+    lift(&[
         [0x02, 0x01, 0x01, 0x03],
         [0x03, 0x02, 0x01, 0x01],
         [0x01, 0x03, 0x02, 0x01],
         [0x01, 0x01, 0x03, 0x02],
-    ]
-    .gmul(&state, 8)
+    ])
+    .gmul(&state, Field::GF256)
 }
 fn inv_mix_columns(state: &AESstate) -> AESstate {
     [
@@ -146,7 +155,7 @@ fn inv_mix_columns(state: &AESstate) -> AESstate {
         [0x0d, 0x0b, 0x0e, 0x09],
         [0x09, 0x0d, 0x0b, 0x0e],
     ]
-    .gmul(&state, 8)
+    .gmul(&state, Field::GF256)
 }
 
 pub static SBOX: [u8; 256] = [
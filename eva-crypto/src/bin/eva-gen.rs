@@ -0,0 +1,346 @@
+//! Generate plaintext/ciphertext datasets with any cipher of eva-crypto.
+extern crate rand;
+
+use eva_crypto::aes::AES;
+use eva_crypto::blowfish::BlowFish;
+use eva_crypto::dataset::{DatasetWriter, FaultModel, Header, Packing};
+use eva_crypto::led::LED;
+use eva_crypto::npy::save_trace_set;
+use eva_crypto::present::PRESENT;
+use eva_crypto::skinny::SKINNY;
+use eva_crypto::sm4::SM4;
+use eva_crypto::traces::TraceSet;
+use eva_crypto::trs::{SampleCoding, TrsHeader, TrsWriter};
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "Usage: eva-gen --cipher NAME --key HEX --output PATH [options]
+
+Ciphers:
+    aes, led, present, skinny64, skinny128, sm4, blowfish
+    Nibble ciphers (led, present, skinny64) take one hex digit per key cell.
+
+Options:
+    --fault SPEC        none (default), sbox:INDEX:VALUE or sbox-table:HEX
+    --count N           number of records (default 10000)
+    --plaintext DIST    byte or nibble, defaults to the cell size of the cipher
+    --seed N            seed of the plaintext generator, drawn at random if absent
+    --format FORMAT     eva (default), raw, npz or trs
+                        raw writes msg.bin and out.bin into the PATH directory,
+                        npz the arrays read by npy::load_trace_set, with empty traces";
+
+enum Cipher {
+    Aes(Box<AES>),
+    Led(LED),
+    Present(PRESENT),
+    Skinny(SKINNY),
+    Sm4(SM4),
+    BlowFish(Box<BlowFish>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Eva,
+    Raw,
+    Npz,
+    Trs,
+}
+
+struct Options {
+    cipher: String,
+    key: String,
+    output: PathBuf,
+    fault: FaultModel,
+    count: u64,
+    packing: Option<Packing>,
+    seed: Option<u64>,
+    format: Format,
+}
+
+impl Cipher {
+    fn new(name: &str, key: &[u8], fault: &FaultModel) -> Result<Self, String> {
+        let cipher = match name {
+            "aes" if [16, 24, 32].contains(&key.len()) => Cipher::Aes(Box::new(AES::new(key))),
+            "led" if [16, 20, 32].contains(&key.len()) => Cipher::Led(LED::new(key)),
+            "present" if [20, 32].contains(&key.len()) => Cipher::Present(PRESENT::new(key)),
+            "skinny64" if [16, 32, 48].contains(&key.len()) => Cipher::Skinny(SKINNY::new(key, 4)),
+            "skinny128" if [16, 32, 48].contains(&key.len()) => Cipher::Skinny(SKINNY::new(key, 8)),
+            "sm4" if key.len() == 16 => Cipher::Sm4(SM4::new(key)),
+            "blowfish"
+                if !key.is_empty()
+                    && key.chunks_exact(4).remainder().is_empty()
+                    && key.len() <= 72 =>
+            {
+                let words: Vec<u32> = key
+                    .chunks(4)
+                    .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+                    .collect();
+                Cipher::BlowFish(Box::new(BlowFish::new(&words)))
+            }
+            "aes" | "led" | "present" | "skinny64" | "skinny128" | "sm4" | "blowfish" => {
+                return Err(format!("invalid key length {} for {}", key.len(), name))
+            }
+            _ => return Err(format!("unknown cipher {}", name)),
+        };
+        let sbox_len = match cell_packing(name) {
+            Packing::Byte => 256,
+            Packing::Nibble => 16,
+        };
+        match (cipher, fault) {
+            (cipher, FaultModel::None) => Ok(cipher),
+            (_, FaultModel::SboxByte { index, value })
+                if *index >= sbox_len || *value as usize >= sbox_len =>
+            {
+                Err(format!("S-box fault out of range for {}", name))
+            }
+            (Cipher::Aes(c), &FaultModel::SboxByte { index, value }) => {
+                Ok(Cipher::Aes(Box::new(c.with_sbox_byte(index, value))))
+            }
+            (Cipher::Led(c), &FaultModel::SboxByte { index, value }) => {
+                Ok(Cipher::Led(c.with_sbox_byte(index, value)))
+            }
+            (Cipher::Present(c), &FaultModel::SboxByte { index, value }) => {
+                Ok(Cipher::Present(c.with_sbox_byte(index, value)))
+            }
+            (Cipher::Skinny(c), &FaultModel::SboxByte { index, value }) => {
+                Ok(Cipher::Skinny(c.with_sbox_byte(index, value)))
+            }
+            (Cipher::Aes(c), FaultModel::Sbox(sbox)) if sbox.len() == 256 => {
+                let mut table = [0u8; 256];
+                table.copy_from_slice(sbox);
+                Ok(Cipher::Aes(Box::new(c.with_sbox(table))))
+            }
+            _ => Err(format!("fault model not supported for {}", name)),
+        }
+    }
+
+    fn block_size(&self) -> usize {
+        match self {
+            Cipher::BlowFish(_) => 8,
+            _ => 16,
+        }
+    }
+
+    fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Cipher::Aes(c) => c.encrypt(data),
+            Cipher::Led(c) => c.encrypt(data),
+            Cipher::Present(c) => c.encrypt(data).to_vec(),
+            Cipher::Skinny(c) => c.encrypt(data).to_vec(),
+            Cipher::Sm4(c) => c.encrypt(data),
+            Cipher::BlowFish(c) => {
+                let mut block = [0u8; 8];
+                block.copy_from_slice(data);
+                c.encrypt(u64::from_be_bytes(block)).to_be_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// Nibble ciphers store one 4-bit cell per byte, for keys as well as for blocks.
+fn cell_packing(cipher: &str) -> Packing {
+    match cipher {
+        "led" | "present" | "skinny64" => Packing::Nibble,
+        _ => Packing::Byte,
+    }
+}
+
+fn parse_hex(hex: &str, nibbles: bool) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("invalid hex string {}", hex))?;
+    if nibbles {
+        Ok(digits)
+    } else if digits.len() % 2 == 1 {
+        Err(format!("odd number of hex digits in {}", hex))
+    } else {
+        Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+    }
+}
+
+fn parse_fault(spec: &str) -> Result<FaultModel, String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    let number = |s: &str| {
+        let (digits, radix) = match s.strip_prefix("0x") {
+            Some(hex) => (hex, 16),
+            None => (s, 10),
+        };
+        usize::from_str_radix(digits, radix).map_err(|_| format!("invalid number {}", s))
+    };
+    match parts.as_slice() {
+        ["none"] => Ok(FaultModel::None),
+        ["sbox", index, value] => Ok(FaultModel::SboxByte {
+            index: number(index)?,
+            value: match number(value)? {
+                value if value <= 0xff => value as u8,
+                _ => return Err(format!("fault value {} is not a byte", value)),
+            },
+        }),
+        ["sbox-table", table] => Ok(FaultModel::Sbox(parse_hex(table, false)?)),
+        _ => Err(format!("invalid fault specification {}", spec)),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        cipher: String::new(),
+        key: String::new(),
+        output: PathBuf::new(),
+        fault: FaultModel::None,
+        count: 10000,
+        packing: None,
+        seed: None,
+        format: Format::Eva,
+    };
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(|s| s.as_str())
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+        match flag.as_str() {
+            "--cipher" => options.cipher = value()?.to_lowercase(),
+            "--key" => options.key = value()?.to_string(),
+            "--output" => options.output = PathBuf::from(value()?),
+            "--fault" => options.fault = parse_fault(value()?)?,
+            "--count" => options.count = value()?.parse().map_err(|_| "invalid count")?,
+            "--seed" => options.seed = Some(value()?.parse().map_err(|_| "invalid seed")?),
+            "--plaintext" => {
+                options.packing = match value()? {
+                    "byte" => Some(Packing::Byte),
+                    "nibble" => Some(Packing::Nibble),
+                    other => return Err(format!("unknown plaintext distribution {}", other)),
+                }
+            }
+            "--format" => {
+                options.format = match value()? {
+                    "eva" => Format::Eva,
+                    "raw" => Format::Raw,
+                    "npz" => Format::Npz,
+                    "trs" => Format::Trs,
+                    other => return Err(format!("unknown format {}", other)),
+                }
+            }
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+    if options.cipher.is_empty() || options.key.is_empty() || options.output.as_os_str().is_empty()
+    {
+        return Err("--cipher, --key and --output are required".to_string());
+    }
+    Ok(options)
+}
+
+fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    File::create(path).map(BufWriter::new)
+}
+
+fn generate(options: Options) -> Result<(), String> {
+    let cells = cell_packing(&options.cipher);
+    let key = parse_hex(&options.key, cells == Packing::Nibble)?;
+    let cipher = Cipher::new(&options.cipher, &key, &options.fault)?;
+    let packing = options.packing.unwrap_or(cells);
+    if packing == Packing::Byte && cells == Packing::Nibble {
+        return Err(format!("{} takes nibble plaintexts", options.cipher));
+    }
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let dist = match packing {
+        Packing::Byte => Uniform::from(0..=255u8),
+        Packing::Nibble => Uniform::from(0..16u8),
+    };
+
+    let block_size = cipher.block_size();
+    let mut records = (0..options.count).map(|_| {
+        let plaintext: Vec<u8> = (0..block_size).map(|_| dist.sample(&mut rng)).collect();
+        let ciphertext = cipher.encrypt(&plaintext);
+        (plaintext, ciphertext)
+    });
+    let header = Header {
+        cipher: options.cipher.clone(),
+        key: key.clone(),
+        packing,
+        block_size,
+        fault: options.fault.clone(),
+        seed: Some(seed),
+        count: options.count,
+    };
+
+    let output = &options.output;
+    let result = match options.format {
+        Format::Eva => create(output).and_then(|f| {
+            let mut writer = DatasetWriter::new(f, &header)?;
+            for (plaintext, ciphertext) in records {
+                writer.write(&plaintext, &ciphertext)?;
+            }
+            writer.finish().map(|_| ())
+        }),
+        Format::Raw => fs::create_dir_all(output).and_then(|_| {
+            let mut msg = create(&output.join("msg.bin"))?;
+            let mut out = create(&output.join("out.bin"))?;
+            for (plaintext, ciphertext) in records {
+                msg.write_all(&plaintext)?;
+                out.write_all(&ciphertext)?;
+            }
+            msg.flush().and_then(|_| out.flush())
+        }),
+        Format::Npz => create(output).and_then(|f| {
+            // The layout of `save_trace_set`, with empty traces.
+            let mut set = TraceSet::new();
+            for (plaintext, ciphertext) in records {
+                set.traces.push(vec![]);
+                set.plaintexts.push(plaintext);
+                set.ciphertexts.push(ciphertext);
+                set.keys.push(key.clone());
+            }
+            save_trace_set(f, &set).map(|_| ())
+        }),
+        Format::Trs => create(output).and_then(|f| {
+            let mut trs_header = TrsHeader::new(
+                options.count as usize,
+                0,
+                SampleCoding::Int8,
+                2 * block_size,
+            );
+            trs_header.global_title = options.cipher.clone();
+            let mut writer = TrsWriter::new(f, trs_header)?;
+            // Stream in chunks, the traces themselves are empty.
+            loop {
+                let mut set = TraceSet::new();
+                for (plaintext, ciphertext) in records.by_ref().take(1024) {
+                    set.traces.push(vec![]);
+                    set.plaintexts.push(plaintext);
+                    set.ciphertexts.push(ciphertext);
+                }
+                if set.is_empty() {
+                    break;
+                }
+                writer.write_chunk(&set)?;
+            }
+            writer.finish().map(|_| ())
+        }),
+    };
+    result.map_err(|e| format!("{}: {}", output.display(), e))?;
+    eprintln!("Wrote {} records with seed {}", options.count, seed);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    if let Err(e) = parse_args(&args).and_then(generate) {
+        eprintln!("eva-gen: {}", e);
+        process::exit(1);
+    }
+}
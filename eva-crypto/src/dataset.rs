@@ -0,0 +1,339 @@
+//! A self-describing container for plaintext/ciphertext datasets.
+//!
+//! A container starts with a header recording how the data was produced, followed by `count`
+//! records of `block_size` plaintext bytes and `block_size` ciphertext bytes. All integers are
+//! little-endian.
+//!
+//! ```text
+//! magic "EVAD" | version u16 | cipher | key | packing u8 | block_size u16 | fault | seed | count u64
+//! ```
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub const MAGIC: [u8; 4] = *b"EVAD";
+pub const VERSION: u16 = 1;
+
+/// How the cells of a block are stored in its bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Packing {
+    /// One 8-bit cell per byte.
+    Byte,
+    /// One 4-bit cell per byte, in the low nibble.
+    Nibble,
+}
+
+/// The fault injected into the cipher which produced the ciphertexts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultModel {
+    None,
+    /// A single S-box entry replaced, as done by `with_sbox_byte`.
+    SboxByte {
+        index: usize,
+        value: u8,
+    },
+    /// The whole S-box replaced, as done by `with_sbox`.
+    Sbox(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub cipher: String,
+    pub key: Vec<u8>,
+    pub packing: Packing,
+    /// Number of bytes in a plaintext or a ciphertext.
+    pub block_size: usize,
+    pub fault: FaultModel,
+    /// Seed of the generator of the plaintexts, if it was recorded.
+    pub seed: Option<u64>,
+    /// Number of records following the header.
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub plaintext: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Writes a header, then the records one by one.
+pub struct DatasetWriter<W: Write> {
+    inner: W,
+    block_size: usize,
+    count: u64,
+    written: u64,
+}
+
+/// Reads a header, then iterates over the records.
+pub struct DatasetReader<R: Read> {
+    inner: R,
+    header: Header,
+    read: u64,
+}
+
+/// Iterates over the records of the old header-less `msg.bin`/`out.bin` file pairs.
+pub struct RawReader<R: Read> {
+    msg: R,
+    out: R,
+    block_size: usize,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Header {
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        write_bytes(w, self.cipher.as_bytes())?;
+        write_bytes(w, &self.key)?;
+        w.write_all(&[match self.packing {
+            Packing::Byte => 0,
+            Packing::Nibble => 1,
+        }])?;
+        if self.block_size > u16::MAX as usize {
+            return Err(invalid("block size too large"));
+        }
+        w.write_all(&(self.block_size as u16).to_le_bytes())?;
+        match &self.fault {
+            FaultModel::None => w.write_all(&[0])?,
+            FaultModel::SboxByte { index, value } => {
+                w.write_all(&[1])?;
+                w.write_all(&(*index as u32).to_le_bytes())?;
+                w.write_all(&[*value])?;
+            }
+            FaultModel::Sbox(sbox) => {
+                w.write_all(&[2])?;
+                write_bytes(w, sbox)?;
+            }
+        }
+        match self.seed {
+            None => w.write_all(&[0])?,
+            Some(seed) => {
+                w.write_all(&[1])?;
+                w.write_all(&seed.to_le_bytes())?;
+            }
+        }
+        w.write_all(&self.count.to_le_bytes())
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Header> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not an eva dataset"));
+        }
+        let version = read_u16(r)?;
+        if version == 0 || version > VERSION {
+            return Err(invalid(&format!("unsupported dataset version {}", version)));
+        }
+        let cipher = String::from_utf8(read_bytes(r)?).map_err(|_| invalid("cipher name"))?;
+        let key = read_bytes(r)?;
+        let packing = match read_u8(r)? {
+            0 => Packing::Byte,
+            1 => Packing::Nibble,
+            _ => return Err(invalid("unknown cell packing")),
+        };
+        let block_size = read_u16(r)? as usize;
+        let fault = match read_u8(r)? {
+            0 => FaultModel::None,
+            1 => {
+                let mut index = [0u8; 4];
+                r.read_exact(&mut index)?;
+                FaultModel::SboxByte {
+                    index: u32::from_le_bytes(index) as usize,
+                    value: read_u8(r)?,
+                }
+            }
+            2 => FaultModel::Sbox(read_bytes(r)?),
+            _ => return Err(invalid("unknown fault model")),
+        };
+        let seed = match read_u8(r)? {
+            0 => None,
+            1 => Some(read_u64(r)?),
+            _ => return Err(invalid("seed flag")),
+        };
+        let count = read_u64(r)?;
+        Ok(Header {
+            cipher,
+            key,
+            packing,
+            block_size,
+            fault,
+            seed,
+            count,
+        })
+    }
+}
+
+impl<W: Write> DatasetWriter<W> {
+    pub fn new(mut inner: W, header: &Header) -> io::Result<Self> {
+        header.write_to(&mut inner)?;
+        Ok(DatasetWriter {
+            inner,
+            block_size: header.block_size,
+            count: header.count,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, plaintext: &[u8], ciphertext: &[u8]) -> io::Result<()> {
+        if plaintext.len() != self.block_size || ciphertext.len() != self.block_size {
+            return Err(invalid("record does not match the block size"));
+        }
+        if self.written == self.count {
+            return Err(invalid("more records than announced in the header"));
+        }
+        self.inner.write_all(plaintext)?;
+        self.inner.write_all(ciphertext)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Check that all announced records were written, and hand back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.written != self.count {
+            return Err(invalid("fewer records than announced in the header"));
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<R: Read> DatasetReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let header = Header::read_from(&mut inner)?;
+        Ok(DatasetReader {
+            inner,
+            header,
+            read: 0,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<R: Read> Iterator for DatasetReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.read == self.header.count {
+            return None;
+        }
+        self.read += 1;
+        let mut plaintext = vec![0u8; self.header.block_size];
+        let mut ciphertext = vec![0u8; self.header.block_size];
+        Some(
+            self.inner
+                .read_exact(&mut plaintext)
+                .and_then(|_| self.inner.read_exact(&mut ciphertext))
+                .map(|_| Record {
+                    plaintext,
+                    ciphertext,
+                }),
+        )
+    }
+}
+
+impl<R: Read> RawReader<R> {
+    pub fn new(msg: R, out: R, block_size: usize) -> Self {
+        RawReader {
+            msg,
+            out,
+            block_size,
+        }
+    }
+}
+
+impl<R: Read> Iterator for RawReader<R> {
+    type Item = io::Result<Record>;
+
+    /// Stops at the end of `msg.bin`. A partial last record or a truncated `out.bin` is an error.
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut plaintext = vec![0u8; self.block_size];
+        match read_full(&mut self.msg, &mut plaintext) {
+            Ok(0) => return None,
+            Ok(n) if n < self.block_size => return Some(Err(invalid("partial record in msg.bin"))),
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+        let mut ciphertext = vec![0u8; self.block_size];
+        Some(self.out.read_exact(&mut ciphertext).map(|_| Record {
+            plaintext,
+            ciphertext,
+        }))
+    }
+}
+
+/// Convert a raw `msg.bin`/`out.bin` pair into a container, one record at a time. The record
+/// count of `header` is replaced by the number of records found, written over the header once
+/// they are all copied.
+pub fn import_raw<R: Read, W: Write + Seek>(
+    msg: R,
+    out: R,
+    mut header: Header,
+    mut dest: W,
+) -> io::Result<W> {
+    let start = dest.stream_position()?;
+    header.count = 0;
+    header.write_to(&mut dest)?;
+    for record in RawReader::new(msg, out, header.block_size) {
+        let record = record?;
+        dest.write_all(&record.plaintext)?;
+        dest.write_all(&record.ciphertext)?;
+        header.count += 1;
+    }
+    let end = dest.stream_position()?;
+    dest.seek(SeekFrom::Start(start))?;
+    header.write_to(&mut dest)?;
+    dest.seek(SeekFrom::Start(end))?;
+    dest.flush()?;
+    Ok(dest)
+}
+
+/// Read until `buf` is full or the end of the input, returning the number of bytes read.
+fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    if bytes.len() > u16::MAX as usize {
+        return Err(invalid("field too long"));
+    }
+    w.write_all(&(bytes.len() as u16).to_le_bytes())?;
+    w.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; read_u16(r)? as usize];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
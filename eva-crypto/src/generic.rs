@@ -9,14 +9,49 @@ pub trait Ops {
     fn rrot(&self) -> Self;
     fn xor(&self, rhs: &Self) -> Self;
     fn and(&self, rhs: &Self) -> Self;
-    fn gmul(&self, rhs: &Self, bits: u8) -> Self;
+    fn gmul(&self, rhs: &Self, field: Field) -> Self;
+}
+
+/// A binary field the cells of a state are multiplied in by `Ops::gmul`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// GF(2^4) modulo x^4 + x + 1, for the nibbles of LED and SKINNY-64.
+    GF16,
+    /// GF(2^8) modulo x^8 + x^4 + x^3 + x + 1, as in AES.
+    GF256,
+}
+
+impl Field {
+    /// Width of an element in bits.
+    pub fn bits(self) -> u8 {
+        match self {
+            Field::GF16 => 4,
+            Field::GF256 => 8,
+        }
+    }
+
+    /// The irreducible polynomial without its leading term, in bits.
+    pub fn poly(self) -> u8 {
+        match self {
+            Field::GF16 => 0x03,
+            Field::GF256 => 0x1b,
+        }
+    }
 }
 
 pub trait Permutation {
     fn sub_sbox(&self, sbox: &[u8]) -> Self;
 }
 
-impl Ops for u8x4x4 {
+/// An element of a cipher state. Besides `u8`, symbolic types can implement it to run the
+/// ciphers on unknown values.
+pub trait Cell: Ops + Permutation + Copy + From<u8> {
+    /// Shift left by `n` bits, dropping the bits shifted out of the byte.
+    fn shl(&self, n: u32) -> Self;
+    fn shr(&self, n: u32) -> Self;
+}
+
+impl<T: Cell> Ops for [[T; 4]; 4] {
     /// ```
     /// use eva_crypto::generic::Ops;
     /// assert_eq!(
@@ -87,7 +122,7 @@ impl Ops for u8x4x4 {
     }
 
     /// ```
-    /// use eva_crypto::generic::Ops;
+    /// use eva_crypto::generic::{Field, Ops};
     /// assert_eq!(
     ///     [
     ///        [0x0e, 0x09, 0x0d, 0x0b],
@@ -100,39 +135,39 @@ impl Ops for u8x4x4 {
     ///        [0x03, 0x02, 0x01, 0x01],
     ///        [0x01, 0x03, 0x02, 0x01],
     ///        [0x01, 0x01, 0x03, 0x02],
-    ///    ], 8),
+    ///    ], Field::GF256),
     ///    [[1, 0, 0, 0]; 4].rrot()
     /// );
     /// ```
-    fn gmul(&self, rhs: &Self, bits: u8) -> Self {
+    fn gmul(&self, rhs: &Self, field: Field) -> Self {
         [
-            self[0].gmul(&[rhs[0][0]; 4], bits),
-            self[0].gmul(&[rhs[1][0]; 4], bits),
-            self[0].gmul(&[rhs[2][0]; 4], bits),
-            self[0].gmul(&[rhs[3][0]; 4], bits),
+            self[0].gmul(&[rhs[0][0]; 4], field),
+            self[0].gmul(&[rhs[1][0]; 4], field),
+            self[0].gmul(&[rhs[2][0]; 4], field),
+            self[0].gmul(&[rhs[3][0]; 4], field),
         ]
         .xor(&[
-            self[1].gmul(&[rhs[0][1]; 4], bits),
-            self[1].gmul(&[rhs[1][1]; 4], bits),
-            self[1].gmul(&[rhs[2][1]; 4], bits),
-            self[1].gmul(&[rhs[3][1]; 4], bits),
+            self[1].gmul(&[rhs[0][1]; 4], field),
+            self[1].gmul(&[rhs[1][1]; 4], field),
+            self[1].gmul(&[rhs[2][1]; 4], field),
+            self[1].gmul(&[rhs[3][1]; 4], field),
         ])
         .xor(&[
-            self[2].gmul(&[rhs[0][2]; 4], bits),
-            self[2].gmul(&[rhs[1][2]; 4], bits),
-            self[2].gmul(&[rhs[2][2]; 4], bits),
-            self[2].gmul(&[rhs[3][2]; 4], bits),
+            self[2].gmul(&[rhs[0][2]; 4], field),
+            self[2].gmul(&[rhs[1][2]; 4], field),
+            self[2].gmul(&[rhs[2][2]; 4], field),
+            self[2].gmul(&[rhs[3][2]; 4], field),
         ])
         .xor(&[
-            self[3].gmul(&[rhs[0][3]; 4], bits),
-            self[3].gmul(&[rhs[1][3]; 4], bits),
-            self[3].gmul(&[rhs[2][3]; 4], bits),
-            self[3].gmul(&[rhs[3][3]; 4], bits),
+            self[3].gmul(&[rhs[0][3]; 4], field),
+            self[3].gmul(&[rhs[1][3]; 4], field),
+            self[3].gmul(&[rhs[2][3]; 4], field),
+            self[3].gmul(&[rhs[3][3]; 4], field),
         ])
     }
 }
 
-impl Ops for u8x4 {
+impl<T: Cell> Ops for [T; 4] {
     /// ```
     /// use eva_crypto::generic::Ops;
     /// assert_eq!(
@@ -165,10 +200,10 @@ impl Ops for u8x4 {
 
     fn xor(&self, rhs: &Self) -> Self {
         [
-            self[0] ^ rhs[0],
-            self[1] ^ rhs[1],
-            self[2] ^ rhs[2],
-            self[3] ^ rhs[3],
+            self[0].xor(&rhs[0]),
+            self[1].xor(&rhs[1]),
+            self[2].xor(&rhs[2]),
+            self[3].xor(&rhs[3]),
         ]
     }
 
@@ -181,19 +216,19 @@ impl Ops for u8x4 {
     /// ```
     fn and(&self, rhs: &Self) -> Self {
         [
-            self[0] & rhs[0],
-            self[1] & rhs[1],
-            self[2] & rhs[2],
-            self[3] & rhs[3],
+            self[0].and(&rhs[0]),
+            self[1].and(&rhs[1]),
+            self[2].and(&rhs[2]),
+            self[3].and(&rhs[3]),
         ]
     }
 
-    fn gmul(&self, rhs: &Self, bits: u8) -> Self {
+    fn gmul(&self, rhs: &Self, field: Field) -> Self {
         [
-            self[0].gmul(&rhs[0], bits),
-            self[1].gmul(&rhs[1], bits),
-            self[2].gmul(&rhs[2], bits),
-            self[3].gmul(&rhs[3], bits),
+            self[0].gmul(&rhs[0], field),
+            self[1].gmul(&rhs[1], field),
+            self[2].gmul(&rhs[2], field),
+            self[3].gmul(&rhs[3], field),
         ]
     }
 }
@@ -211,12 +246,8 @@ impl Ops for u8 {
     fn and(&self, rhs: &Self) -> Self {
         self & rhs
     }
-    fn gmul(&self, rhs: &Self, bits: u8) -> Self {
-        match bits {
-            8 => gmul_x(*self, *rhs, 0x1b, 8),
-            4 => gmul_x(*self, *rhs, 0x03, 4),
-            _ => unimplemented!(),
-        }
+    fn gmul(&self, rhs: &Self, field: Field) -> Self {
+        gmul_x(*self, *rhs, field.poly(), field.bits())
     }
 }
 
@@ -237,18 +268,33 @@ pub fn gmul_x(mut a: u8, mut b: u8, poly: u8, bits: u8) -> u8 {
     p & (0xff >> (8 - bits))
 }
 
-impl Permutation for u8x4 {
+impl Cell for u8 {
+    fn shl(&self, n: u32) -> Self {
+        self.checked_shl(n).unwrap_or(0)
+    }
+    fn shr(&self, n: u32) -> Self {
+        self.checked_shr(n).unwrap_or(0)
+    }
+}
+
+impl Permutation for u8 {
+    fn sub_sbox(&self, sbox: &[u8]) -> Self {
+        sbox[*self as usize]
+    }
+}
+
+impl<T: Cell> Permutation for [T; 4] {
     fn sub_sbox(&self, sbox: &[u8]) -> Self {
         [
-            sbox[self[0] as usize],
-            sbox[self[1] as usize],
-            sbox[self[2] as usize],
-            sbox[self[3] as usize],
+            self[0].sub_sbox(sbox),
+            self[1].sub_sbox(sbox),
+            self[2].sub_sbox(sbox),
+            self[3].sub_sbox(sbox),
         ]
     }
 }
 
-impl Permutation for u8x4x4 {
+impl<T: Cell> Permutation for [[T; 4]; 4] {
     fn sub_sbox(&self, sbox: &[u8]) -> Self {
         [
             self[0].sub_sbox(sbox),
@@ -267,17 +313,17 @@ impl Permutation for u8x4x4 {
 ///        [[0x1;4]; 4]
 ///    );
 /// ```
-pub fn create_u8x4x4(data: &[u8]) -> u8x4x4 {
+pub fn create_u8x4x4<T: Cell>(data: &[T]) -> [[T; 4]; 4] {
     assert_eq!(data.len(), 16);
-    let mut state = [[0; 4]; 4];
+    let mut state = [[T::from(0); 4]; 4];
     for (i, &j) in data.iter().enumerate() {
         state[i / 4][i % 4] = j;
     }
     state
 }
 
-pub fn create_u8x16(data: &u8x4x4) -> [u8; 16] {
-    let mut state = [0u8; 16];
+pub fn create_u8x16<T: Cell>(data: &[[T; 4]; 4]) -> [T; 16] {
+    let mut state = [T::from(0); 16];
     for i in 0..16 {
         state[i] = data[i / 4 as usize][i % 4 as usize];
     }
@@ -335,8 +381,8 @@ pub fn u8x4_to_u32(data: u8x4) -> u32 {
 ///     ]
 /// );
 /// ```
-pub fn transpose(input: &u8x4x4) -> u8x4x4 {
-    let mut out = [[0; 4]; 4];
+pub fn transpose<T: Cell>(input: &[[T; 4]; 4]) -> [[T; 4]; 4] {
+    let mut out = *input;
     for (i, &n) in input.iter().enumerate() {
         for (j, &u) in n.iter().enumerate() {
             out[j][i] = u;
@@ -345,6 +391,17 @@ pub fn transpose(input: &u8x4x4) -> u8x4x4 {
     out
 }
 
+/// Convert a state of constants to any cell type.
+pub fn lift<T: Cell>(state: &u8x4x4) -> [[T; 4]; 4] {
+    let mut out = [[T::from(0); 4]; 4];
+    for (i, row) in state.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            out[i][j] = T::from(cell);
+        }
+    }
+    out
+}
+
 /// Expand the data to bits vector.
 /// ```
 /// use eva_crypto::generic::expand_bits;
@@ -1,4 +1,4 @@
-use super::generic::{create_u8x4x4, u8x4x4, Ops, Permutation};
+use super::generic::{create_u8x4x4, lift, u8x4x4, Cell, Field, Ops, Permutation};
 
 /// LED block cipher's block length is 64 bits, and it supports 3 key lengths of 64, 80 and 128 bits in the [paper](https://link.springer.com/chapter/10.1007/978-3-642-23951-9_22).
 pub struct LED {
@@ -35,13 +35,7 @@ impl LED {
 
     /// Encrypt a block.
     pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
-        let mut state = create_u8x4x4(data);
-        for i in 0..self.ns {
-            state = add_round_key(&state, &self.key, self.keysize, i);
-            state = step(&state, self.keysize, i, &self.sbox);
-        }
-        state = add_round_key(&state, &self.key, self.keysize, self.ns);
-        state.concat()
+        encrypt_block(data, &self.key, &self.sbox)
     }
 
     /// Decrypt a block.
@@ -71,7 +65,20 @@ impl LED {
     }
 }
 
-fn step(state: &LEDstate, keysize: u8, round: u8, sbox: &[u8]) -> LEDstate {
+/// Encrypt a block of any cell type, e.g. symbolic nibbles. The key has 16, 20 or 32 cells.
+pub fn encrypt_block<T: Cell>(data: &[T], key: &[T], sbox: &[u8]) -> Vec<T> {
+    let keysize = (key.len() * 4) as u8;
+    let ns = if keysize == 64 { 8 } else { 12 };
+    let mut state = create_u8x4x4(data);
+    for i in 0..ns {
+        state = add_round_key(&state, key, keysize, i);
+        state = step(&state, keysize, i, sbox);
+    }
+    state = add_round_key(&state, key, keysize, ns);
+    state.concat()
+}
+
+fn step<T: Cell>(state: &[[T; 4]; 4], keysize: u8, round: u8, sbox: &[u8]) -> [[T; 4]; 4] {
     let mut out = *state;
     for i in 0..4 {
         out = add_constants(&out, round * 4 + i, keysize);
@@ -91,38 +98,38 @@ fn inv_step(state: &LEDstate, keysize: u8, round: u8, rsbox: &[u8]) -> LEDstate
     }
     out
 }
-fn add_round_key(state: &LEDstate, key: &[u8], keysize: u8, round: u8) -> LEDstate {
-    let mut rkey: [u8; 16] = [0; 16];
+fn add_round_key<T: Cell>(state: &[[T; 4]; 4], key: &[T], keysize: u8, round: u8) -> [[T; 4]; 4] {
+    let mut rkey = [T::from(0); 16];
     for i in 0..16 {
         rkey[i] = key[((round * 16 + i as u8) % (keysize / 4)) as usize];
     }
     state.xor(&create_u8x4x4(&rkey))
 }
-fn add_constants(state: &LEDstate, r: u8, keysize: u8) -> LEDstate {
-    state.xor(&[
+fn add_constants<T: Cell>(state: &[[T; 4]; 4], r: u8, keysize: u8) -> [[T; 4]; 4] {
+    state.xor(&lift(&[
         [keysize >> 4, (RCON[r as usize] >> 3) & 0x7, 0, 0],
         [1 ^ ((keysize >> 4) & 0xf), RCON[r as usize] & 0x7, 0, 0],
         [2 ^ (keysize & 0xf), (RCON[r as usize] >> 3) & 0x7, 0, 0],
         [3 ^ (keysize & 0xf), RCON[r as usize] & 0x7, 0, 0],
-    ])
+    ]))
 }
-fn sub_cells(state: &LEDstate, sbox: &[u8]) -> LEDstate {
+fn sub_cells<T: Cell>(state: &[[T; 4]; 4], sbox: &[u8]) -> [[T; 4]; 4] {
     state.sub_sbox(sbox)
 }
 fn inv_sub_cells(state: &LEDstate, rsbox: &[u8]) -> LEDstate {
     state.sub_sbox(rsbox)
 }
-fn shift_rows(state: &LEDstate) -> LEDstate {
+fn shift_rows<T: Cell>(state: &[[T; 4]; 4]) -> [[T; 4]; 4] {
     state.lrot()
 }
 fn inv_shift_rows(state: &LEDstate) -> LEDstate {
     state.rrot()
 }
-pub fn mix_columns_serial(state: &LEDstate) -> LEDstate {
-    state.gmul(&MDS, 4)
+pub fn mix_columns_serial<T: Cell>(state: &[[T; 4]; 4]) -> [[T; 4]; 4] {
+    state.gmul(&lift(&MDS), Field::GF16)
 }
 pub fn inv_mix_columns_serial(state: &LEDstate) -> LEDstate {
-    state.gmul(&RMDS, 4)
+    state.gmul(&RMDS, Field::GF16)
 }
 
 pub static RCON: [u8; 48] = [
@@ -1,7 +1,11 @@
 pub mod aes;
 pub mod blowfish;
+pub mod dataset;
 pub mod generic;
 pub mod led;
+pub mod npy;
 pub mod present;
 pub mod skinny;
 pub mod sm4;
+pub mod traces;
+pub mod trs;
@@ -0,0 +1,558 @@
+//! Reading and writing NumPy `.npy` arrays and `.npz` archives.
+//!
+//! Only C-ordered, little-endian `uint8`, `int16` and `float32` arrays are supported, which covers
+//! oscilloscope traces and byte-oriented cipher data. Archives are read and written uncompressed,
+//! as produced by `numpy.savez`.
+use super::traces::TraceSet;
+use memmap::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for i16 {}
+    impl Sealed for f32 {}
+}
+
+/// An element type which can be stored in an `.npy` file.
+pub trait Element: private::Sealed + Copy + Default {
+    /// The `descr` entry of the `.npy` header.
+    const DESCR: &'static str;
+    fn from_le(bytes: &[u8]) -> Self;
+    fn write_le(self, out: &mut Vec<u8>);
+}
+
+impl Element for u8 {
+    const DESCR: &'static str = "|u1";
+    fn from_le(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.push(self)
+    }
+}
+
+impl Element for i16 {
+    const DESCR: &'static str = "<i2";
+    fn from_le(bytes: &[u8]) -> Self {
+        i16::from_le_bytes([bytes[0], bytes[1]])
+    }
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes())
+    }
+}
+
+impl Element for f32 {
+    const DESCR: &'static str = "<f4";
+    fn from_le(bytes: &[u8]) -> Self {
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes())
+    }
+}
+
+/// A C-ordered n-dimensional array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Array<T> {
+    pub shape: Vec<usize>,
+    pub data: Vec<T>,
+}
+
+/// An array whose element type is only known after reading its header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnyArray {
+    U8(Array<u8>),
+    I16(Array<i16>),
+    F32(Array<f32>),
+}
+
+/// An `.npy` file mapped into memory instead of being read.
+pub struct MappedArray<T: Element> {
+    map: Mmap,
+    offset: usize,
+    shape: Vec<usize>,
+    element: PhantomData<T>,
+}
+
+/// Reads the arrays of an `.npz` archive by name.
+pub struct NpzReader<R: Read + Seek> {
+    inner: R,
+    entries: HashMap<String, (u64, usize)>,
+}
+
+/// Writes arrays into an `.npz` archive.
+pub struct NpzWriter<W: Write> {
+    inner: W,
+    offset: u64,
+    central: Vec<u8>,
+    count: u16,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl<T: Element> Array<T> {
+    /// An array of `shape` holding `data` in row-major order, which must have as many elements.
+    pub fn new(shape: Vec<usize>, data: Vec<T>) -> io::Result<Self> {
+        let len = shape.iter().try_fold(1usize, |len, &d| len.checked_mul(d));
+        if len != Some(data.len()) {
+            return Err(invalid("shape does not match the data"));
+        }
+        Ok(Array { shape, data })
+    }
+
+    /// Build a 2-dimensional array from rows of the same length.
+    pub fn from_rows(rows: &[Vec<T>]) -> io::Result<Self> {
+        let cols = rows.first().map_or(0, |r| r.len());
+        if rows.iter().any(|r| r.len() != cols) {
+            return Err(invalid("rows of different lengths"));
+        }
+        Ok(Array {
+            shape: vec![rows.len(), cols],
+            data: rows.concat(),
+        })
+    }
+
+    /// Split the array along its first axis. A 0- or 1-dimensional array is a single row.
+    pub fn to_rows(&self) -> Vec<Vec<T>> {
+        if self.shape.len() < 2 {
+            return vec![self.data.clone()];
+        }
+        let cols: usize = self.shape[1..].iter().product();
+        if cols == 0 {
+            return vec![vec![]; self.shape[0]];
+        }
+        self.data.chunks(cols).map(|r| r.to_vec()).collect()
+    }
+}
+
+impl AnyArray {
+    pub fn shape(&self) -> &[usize] {
+        match self {
+            AnyArray::U8(a) => &a.shape,
+            AnyArray::I16(a) => &a.shape,
+            AnyArray::F32(a) => &a.shape,
+        }
+    }
+
+    pub fn to_f32(&self) -> Array<f32> {
+        match self {
+            AnyArray::U8(a) => Array {
+                shape: a.shape.clone(),
+                data: a.data.iter().map(|&x| x.into()).collect(),
+            },
+            AnyArray::I16(a) => Array {
+                shape: a.shape.clone(),
+                data: a.data.iter().map(|&x| x.into()).collect(),
+            },
+            AnyArray::F32(a) => a.clone(),
+        }
+    }
+}
+
+/// Parse the magic string and the header dictionary, returning the `descr`, the shape and the
+/// length of the whole header.
+fn parse_header(bytes: &[u8]) -> io::Result<(String, Vec<usize>, usize)> {
+    if bytes.len() < 10 || &bytes[0..6] != MAGIC {
+        return Err(invalid("not an npy file"));
+    }
+    let (len, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        _ => return Err(invalid("unsupported npy version")),
+    };
+    let dict = bytes
+        .get(start..start + len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| invalid("truncated npy header"))?;
+
+    let value = |key: &str| {
+        dict.find(&format!("'{}':", key))
+            .map(|i| dict[i + key.len() + 3..].trim_start())
+            .ok_or_else(|| invalid(&format!("npy header has no {}", key)))
+    };
+    let descr = value("descr")?;
+    let descr = descr
+        .get(1..)
+        .ok_or_else(|| invalid("npy descr"))?
+        .split(&['\'', '"'][..])
+        .next()
+        .unwrap_or("")
+        .to_string();
+    if value("fortran_order")?.starts_with("True") {
+        return Err(invalid("Fortran-ordered arrays are not supported"));
+    }
+    let shape = value("shape")?;
+    let shape = shape
+        .find(')')
+        .and_then(|end| shape.get(1..end))
+        .ok_or_else(|| invalid("npy shape"))?;
+    let shape = shape
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim_end_matches('L').parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| invalid("npy shape"))?;
+    Ok((descr, shape, start + len))
+}
+
+fn check_descr<T: Element>(descr: &str) -> io::Result<()> {
+    // Single bytes have no byte order, but some writers still put one.
+    if descr == T::DESCR || (T::DESCR == "|u1" && (descr == "<u1" || descr == "u1")) {
+        Ok(())
+    } else {
+        Err(invalid(&format!(
+            "expected {} elements, found {}",
+            T::DESCR,
+            descr
+        )))
+    }
+}
+
+fn read_header<R: Read>(r: &mut R) -> io::Result<(String, Vec<usize>)> {
+    let mut prefix = vec![0u8; 10];
+    r.read_exact(&mut prefix)?;
+    let len = match prefix[6] {
+        1 => u16::from_le_bytes([prefix[8], prefix[9]]) as usize,
+        2 | 3 => {
+            let mut rest = [0u8; 2];
+            r.read_exact(&mut rest)?;
+            prefix.extend_from_slice(&rest);
+            u32::from_le_bytes([prefix[8], prefix[9], prefix[10], prefix[11]]) as usize
+        }
+        _ => return Err(invalid("unsupported npy version")),
+    };
+    let mut header = prefix;
+    let start = header.len();
+    header.resize(start + len, 0);
+    r.read_exact(&mut header[start..])?;
+    let (descr, shape, _) = parse_header(&header)?;
+    Ok((descr, shape))
+}
+
+/// Number of bytes of the data of an array of `shape`.
+fn data_len<T: Element>(shape: &[usize]) -> io::Result<usize> {
+    shape
+        .iter()
+        .try_fold(std::mem::size_of::<T>(), |len, &d| len.checked_mul(d))
+        .ok_or_else(|| invalid("npy shape too large"))
+}
+
+fn read_data<T: Element, R: Read>(r: &mut R, shape: Vec<usize>) -> io::Result<Array<T>> {
+    let size = std::mem::size_of::<T>();
+    let mut bytes = vec![0u8; data_len::<T>(&shape)?];
+    r.read_exact(&mut bytes)?;
+    let data = bytes.chunks(size).map(T::from_le).collect();
+    Ok(Array { shape, data })
+}
+
+/// Read an `.npy` array of a known element type.
+pub fn read_npy<T: Element, R: Read>(r: &mut R) -> io::Result<Array<T>> {
+    let (descr, shape) = read_header(r)?;
+    check_descr::<T>(&descr)?;
+    read_data(r, shape)
+}
+
+/// Read an `.npy` array of any supported element type.
+pub fn read_npy_any<R: Read>(r: &mut R) -> io::Result<AnyArray> {
+    let (descr, shape) = read_header(r)?;
+    match descr.as_str() {
+        "|u1" | "<u1" | "u1" => Ok(AnyArray::U8(read_data(r, shape)?)),
+        "<i2" => Ok(AnyArray::I16(read_data(r, shape)?)),
+        "<f4" => Ok(AnyArray::F32(read_data(r, shape)?)),
+        _ => Err(invalid(&format!("unsupported element type {}", descr))),
+    }
+}
+
+/// Write an array as a version 1.0 `.npy` file.
+pub fn write_npy<T: Element, W: Write>(w: &mut W, array: &Array<T>) -> io::Result<()> {
+    let shape = match array.shape.len() {
+        1 => format!("({},)", array.shape[0]),
+        _ => format!(
+            "({})",
+            array
+                .shape
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape
+    );
+    // The data starts on a 64-byte boundary, the header ends with a newline.
+    while (10 + dict.len() + 1) % 64 != 0 {
+        dict.push(' ');
+    }
+    dict.push('\n');
+    if dict.len() > u16::MAX as usize {
+        return Err(invalid("npy header too long"));
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(dict.as_bytes());
+    for &x in array.data.iter() {
+        x.write_le(&mut bytes);
+    }
+    w.write_all(&bytes)
+}
+
+impl<T: Element> MappedArray<T> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // The mapping is read-only. Like any memory map, it must not be truncated by another
+        // process while in use.
+        let map = unsafe { Mmap::map(&file)? };
+        let (descr, shape, offset) = parse_header(&map)?;
+        check_descr::<T>(&descr)?;
+        let len = data_len::<T>(&shape)?;
+        if map.len() - offset < len {
+            return Err(invalid("truncated npy data"));
+        }
+        if map[offset..]
+            .as_ptr()
+            .align_offset(std::mem::align_of::<T>())
+            != 0
+        {
+            return Err(invalid("misaligned npy data"));
+        }
+        Ok(MappedArray {
+            map,
+            offset,
+            shape,
+            element: PhantomData,
+        })
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// All elements, in C order.
+    pub fn data(&self) -> &[T] {
+        let len = self.shape.iter().product();
+        // The length and alignment were checked in `open`, and `Element` is only implemented for
+        // types without invalid bit patterns.
+        unsafe { std::slice::from_raw_parts(self.map[self.offset..].as_ptr() as *const T, len) }
+    }
+
+    /// Row `i` along the first axis.
+    pub fn row(&self, i: usize) -> &[T] {
+        let cols: usize = self.shape.iter().skip(1).product();
+        &self.data()[i * cols..(i + 1) * cols]
+    }
+}
+
+/// CRC-32 as used by zip archives.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn le16(b: &[u8], at: usize) -> usize {
+    u16::from_le_bytes([b[at], b[at + 1]]) as usize
+}
+
+fn le32(b: &[u8], at: usize) -> usize {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]) as usize
+}
+
+impl<R: Read + Seek> NpzReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        // The end of central directory record is within the last 64KiB + 22 bytes.
+        let size = inner.seek(SeekFrom::End(0))?;
+        let tail_len = size.min(0x10000 + 22);
+        inner.seek(SeekFrom::Start(size - tail_len))?;
+        let mut tail = vec![0u8; tail_len as usize];
+        inner.read_exact(&mut tail)?;
+        let eocd = (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|&i| tail[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+            .ok_or_else(|| invalid("not a zip archive"))?;
+        let count = le16(&tail, eocd + 10);
+        let cd_size = le32(&tail, eocd + 12);
+        let cd_offset = le32(&tail, eocd + 16);
+
+        inner.seek(SeekFrom::Start(cd_offset as u64))?;
+        let mut cd = vec![0u8; cd_size];
+        inner.read_exact(&mut cd)?;
+        let mut entries = HashMap::new();
+        let mut at = 0;
+        for _ in 0..count {
+            if cd.len() < at + 46 || cd[at..at + 4] != [0x50, 0x4b, 0x01, 0x02] {
+                return Err(invalid("corrupted zip central directory"));
+            }
+            let method = le16(&cd, at + 10);
+            let size = le32(&cd, at + 24);
+            let name_len = le16(&cd, at + 28);
+            let skip = name_len + le16(&cd, at + 30) + le16(&cd, at + 32);
+            let offset = le32(&cd, at + 42) as u64;
+            let name = cd
+                .get(at + 46..at + 46 + name_len)
+                .ok_or_else(|| invalid("corrupted zip central directory"))?;
+            let name = String::from_utf8_lossy(name).to_string();
+            if method != 0 {
+                return Err(invalid(&format!("{} is compressed", name)));
+            }
+            let name = name.trim_end_matches(".npy").to_string();
+            entries.insert(name, (offset, size));
+            at += 46 + skip;
+        }
+        Ok(NpzReader { inner, entries })
+    }
+
+    /// Names of the arrays, without the `.npy` suffix.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.entries.keys().map(|s| s.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    fn entry(&mut self, name: &str) -> io::Result<io::Take<&mut R>> {
+        let &(offset, size) = self
+            .entries
+            .get(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_string()))?;
+        let mut local = [0u8; 30];
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(&mut local)?;
+        if local[0..4] != [0x50, 0x4b, 0x03, 0x04] {
+            return Err(invalid("corrupted zip local header"));
+        }
+        let skip = le16(&local, 26) + le16(&local, 28);
+        self.inner.seek(SeekFrom::Current(skip as i64))?;
+        Ok((&mut self.inner).take(size as u64))
+    }
+
+    pub fn array<T: Element>(&mut self, name: &str) -> io::Result<Array<T>> {
+        read_npy(&mut self.entry(name)?)
+    }
+
+    pub fn any(&mut self, name: &str) -> io::Result<AnyArray> {
+        read_npy_any(&mut self.entry(name)?)
+    }
+}
+
+impl<W: Write> NpzWriter<W> {
+    pub fn new(inner: W) -> Self {
+        NpzWriter {
+            inner,
+            offset: 0,
+            central: vec![],
+            count: 0,
+        }
+    }
+
+    pub fn add<T: Element>(&mut self, name: &str, array: &Array<T>) -> io::Result<()> {
+        let mut data = vec![];
+        write_npy(&mut data, array)?;
+        let name = format!("{}.npy", name);
+        if self.offset + data.len() as u64 > u32::MAX as u64 || self.count == u16::MAX {
+            return Err(invalid("archive too large"));
+        }
+
+        // Version 2.0, no flags, stored, 1980-01-01 00:00.
+        let mut common = vec![];
+        common.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        common.extend_from_slice(&crc32(&data).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&[0, 0]);
+
+        let mut local = vec![0x50, 0x4b, 0x03, 0x04];
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        self.inner.write_all(&local)?;
+        self.inner.write_all(&data)?;
+
+        self.central
+            .extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0]);
+        self.central.extend_from_slice(&common);
+        self.central.extend_from_slice(&[0; 10]);
+        self.central
+            .extend_from_slice(&(self.offset as u32).to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+
+        self.offset += (local.len() + data.len()) as u64;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write the central directory, and hand back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut eocd = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0];
+        eocd.extend_from_slice(&self.count.to_le_bytes());
+        eocd.extend_from_slice(&self.count.to_le_bytes());
+        eocd.extend_from_slice(&(self.central.len() as u32).to_le_bytes());
+        eocd.extend_from_slice(&(self.offset as u32).to_le_bytes());
+        eocd.extend_from_slice(&[0, 0]);
+        self.inner.write_all(&self.central)?;
+        self.inner.write_all(&eocd)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Load a trace set from an archive with a `traces` array and optional `plaintexts`,
+/// `ciphertexts` and `keys` byte arrays.
+pub fn load_trace_set<R: Read + Seek>(r: R) -> io::Result<TraceSet> {
+    let mut npz = NpzReader::new(r)?;
+    let mut set = TraceSet::new();
+    set.traces = npz.any("traces")?.to_f32().to_rows();
+    let mut bytes = |name: &str| -> io::Result<Vec<Vec<u8>>> {
+        match npz.entries.contains_key(name) {
+            true => Ok(npz.array::<u8>(name)?.to_rows()),
+            false => Ok(vec![]),
+        }
+    };
+    set.plaintexts = bytes("plaintexts")?;
+    set.ciphertexts = bytes("ciphertexts")?;
+    set.keys = bytes("keys")?;
+    Ok(set)
+}
+
+/// Save a trace set in the layout read by `load_trace_set`. Empty fields are left out.
+pub fn save_trace_set<W: Write>(w: W, set: &TraceSet) -> io::Result<W> {
+    let mut npz = NpzWriter::new(w);
+    npz.add("traces", &Array::from_rows(&set.traces)?)?;
+    for &(name, field) in [
+        ("plaintexts", &set.plaintexts),
+        ("ciphertexts", &set.ciphertexts),
+        ("keys", &set.keys),
+    ]
+    .iter()
+    {
+        if !field.is_empty() {
+            npz.add(name, &Array::from_rows(field)?)?;
+        }
+    }
+    npz.finish()
+}
@@ -1,6 +1,4 @@
-use super::generic::{
-    create_u8x16, create_u8x4x4, expand_bits, restore_data, transpose, u8x4x4, Ops, Permutation,
-};
+use super::generic::{create_u8x16, create_u8x4x4, transpose, u8x4x4, Cell, Ops, Permutation};
 
 type PREstate = u8x4x4;
 
@@ -13,9 +11,7 @@ pub struct PRESENT {
 
 impl PRESENT {
     pub fn new(key: &[u8]) -> PRESENT {
-        let rounds = 32;
-        let mut round_keys: Vec<PREstate> = vec![Default::default(); rounds];
-        key_expansion(key.to_vec(), &mut round_keys);
+        let round_keys: Vec<PREstate> = expand_key(key, 32);
 
         PRESENT {
             round_keys,
@@ -26,15 +22,9 @@ impl PRESENT {
 
     /// Encrypt a block.
     pub fn encrypt(&self, data: &[u8]) -> [u8; 16] {
-        let rounds = 32;
-        let mut state = create_u8x4x4(data);
-        for i in 0..rounds - 1 {
-            state = add_round_key(&state, &self.round_keys[i]);
-            state = sbox_layer(&state, &self.sbox);
-            state = p_layer(&state, &PBOX);
-        }
-        state = add_round_key(&state, &self.round_keys[rounds - 1]);
-        create_u8x16(&state)
+        let mut out = [0; 16];
+        out.copy_from_slice(&encrypt_block(data, &self.round_keys, &self.sbox));
+        out
     }
 
     /// Decrypt a block.
@@ -65,53 +55,76 @@ impl PRESENT {
         self
     }
 }
-fn add_round_key(state: &PREstate, round_key: &PREstate) -> PREstate {
+/// Encrypt a block of any cell type, e.g. symbolic nibbles, under the round keys of `expand_key`.
+pub fn encrypt_block<T: Cell>(data: &[T], round_keys: &[[[T; 4]; 4]], sbox: &[u8]) -> Vec<T> {
+    let rounds = round_keys.len();
+    let mut state = create_u8x4x4(data);
+    for round_key in round_keys[..rounds - 1].iter() {
+        state = add_round_key(&state, round_key);
+        state = sbox_layer(&state, sbox);
+        state = p_layer(&state, &PBOX);
+    }
+    state = add_round_key(&state, &round_keys[rounds - 1]);
+    create_u8x16(&state).to_vec()
+}
+
+fn add_round_key<T: Cell>(state: &[[T; 4]; 4], round_key: &[[T; 4]; 4]) -> [[T; 4]; 4] {
     state.xor(&transpose(&round_key))
 }
-fn sbox_layer(state: &PREstate, sbox: &[u8]) -> PREstate {
+fn sbox_layer<T: Cell>(state: &[[T; 4]; 4], sbox: &[u8]) -> [[T; 4]; 4] {
     state.sub_sbox(sbox)
 }
 fn inv_sbox_layer(state: &PREstate, rsbox: &[u8]) -> PREstate {
     state.sub_sbox(rsbox)
 }
-fn p_layer(state: &PREstate, pbox: &[u8]) -> PREstate {
-    let bits = expand_bits(&state.concat().to_vec(), 4);
-    let mut p_bits: Vec<bool> = vec![true; 64];
-    for i in 0..64 {
-        p_bits[i] = bits[pbox[i] as usize];
+fn p_layer<T: Cell>(state: &[[T; 4]; 4], pbox: &[u8]) -> [[T; 4]; 4] {
+    let cells = state.concat();
+    let mut out = [T::from(0); 16];
+    for (i, &p) in pbox.iter().enumerate() {
+        // Bit `p` of the state, counting from the most significant bit of the first cell.
+        let p = p as u32;
+        let bit = cells[p as usize / 4].shr(3 - p % 4).and(&T::from(1));
+        out[i / 4] = out[i / 4].xor(&bit.shl(3 - i as u32 % 4));
     }
-    create_u8x4x4(&restore_data(&p_bits, 4)[0..16])
+    create_u8x4x4(&out)
 }
-fn key_expansion(key: Vec<u8>, round_keys: &mut [PREstate]) {
+/// The `rounds` round keys of an 80- or 128-bit key given as 20 or 32 nibbles. The nibbles may
+/// be symbolic, for an unknown key.
+pub fn expand_key<T: Cell>(key: &[T], rounds: usize) -> Vec<[[T; 4]; 4]> {
     let keysize = key.len() * 4;
-    let rounds = round_keys.len();
-    let mut k_register = expand_bits(&key, 4);
     match keysize {
         80 | 128 => (),
         _ => panic!("Key length {} is not valid!", keysize),
     }
 
-    for (i, round_key) in round_keys.iter_mut().enumerate().take(rounds) {
-        *round_key = create_u8x4x4(&restore_data(&k_register, 4)[0..16]);
-        // rotate left 61 bits
-        k_register[..].rotate_left(61);
-        let mut buffer = restore_data(&k_register, 4);
-        if keysize == 80 {
-            // Sbox
-            buffer[0] = SBOX[buffer[0] as usize];
-            //  XOR with the round counter
-            buffer[15] ^= (i + 1 >> 1) as u8;
-            buffer[16] ^= (i + 1 << 3 & 0xf) as u8;
+    let mut register = key.to_vec();
+    let mut round_keys = vec![];
+    for i in 0..rounds {
+        round_keys.push(create_u8x4x4(&register[0..16]));
+        // rotate left 61 bits, 15 nibbles and then 1 bit
+        register.rotate_left(15);
+        let n = register.len();
+        register = (0..n)
+            .map(|j| {
+                register[j]
+                    .shl(1)
+                    .and(&T::from(0xf))
+                    .xor(&register[(j + 1) % n].shr(3))
+            })
+            .collect();
+        // Sbox
+        register[0] = register[0].sub_sbox(&SBOX);
+        let counter = if keysize == 80 {
+            [i + 1 >> 1, i + 1 << 3 & 0xf]
         } else {
-            // Sbox
-            buffer[0] = SBOX[buffer[0] as usize];
-            buffer[1] = SBOX[buffer[1] as usize];
-            //  XOR with the round counter
-            buffer[15] ^= (i + 1 >> 2) as u8;
-            buffer[16] ^= (i + 1 << 2 & 0xf) as u8;
-        }
-        k_register = expand_bits(&buffer, 4);
+            register[1] = register[1].sub_sbox(&SBOX);
+            [i + 1 >> 2, i + 1 << 2 & 0xf]
+        };
+        //  XOR with the round counter
+        register[15] = register[15].xor(&T::from(counter[0] as u8));
+        register[16] = register[16].xor(&T::from(counter[1] as u8));
     }
+    round_keys
 }
 
 pub static SBOX: [u8; 16] = [
@@ -1,4 +1,4 @@
-use super::generic::{create_u8x16, create_u8x4x4, u8x4x4, Ops, Permutation};
+use super::generic::{create_u8x16, create_u8x4x4, lift, u8x4x4, Cell, Field, Ops, Permutation};
 
 type SKIstate = u8x4x4;
 
@@ -11,7 +11,7 @@ pub struct SKINNY {
 
 impl SKINNY {
     pub fn new(key: &[u8], cell_size: u8) -> SKINNY {
-        let round_keys: Vec<SKIstate> = key_expansion(key.to_vec(), cell_size);
+        let round_keys: Vec<SKIstate> = expand_key(key, cell_size);
 
         let (sbox, rsbox) = if cell_size == 4 {
             (SBOX_4.to_vec(), RSBOX_4.to_vec())
@@ -29,22 +29,21 @@ impl SKINNY {
 
     /// Encrypt a block.
     pub fn encrypt(&self, data: &[u8]) -> [u8; 16] {
-        let mut state = create_u8x4x4(data);
-        for i in 0..self.round_keys.len() {
-            state = sub_cells(state, &self.sbox[..]);
-            state = add_constants(state, i);
-            state = add_round_tweakey(state, self.round_keys[i]);
-            state = shift_rows(state);
-            state = state.gmul(&MDS, self.cell_size);
-        }
-        create_u8x16(&state)
+        let mut out = [0; 16];
+        out.copy_from_slice(&encrypt_block(
+            data,
+            &self.round_keys,
+            &self.sbox,
+            self.cell_size,
+        ));
+        out
     }
 
     /// Decrypt a block.
     pub fn decrypt(&self, data: &[u8]) -> [u8; 16] {
         let mut state = create_u8x4x4(data);
         for i in (0..self.round_keys.len()).rev() {
-            state = state.gmul(&RMDS, self.cell_size);
+            state = state.gmul(&RMDS, field(self.cell_size));
             state = inv_shift_rows(state);
             state = add_round_tweakey(state, self.round_keys[i]);
             state = add_constants(state, i);
@@ -69,12 +68,33 @@ impl SKINNY {
     }
 }
 
-fn add_round_tweakey(state: SKIstate, round_key: SKIstate) -> SKIstate {
+/// Encrypt a block of any cell type, e.g. symbolic cells, under the round tweakeys of
+/// `expand_key`.
+pub fn encrypt_block<T: Cell>(
+    data: &[T],
+    round_keys: &[[[T; 4]; 4]],
+    sbox: &[u8],
+    cell_size: u8,
+) -> Vec<T> {
+    let mut state = create_u8x4x4(data);
+    for (i, round_key) in round_keys.iter().enumerate() {
+        state = sub_cells(state, sbox);
+        state = add_constants(state, i);
+        state = add_round_tweakey(state, *round_key);
+        state = shift_rows(state);
+        state = state.gmul(&lift(&MDS), field(cell_size));
+    }
+    create_u8x16(&state).to_vec()
+}
+
+fn add_round_tweakey<T: Cell>(state: [[T; 4]; 4], round_key: [[T; 4]; 4]) -> [[T; 4]; 4] {
     state.xor(&round_key)
 }
 
-fn key_expansion(key: Vec<u8>, cell_size: u8) -> Vec<SKIstate> {
-    let mut tks = key;
+/// The round tweakeys of one to three tweakeys of 16 cells. The cells may be symbolic, for an
+/// unknown key.
+pub fn expand_key<T: Cell>(key: &[T], cell_size: u8) -> Vec<[[T; 4]; 4]> {
+    let mut tks = key.to_vec();
     let mut round_keys = vec![];
     let round = match (cell_size, tks.len() / 16) {
         (4, 1) => 32,
@@ -89,44 +109,57 @@ fn key_expansion(key: Vec<u8>, cell_size: u8) -> Vec<SKIstate> {
         let round_key = tks
             .chunks(16)
             .map(|x| create_u8x4x4(x))
-            .fold([[0; 4]; 4], |res, i| res.xor(&i))
-            .and(&[[0xff; 4], [0xff; 4], [0x00; 4], [0x00; 4]]);
+            .fold(lift(&[[0; 4]; 4]), |res, i| res.xor(&i))
+            .and(&lift(&[[0xff; 4], [0xff; 4], [0x00; 4], [0x00; 4]]));
         round_keys.push(round_key);
         for (i, tk) in tks.chunks_mut(16).enumerate() {
-            let tmp: Vec<u8> = tk
-                .iter()
-                .enumerate()
-                .map(|(j, _)| {
+            let tmp: Vec<T> = (0..16)
+                .map(|j| {
                     let cell = tk[PBOX[j] as usize];
                     if j >= 8 {
                         return cell;
                     }
-                    match (i, cell_size) {
-                        (1, 4) => {
-                            ((cell << 1) & 0x0f)
-                                ^ ((cell >> 2) & 0b00000001)
-                                ^ ((cell >> 3) & 0b00000001)
-                        }
-                        (2, 4) => (cell >> 1) ^ ((cell << 3) & 0b00001000) ^ (cell & 0b00001000),
-                        (1, 8) => cell.rotate_left(1) ^ (0x01 & cell >> 5),
-                        (2, 8) => cell.rotate_right(1) ^ (0x80 & cell << 1),
-                        _ => cell,
-                    }
+                    lfsr(cell, i, cell_size)
                 })
                 .collect();
-            for (j, byte) in tk.iter_mut().enumerate() {
-                *byte = tmp[j];
-            }
+            tk.copy_from_slice(&tmp);
         }
     }
     round_keys
 }
 
-fn sub_cells(state: SKIstate, sbox: &[u8]) -> SKIstate {
+/// The LFSR applied to the cells of tweakey `i`.
+fn lfsr<T: Cell>(cell: T, i: usize, cell_size: u8) -> T {
+    let mask = |x: u8| T::from(x);
+    match (i, cell_size) {
+        (1, 4) => cell
+            .shl(1)
+            .and(&mask(0x0f))
+            .xor(&cell.shr(2).and(&mask(0b00000001)))
+            .xor(&cell.shr(3).and(&mask(0b00000001))),
+        (2, 4) => cell
+            .shr(1)
+            .xor(&cell.shl(3).and(&mask(0b00001000)))
+            .xor(&cell.and(&mask(0b00001000))),
+        (1, 8) => cell.lrot().xor(&cell.shr(5).and(&mask(0x01))),
+        (2, 8) => cell.rrot().xor(&cell.shl(1).and(&mask(0x80))),
+        _ => cell,
+    }
+}
+
+/// The field of the cells, in which the binary MixColumns matrix only masks them.
+fn field(cell_size: u8) -> Field {
+    match cell_size {
+        4 => Field::GF16,
+        _ => Field::GF256,
+    }
+}
+
+fn sub_cells<T: Cell>(state: [[T; 4]; 4], sbox: &[u8]) -> [[T; 4]; 4] {
     state.sub_sbox(sbox)
 }
 
-fn shift_rows(state: SKIstate) -> SKIstate {
+fn shift_rows<T: Cell>(state: [[T; 4]; 4]) -> [[T; 4]; 4] {
     state.rrot()
 }
 
@@ -134,13 +167,13 @@ fn inv_shift_rows(state: SKIstate) -> SKIstate {
     state.lrot()
 }
 
-fn add_constants(state: SKIstate, round: usize) -> SKIstate {
-    state.xor(&[
+fn add_constants<T: Cell>(state: [[T; 4]; 4], round: usize) -> [[T; 4]; 4] {
+    state.xor(&lift(&[
         [RCON[round] & 0x0f, 0, 0, 0],
         [(RCON[round] & 0xf0) >> 4, 0, 0, 0],
         [0x2, 0, 0, 0],
         [0; 4],
-    ])
+    ]))
 }
 
 pub static SBOX_4: [u8; 16] = [
@@ -13,7 +13,7 @@ impl SM4 {
         SM4 { round_keys }
     }
 
-    pub fn encrypt(self, data: &[u8]) -> Vec<u8> {
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
         let mut state = create_u8x4x4(data);
         for i in 0..32 {
             let new = round_function(&state, &self.round_keys[i]);
@@ -21,7 +21,7 @@ impl SM4 {
         }
         reverse(&state).concat()
     }
-    pub fn decrypt(self, data: &[u8]) -> Vec<u8> {
+    pub fn decrypt(&self, data: &[u8]) -> Vec<u8> {
         let mut state = reverse(&create_u8x4x4(data));
         for i in (0..32).rev() {
             println!("{:x?}", state);
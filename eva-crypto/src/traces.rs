@@ -0,0 +1,25 @@
+/// Side-channel traces with the data processed during each acquisition.
+///
+/// Row `i` of every field belongs to trace `i`. Fields which were not recorded are left empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceSet {
+    pub traces: Vec<Vec<f32>>,
+    pub plaintexts: Vec<Vec<u8>>,
+    pub ciphertexts: Vec<Vec<u8>>,
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl TraceSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Number of traces in the set.
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+}
@@ -0,0 +1,332 @@
+//! Reading and writing Riscure Inspector `.trs` trace sets.
+//!
+//! A trace set starts with tag-length-value header objects, ended by a trace block marker. Each
+//! trace is then stored as a title, the crypto data and the samples. The crypto data is taken to
+//! be the plaintext followed by the ciphertext, which is how Inspector stores it by default.
+use super::traces::TraceSet;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+const NUMBER_OF_TRACES: u8 = 0x41;
+const NUMBER_OF_SAMPLES: u8 = 0x42;
+const SAMPLE_CODING: u8 = 0x43;
+const DATA_LENGTH: u8 = 0x44;
+const TITLE_SPACE: u8 = 0x45;
+const GLOBAL_TITLE: u8 = 0x46;
+const DESCRIPTION: u8 = 0x47;
+const X_SCALE: u8 = 0x4b;
+const Y_SCALE: u8 = 0x4c;
+const TRACE_BLOCK: u8 = 0x5f;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleCoding {
+    Int8,
+    Int16,
+    Int32,
+    Float,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrsHeader {
+    /// Number of traces in the set.
+    pub traces: usize,
+    /// Number of samples in each trace.
+    pub samples: usize,
+    pub coding: SampleCoding,
+    /// Number of crypto data bytes in each trace.
+    pub data_length: usize,
+    /// Number of title bytes in each trace.
+    pub title_space: usize,
+    pub global_title: String,
+    pub description: String,
+    pub x_scale: f32,
+    pub y_scale: f32,
+}
+
+/// Reads a header, then the traces in chunks.
+pub struct TrsReader<R: Read> {
+    inner: R,
+    header: TrsHeader,
+    plaintext_len: usize,
+    read: usize,
+    /// Bytes of a trace: its title, its data and its samples.
+    record: usize,
+}
+
+/// Writes a header, then the traces in chunks.
+pub struct TrsWriter<W: Write> {
+    inner: W,
+    header: TrsHeader,
+    written: usize,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// `value` as the integer type of a header field, which it must fit.
+fn field<T: TryFrom<usize>>(value: usize, name: &str) -> io::Result<T> {
+    T::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {} does not fit in the trs header", name, value),
+        )
+    })
+}
+
+impl SampleCoding {
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0x01 => Ok(SampleCoding::Int8),
+            0x02 => Ok(SampleCoding::Int16),
+            0x04 => Ok(SampleCoding::Int32),
+            0x14 => Ok(SampleCoding::Float),
+            _ => Err(invalid(&format!("unknown sample coding {:#x}", byte))),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            SampleCoding::Int8 => 0x01,
+            SampleCoding::Int16 => 0x02,
+            SampleCoding::Int32 => 0x04,
+            SampleCoding::Float => 0x14,
+        }
+    }
+
+    /// Number of bytes of one sample.
+    pub fn size(self) -> usize {
+        (self.to_byte() & 0x0f) as usize
+    }
+
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleCoding::Int8 => bytes[0] as i8 as f32,
+            SampleCoding::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            SampleCoding::Int32 => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32
+            }
+            SampleCoding::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+
+    /// Integer codings round and saturate.
+    fn encode(self, sample: f32, out: &mut Vec<u8>) {
+        match self {
+            SampleCoding::Int8 => out.push(sample.round() as i8 as u8),
+            SampleCoding::Int16 => out.extend_from_slice(&(sample.round() as i16).to_le_bytes()),
+            SampleCoding::Int32 => out.extend_from_slice(&(sample.round() as i32).to_le_bytes()),
+            SampleCoding::Float => out.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+impl TrsHeader {
+    pub fn new(traces: usize, samples: usize, coding: SampleCoding, data_length: usize) -> Self {
+        TrsHeader {
+            traces,
+            samples,
+            coding,
+            data_length,
+            title_space: 0,
+            global_title: String::new(),
+            description: String::new(),
+            x_scale: 1.0,
+            y_scale: 1.0,
+        }
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<TrsHeader> {
+        let (mut traces, mut samples, mut coding) = (None, None, None);
+        let mut header = TrsHeader::new(0, 0, SampleCoding::Int8, 0);
+        loop {
+            let mut tag = [0u8; 2];
+            r.read_exact(&mut tag)?;
+            let mut len = tag[1] as usize;
+            if len & 0x80 != 0 {
+                if len & 0x7f > 4 {
+                    return Err(invalid("trs length field longer than 4 bytes"));
+                }
+                let mut bytes = vec![0u8; len & 0x7f];
+                r.read_exact(&mut bytes)?;
+                len = bytes
+                    .iter()
+                    .rev()
+                    .fold(0, |len, &b| (len << 8) | b as usize);
+            }
+            // Read what is there rather than allocating a length taken from the file.
+            let mut value = vec![];
+            r.by_ref().take(len as u64).read_to_end(&mut value)?;
+            if value.len() < len {
+                return Err(invalid("truncated trs header"));
+            }
+            let int = || value.iter().rev().fold(0, |n, &b| (n << 8) | b as usize);
+            let float = || {
+                if value.len() == 4 {
+                    Ok(f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                } else {
+                    Err(invalid("scale is not a 4-byte float"))
+                }
+            };
+            match tag[0] {
+                NUMBER_OF_TRACES => traces = Some(int()),
+                NUMBER_OF_SAMPLES => samples = Some(int()),
+                SAMPLE_CODING => coding = Some(SampleCoding::from_byte(int() as u8)?),
+                DATA_LENGTH => header.data_length = int(),
+                TITLE_SPACE => header.title_space = int(),
+                GLOBAL_TITLE => header.global_title = String::from_utf8_lossy(&value).to_string(),
+                DESCRIPTION => header.description = String::from_utf8_lossy(&value).to_string(),
+                X_SCALE => header.x_scale = float()?,
+                Y_SCALE => header.y_scale = float()?,
+                TRACE_BLOCK => break,
+                // Display settings and extensions have no bearing on the data.
+                _ => (),
+            }
+        }
+        header.traces = traces.ok_or_else(|| invalid("trs header has no trace count"))?;
+        header.samples = samples.ok_or_else(|| invalid("trs header has no sample count"))?;
+        header.coding = coding.ok_or_else(|| invalid("trs header has no sample coding"))?;
+        Ok(header)
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let traces: u32 = field(self.traces, "trace count")?;
+        let samples: u32 = field(self.samples, "sample count")?;
+        let data_length: u16 = field(self.data_length, "data length")?;
+        let title_space: u8 = field(self.title_space, "title space")?;
+        let mut bytes = vec![];
+        let mut object = |tag: u8, value: &[u8]| {
+            bytes.push(tag);
+            if value.len() < 0x80 {
+                bytes.push(value.len() as u8);
+            } else {
+                bytes.push(0x84);
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            }
+            bytes.extend_from_slice(value);
+        };
+        object(NUMBER_OF_TRACES, &traces.to_le_bytes());
+        object(NUMBER_OF_SAMPLES, &samples.to_le_bytes());
+        object(SAMPLE_CODING, &[self.coding.to_byte()]);
+        object(DATA_LENGTH, &data_length.to_le_bytes());
+        object(TITLE_SPACE, &[title_space]);
+        if !self.global_title.is_empty() {
+            object(GLOBAL_TITLE, self.global_title.as_bytes());
+        }
+        if !self.description.is_empty() {
+            object(DESCRIPTION, self.description.as_bytes());
+        }
+        object(X_SCALE, &self.x_scale.to_le_bytes());
+        object(Y_SCALE, &self.y_scale.to_le_bytes());
+        object(TRACE_BLOCK, &[]);
+        w.write_all(&bytes)
+    }
+}
+
+impl<R: Read> TrsReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let header = TrsHeader::read_from(&mut inner)?;
+        let plaintext_len = header.data_length / 2;
+        let record = header
+            .samples
+            .checked_mul(header.coding.size())
+            .and_then(|len| len.checked_add(header.data_length))
+            .and_then(|len| len.checked_add(header.title_space))
+            .ok_or_else(|| invalid("trs trace size too large"))?;
+        Ok(TrsReader {
+            inner,
+            header,
+            plaintext_len,
+            read: 0,
+            record,
+        })
+    }
+
+    /// Set how many leading bytes of the crypto data are the plaintext, half of it by default.
+    pub fn with_plaintext_len(mut self, len: usize) -> Self {
+        self.plaintext_len = len.min(self.header.data_length);
+        self
+    }
+
+    pub fn header(&self) -> &TrsHeader {
+        &self.header
+    }
+
+    /// Read at most `n` traces. The returned set is empty once all traces were read.
+    pub fn read_chunk(&mut self, n: usize) -> io::Result<TraceSet> {
+        let header = &self.header;
+        let n = n.min(header.traces - self.read);
+        let size = header.coding.size();
+        let mut buffer = vec![];
+        let mut set = TraceSet::new();
+        for _ in 0..n {
+            // The buffer only grows with the bytes read, whatever size the header claims.
+            buffer.clear();
+            let record = self.record as u64;
+            self.inner.by_ref().take(record).read_to_end(&mut buffer)?;
+            if buffer.len() < self.record {
+                return Err(invalid("truncated trace"));
+            }
+            let data = &buffer[header.title_space..header.title_space + header.data_length];
+            let samples = &buffer[header.title_space + header.data_length..];
+            set.plaintexts.push(data[..self.plaintext_len].to_vec());
+            set.ciphertexts.push(data[self.plaintext_len..].to_vec());
+            set.traces.push(
+                samples
+                    .chunks(size)
+                    .map(|s| header.coding.decode(s))
+                    .collect(),
+            );
+        }
+        self.read += n;
+        Ok(set)
+    }
+}
+
+impl<W: Write> TrsWriter<W> {
+    pub fn new(mut inner: W, header: TrsHeader) -> io::Result<Self> {
+        header.write_to(&mut inner)?;
+        Ok(TrsWriter {
+            inner,
+            header,
+            written: 0,
+        })
+    }
+
+    /// Append the traces of `set`, with their plaintexts and ciphertexts as crypto data. Traces
+    /// have blank titles.
+    pub fn write_chunk(&mut self, set: &TraceSet) -> io::Result<()> {
+        let header = &self.header;
+        if self.written + set.len() > header.traces {
+            return Err(invalid("more traces than announced in the header"));
+        }
+        let mut bytes = vec![];
+        for (i, trace) in set.traces.iter().enumerate() {
+            let data: Vec<u8> = [set.plaintexts.get(i), set.ciphertexts.get(i)]
+                .iter()
+                .flatten()
+                .flat_map(|d| d.iter().cloned())
+                .collect();
+            if data.len() != header.data_length || trace.len() != header.samples {
+                return Err(invalid("trace does not match the header"));
+            }
+            bytes.resize(bytes.len() + header.title_space, b' ');
+            bytes.extend_from_slice(&data);
+            for &sample in trace.iter() {
+                header.coding.encode(sample, &mut bytes);
+            }
+        }
+        self.inner.write_all(&bytes)?;
+        self.written += set.len();
+        Ok(())
+    }
+
+    /// Check that all announced traces were written, and hand back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.written != self.header.traces {
+            return Err(invalid("fewer traces than announced in the header"));
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
@@ -0,0 +1,92 @@
+use eva_crypto::aes::AES;
+use eva_crypto::dataset::*;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind, Read};
+
+#[cfg(test)]
+#[test]
+fn dataset_roundtrip() {
+    let key: Vec<u8> = (0..16).collect();
+    let header = Header {
+        cipher: "aes".to_string(),
+        key: key.clone(),
+        packing: Packing::Byte,
+        block_size: 16,
+        fault: FaultModel::SboxByte {
+            index: 0,
+            value: 0xd,
+        },
+        seed: Some(42),
+        count: 3,
+    };
+    let cipher = AES::new(&key).with_sbox_byte(0, 0xd);
+    let mut writer = DatasetWriter::new(vec![], &header).unwrap();
+    for i in 0..3 {
+        let plaintext = [i as u8; 16];
+        writer
+            .write(&plaintext, &cipher.encrypt(&plaintext))
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let reader = DatasetReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.header(), &header);
+    let records: Vec<Record> = reader.map(|r| r.unwrap()).collect();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[2].plaintext, vec![2; 16]);
+    assert_eq!(records[2].ciphertext, cipher.encrypt(&[2; 16]));
+}
+
+#[test]
+fn dataset_count_mismatch() {
+    let header = Header {
+        cipher: "present".to_string(),
+        key: vec![0; 20],
+        packing: Packing::Nibble,
+        block_size: 16,
+        fault: FaultModel::None,
+        seed: None,
+        count: 2,
+    };
+    let mut writer = DatasetWriter::new(vec![], &header).unwrap();
+    writer.write(&[0; 16], &[0; 16]).unwrap();
+    assert!(writer.finish().is_err());
+    assert!(DatasetReader::new(&b"EVAD\x09\x00"[..]).is_err());
+}
+
+#[test]
+fn dataset_import_raw() {
+    let header = Header {
+        cipher: "present".to_string(),
+        key: vec![9; 20],
+        packing: Packing::Nibble,
+        block_size: 16,
+        fault: FaultModel::SboxByte {
+            index: 0,
+            value: 0xd,
+        },
+        seed: None,
+        count: 0,
+    };
+    let msg = File::open("examples/data/present/msg.bin").unwrap();
+    let out = File::open("examples/data/present/out.bin").unwrap();
+    let bytes = import_raw(msg, out, header.clone(), Cursor::new(vec![]))
+        .unwrap()
+        .into_inner();
+
+    let reader = DatasetReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.header().count, 300);
+    let first = reader.map(|r| r.unwrap()).next().unwrap();
+    let mut plaintext = [0u8; 16];
+    File::open("examples/data/present/msg.bin")
+        .unwrap()
+        .read_exact(&mut plaintext)
+        .unwrap();
+    assert_eq!(first.plaintext, plaintext.to_vec());
+
+    // A partial last record is an error, not the end of the file.
+    let msg = [0u8; 16 * 2 + 5];
+    let out = [0u8; 16 * 3];
+    let error = import_raw(&msg[..], &out[..], header, Cursor::new(vec![])).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,111 @@
+use eva_crypto::aes::AES;
+use eva_crypto::dataset::*;
+use eva_crypto::npy::load_trace_set;
+use eva_crypto::present::PRESENT;
+use std::env;
+use std::fs::{self, File};
+use std::process::Command;
+
+fn eva_gen(args: &[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_eva-gen"))
+        .args(args)
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[cfg(test)]
+#[test]
+fn gen_faulty_present() {
+    let path = env::temp_dir().join("eva-gen-present.evad");
+    let path = path.to_str().unwrap();
+    let key = "0123456789abcdef0123";
+    let args = [
+        "--cipher",
+        "present",
+        "--key",
+        key,
+        "--fault",
+        "sbox:3:0x4",
+        "--count",
+        "50",
+        "--seed",
+        "7",
+        "--output",
+        path,
+    ];
+    assert!(eva_gen(&args));
+    let first = fs::read(path).unwrap();
+    assert!(eva_gen(&args));
+    assert_eq!(fs::read(path).unwrap(), first);
+
+    let reader = DatasetReader::new(File::open(path).unwrap()).unwrap();
+    let header = reader.header().clone();
+    assert_eq!(header.packing, Packing::Nibble);
+    assert_eq!(header.seed, Some(7));
+    assert_eq!(header.fault, FaultModel::SboxByte { index: 3, value: 4 });
+    let cipher = PRESENT::new(&header.key).with_sbox_byte(3, 4);
+    let records: Vec<Record> = reader.map(|r| r.unwrap()).collect();
+    assert_eq!(records.len(), 50);
+    for record in records.iter() {
+        assert!(record.plaintext.iter().all(|&x| x < 16));
+        assert_eq!(
+            record.ciphertext,
+            cipher.encrypt(&record.plaintext).to_vec()
+        );
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn gen_rejects_bad_options() {
+    let path = env::temp_dir().join("eva-gen-rejected");
+    let path = path.to_str().unwrap();
+    let key = "000102030405060708090a0b0c0d0e0f";
+    assert!(!eva_gen(&[
+        "--cipher", "aes", "--key", "0001", "--output", path
+    ]));
+    assert!(!eva_gen(&[
+        "--cipher", "sm4", "--key", key, "--fault", "sbox:0:1", "--output", path
+    ]));
+    assert!(!eva_gen(&[
+        "--cipher",
+        "aes",
+        "--key",
+        key,
+        "--fault",
+        "sbox:0:300",
+        "--output",
+        path
+    ]));
+    assert!(!eva_gen(&[
+        "--cipher",
+        "present",
+        "--key",
+        key,
+        "--plaintext",
+        "byte",
+        "--output",
+        path
+    ]));
+}
+
+#[test]
+fn gen_npz_trace_set() {
+    let path = env::temp_dir().join("eva-gen-aes.npz");
+    let path = path.to_str().unwrap();
+    let key: Vec<u8> = (0..16).collect();
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    assert!(eva_gen(&[
+        "--cipher", "aes", "--key", &hex, "--count", "3", "--format", "npz", "--output", path
+    ]));
+
+    let set = load_trace_set(File::open(path).unwrap()).unwrap();
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.keys, vec![key.clone(); 3]);
+    let cipher = AES::new(&key);
+    for (plaintext, ciphertext) in set.plaintexts.iter().zip(set.ciphertexts.iter()) {
+        assert_eq!(&cipher.encrypt(plaintext), ciphertext);
+    }
+    fs::remove_file(path).unwrap();
+}
@@ -13,7 +13,7 @@ fn led_64() {
 
     let msg = create_u8x4x4(&key.clone());
     println!("{:x?}", mix_columns_serial(&msg));
-    println!("{:x?}", 8u8.gmul(&8, 4));
+    println!("{:x?}", 8u8.gmul(&8, Field::GF16));
 
     assert!(false);
     assert_eq!(led::LED::new(&key).encrypt(&plaintext), ciphertext.to_vec());
@@ -0,0 +1,100 @@
+use eva_crypto::npy::*;
+use eva_crypto::traces::TraceSet;
+use std::fs::File;
+use std::io::Cursor;
+
+#[cfg(test)]
+#[test]
+fn npy_roundtrip() {
+    let array = Array::new(vec![2, 3], vec![-1i16, 2, -3, 4, -5, 6]).unwrap();
+    let mut bytes = vec![];
+    write_npy(&mut bytes, &array).unwrap();
+    assert_eq!(&bytes[0..8], b"\x93NUMPY\x01\x00");
+    assert_eq!((10 + bytes[8] as usize) % 64, 0);
+    assert_eq!(read_npy::<i16, _>(&mut &bytes[..]).unwrap(), array);
+    assert!(read_npy::<f32, _>(&mut &bytes[..]).is_err());
+    assert_eq!(
+        read_npy_any(&mut &bytes[..]).unwrap().to_f32().data,
+        vec![-1.0, 2.0, -3.0, 4.0, -5.0, 6.0]
+    );
+    assert_eq!(array.to_rows(), vec![vec![-1, 2, -3], vec![4, -5, 6]]);
+}
+
+#[test]
+fn npy_numpy_header() {
+    // As written by numpy 1.17 for `np.arange(4, dtype=np.uint8)`.
+    let mut bytes = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+    let mut dict = "{'descr': '|u1', 'fortran_order': False, 'shape': (4,), }".to_string();
+    while dict.len() < 0x75 {
+        dict.push(' ');
+    }
+    dict.push('\n');
+    bytes.extend_from_slice(dict.as_bytes());
+    bytes.extend_from_slice(&[0, 1, 2, 3]);
+    let array = read_npy::<u8, _>(&mut &bytes[..]).unwrap();
+    assert_eq!(array.shape, vec![4]);
+    assert_eq!(array.data, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn npz_trace_set() {
+    let set = TraceSet {
+        traces: vec![vec![0.5, 1.5, 2.5], vec![-0.5, -1.5, -2.5]],
+        plaintexts: vec![vec![0; 16], vec![1; 16]],
+        ciphertexts: vec![],
+        keys: vec![vec![7; 16], vec![7; 16]],
+    };
+    let bytes = save_trace_set(vec![], &set).unwrap();
+    let mut npz = NpzReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(npz.names(), vec!["keys", "plaintexts", "traces"]);
+    assert_eq!(npz.array::<u8>("keys").unwrap().shape, vec![2, 16]);
+    assert_eq!(load_trace_set(Cursor::new(&bytes)).unwrap(), set);
+}
+
+#[test]
+fn npy_memory_map() {
+    let path = std::env::temp_dir().join(format!("eva-npy-{}.npy", std::process::id()));
+    let array = Array::new(vec![3, 2], vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+    write_npy(&mut File::create(&path).unwrap(), &array).unwrap();
+
+    let mapped = MappedArray::<f32>::open(&path).unwrap();
+    assert_eq!(mapped.shape(), &[3, 2]);
+    assert_eq!(mapped.row(1), &[3.0, 4.0]);
+    assert_eq!(mapped.data(), &array.data[..]);
+    assert!(MappedArray::<i16>::open(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn npy_malformed_header() {
+    let header = |dict: &str| {
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(dict.as_bytes());
+        read_npy_any(&mut &bytes[..]).unwrap_err().kind()
+    };
+    let invalid = std::io::ErrorKind::InvalidData;
+    assert_eq!(header("{'descr':"), invalid);
+    let error = Array::new(vec![2, 2], vec![1u8, 2, 3]).unwrap_err();
+    assert_eq!(error.kind(), invalid);
+    assert!(Array::<u8>::new(vec![usize::MAX, 2], vec![]).is_err());
+    assert_eq!(header("{'descr': é, 'shape': (4,), }"), invalid);
+    assert_eq!(
+        header("{'descr': '|u1', 'fortran_order': False, 'shape': )4,(, }"),
+        invalid
+    );
+    let huge = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, 4), }}",
+        usize::MAX
+    );
+    assert_eq!(header(&huge), invalid);
+
+    // A central directory entry whose name runs past the end of the archive.
+    let mut bytes = save_trace_set(vec![], &TraceSet::default()).unwrap();
+    let cd = bytes.len() - 22 - 46 - "traces.npy".len();
+    bytes[cd + 28] = 0xff;
+    assert_eq!(
+        NpzReader::new(Cursor::new(&bytes)).err().unwrap().kind(),
+        invalid
+    );
+}
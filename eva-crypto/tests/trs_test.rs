@@ -0,0 +1,82 @@
+use eva_crypto::traces::TraceSet;
+use eva_crypto::trs::*;
+
+#[cfg(test)]
+#[test]
+fn trs_roundtrip_in_chunks() {
+    let mut set = TraceSet::new();
+    for i in 0..5 {
+        set.traces.push(vec![i as f32, -(i as f32), 300.0]);
+        set.plaintexts.push(vec![i as u8; 4]);
+        set.ciphertexts.push(vec![0xf0 | i as u8; 4]);
+    }
+    let mut header = TrsHeader::new(5, 3, SampleCoding::Int16, 8);
+    header.title_space = 2;
+    header.global_title = "aes".to_string();
+    let mut writer = TrsWriter::new(vec![], header.clone()).unwrap();
+    writer.write_chunk(&set).unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let mut reader = TrsReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.header(), &header);
+    let first = reader.read_chunk(3).unwrap();
+    let second = reader.read_chunk(3).unwrap();
+    assert_eq!(first.len(), 3);
+    assert_eq!(second.len(), 2);
+    assert!(reader.read_chunk(3).unwrap().is_empty());
+    assert_eq!(second.traces[1], set.traces[4]);
+    assert_eq!(second.plaintexts[1], set.plaintexts[4]);
+    assert_eq!(second.ciphertexts[1], set.ciphertexts[4]);
+}
+
+#[test]
+fn trs_inspector_header() {
+    // Int8 samples, 4 bytes of data split 1/3, an unknown tag and a long-form length.
+    let mut bytes = vec![
+        0x41, 0x04, 0x01, 0x00, 0x00, 0x00, 0x42, 0x04, 0x02, 0x00, 0x00, 0x00, 0x43, 0x01, 0x01,
+        0x44, 0x02, 0x04, 0x00, 0x49, 0x81, 0x01, b's', 0x5f, 0x00,
+    ];
+    bytes.extend_from_slice(&[1, 2, 3, 4, 0x80, 0x7f]);
+    let mut reader = TrsReader::new(&bytes[..]).unwrap().with_plaintext_len(1);
+    let header = reader.header().clone();
+    assert_eq!((header.traces, header.samples), (1, 2));
+    assert_eq!(header.coding, SampleCoding::Int8);
+    let set = reader.read_chunk(10).unwrap();
+    assert_eq!(set.plaintexts, vec![vec![1]]);
+    assert_eq!(set.ciphertexts, vec![vec![2, 3, 4]]);
+    assert_eq!(set.traces, vec![vec![-128.0, 127.0]]);
+}
+
+#[test]
+fn trs_header_overflow() {
+    let mut header = TrsHeader::new(1, 3, SampleCoding::Float, 0x10000);
+    let error = TrsWriter::new(vec![], header.clone()).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    header.data_length = 16;
+    header.title_space = 256;
+    assert!(header.write_to(&mut vec![]).is_err());
+    header.title_space = 255;
+    assert!(header.write_to(&mut vec![]).is_ok());
+}
+
+#[test]
+fn trs_corrupt_header() {
+    let invalid = std::io::ErrorKind::InvalidData;
+    // A length field of 8 bytes, and an object claiming 2 GB.
+    let error = TrsReader::new(&[0x41, 0x88, 0, 0, 0, 0, 0, 0, 0, 1][..])
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), invalid);
+    let error = TrsReader::new(&[0x41, 0x84, 0, 0, 0, 0x80, 1][..])
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), invalid);
+
+    // A header claiming 16 GB per trace, followed by a few bytes.
+    let header = TrsHeader::new(1, u32::MAX as usize, SampleCoding::Float, 0);
+    let mut bytes = vec![];
+    header.write_to(&mut bytes).unwrap();
+    bytes.extend_from_slice(&[0; 12]);
+    let mut reader = TrsReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.read_chunk(1).unwrap_err().kind(), invalid);
+}
@@ -0,0 +1,60 @@
+//! The SAT solvers an instance can be handed to.
+use eva_builder::generic::{Instance, UnknownVariable};
+use std::fmt;
+use std::time::Duration;
+
+/// A literal `(var, positive)`, with variables numbered from 1 like those of an instance.
+pub type Literal = (u32, bool);
+
+/// What a solver has been given and how long it took.
+#[derive(Default, Clone, Copy)]
+pub struct Statistics {
+    pub variables: u32,
+    pub clauses: usize,
+    pub xor_clauses: usize,
+    pub solves: u32,
+    pub time: Duration,
+}
+
+pub trait Solver {
+    /// A variable numbered after all the previous ones.
+    fn new_var(&mut self) -> u32;
+
+    fn add_clause(&mut self, lits: &[Literal]);
+
+    /// Constrain the XOR of the literals to be true.
+    fn add_xor(&mut self, lits: &[Literal]);
+
+    /// Solve with the literals `assumptions` holding for this call only. `None` means the
+    /// solver gave up, such as at its time limit.
+    fn solve(&mut self, assumptions: &[Literal]) -> Result<Option<bool>, String>;
+
+    /// Values of the last model found, `model()[v - 1]` for variable `v`.
+    fn model(&self) -> Vec<bool>;
+
+    fn statistics(&self) -> Statistics;
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Variables: {}", self.variables)?;
+        writeln!(f, "Clauses: {}", self.clauses)?;
+        writeln!(f, "XOR clauses: {}", self.xor_clauses)?;
+        writeln!(f, "Solves: {}", self.solves)?;
+        write!(f, "Solving time: {:.3}s", self.time.as_secs_f64())
+    }
+}
+
+/// Add the variables and hard clauses of `instance`, which must be the first ones added.
+pub fn load(solver: &mut dyn Solver, instance: &Instance) -> Result<(), UnknownVariable> {
+    for _ in 0..instance.variables.len() {
+        solver.new_var();
+    }
+    for (xor, lits) in instance.numbered()? {
+        match xor {
+            true => solver.add_xor(&lits),
+            false => solver.add_clause(&lits),
+        }
+    }
+    Ok(())
+}
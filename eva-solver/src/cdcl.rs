@@ -0,0 +1,1137 @@
+//! A CDCL solver with native XOR clauses, for builds without CryptoMiniSat.
+//!
+//! Clauses use two watched literals and XOR clauses two watched variables. Conflicts are
+//! analysed to their first unique implication point and the learnt clauses minimized,
+//! decisions follow VSIDS activities with phase saving, restarts follow the Luby sequence and
+//! learnt clauses are reduced by LBD. At decision level 0, the XOR clauses are reduced by
+//! Gauss-Jordan elimination, which finds the units, equivalences and contradictions they imply
+//! together.
+//!
+//! Elimination is limited: it never runs during search, where each XOR clause only propagates
+//! on its own through its two watches, and it is skipped when rows times rows times columns
+//! exceed `GAUSS_BUDGET`. The full AES unrolling is past that budget, so its XOR clauses get no
+//! elimination at all, unlike with CryptoMiniSat.
+use super::backend::{Literal, Solver, Statistics};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Variable `v` (from 0) is `2 v` when positive and `2 v + 1` when negated.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct Lit(u32);
+
+impl Lit {
+    fn new(var: usize, positive: bool) -> Self {
+        Lit((var as u32) << 1 | !positive as u32)
+    }
+
+    fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    fn positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::ops::Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Reason {
+    Decision,
+    Clause(usize),
+    /// The clause is kept in `xor_reasons`, under the implied variable.
+    Xor,
+}
+
+struct Clause {
+    lits: Vec<Lit>,
+    learnt: bool,
+    lbd: usize,
+    deleted: bool,
+}
+
+struct Xor {
+    vars: Vec<usize>,
+    rhs: bool,
+    /// Positions in `vars` of the two watched variables.
+    watched: [usize; 2],
+}
+
+#[derive(Clone, Copy)]
+struct Watch {
+    clause: usize,
+    /// A literal of the clause, which satisfies it when true without reading it.
+    blocker: Lit,
+}
+
+/// Binary heap of variables by activity, for picking decisions.
+#[derive(Default)]
+struct Heap {
+    heap: Vec<usize>,
+    position: Vec<Option<usize>>,
+}
+
+impl Heap {
+    fn contains(&self, var: usize) -> bool {
+        self.position[var].is_some()
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.contains(var) {
+            return;
+        }
+        self.position[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.position[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.position[last] = Some(0);
+            self.down(0, activity);
+        }
+        Some(top)
+    }
+
+    /// Restore the order after the activity of `var` increased.
+    fn increased(&mut self, var: usize, activity: &[f64]) {
+        if let Some(i) = self.position[var] {
+            self.up(i, activity);
+        }
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[var] {
+                break;
+            }
+            self.heap[i] = self.heap[parent];
+            self.position[self.heap[i]] = Some(i);
+            i = parent;
+        }
+        self.heap[i] = var;
+        self.position[var] = Some(i);
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+        loop {
+            let mut child = 2 * i + 1;
+            if child >= self.heap.len() {
+                break;
+            }
+            if child + 1 < self.heap.len()
+                && activity[self.heap[child + 1]] > activity[self.heap[child]]
+            {
+                child += 1;
+            }
+            if activity[self.heap[child]] <= activity[var] {
+                break;
+            }
+            self.heap[i] = self.heap[child];
+            self.position[self.heap[i]] = Some(i);
+            i = child;
+        }
+        self.heap[i] = var;
+        self.position[var] = Some(i);
+    }
+}
+
+/// Element `i` (from 1) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, ...
+fn luby(mut i: u64) -> u64 {
+    loop {
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if (1 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// Largest XOR system, as rows times rows times columns, eliminated at level 0.
+const GAUSS_BUDGET: usize = 1 << 32;
+/// Rows found by elimination are kept as XOR clauses up to this length.
+const GAUSS_ROW: usize = 3;
+const RESTART_BASE: u64 = 100;
+/// Propagations between two looks at the clock during a search.
+const DEADLINE_STEPS: u64 = 256;
+/// Conflicts before the first deletion of learnt clauses, and the growth of the interval.
+const REDUCE_BASE: u64 = 2000;
+const REDUCE_STEP: u64 = 300;
+
+pub struct Cdcl {
+    clauses: Vec<Clause>,
+    watches: Vec<Vec<Watch>>,
+    xors: Vec<Xor>,
+    xor_watches: Vec<Vec<usize>>,
+    /// Rows of `xors`, to keep those found by elimination only once.
+    xor_rows: HashSet<(Vec<usize>, bool)>,
+    xor_reasons: Vec<Vec<Lit>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Reason>,
+    trail: Vec<Lit>,
+    trail_limits: Vec<usize>,
+    queue_head: usize,
+    activity: Vec<f64>,
+    var_increment: f64,
+    heap: Heap,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    failed: Vec<bool>,
+    learnts: usize,
+    conflicts: u64,
+    /// Conflicts between deletions of learnt clauses, growing after each.
+    reduce_interval: u64,
+    next_reduce: u64,
+    /// False once the clauses are known to be unsatisfiable.
+    ok: bool,
+    /// Length of the trail at level 0 when the XOR clauses were last eliminated.
+    gauss_trail: Option<usize>,
+    model: Vec<bool>,
+    time_limit: Option<Duration>,
+    stats: Statistics,
+}
+
+impl Cdcl {
+    pub fn new(time_limit: Option<f64>) -> Self {
+        Cdcl {
+            clauses: vec![],
+            watches: vec![],
+            xors: vec![],
+            xor_watches: vec![],
+            xor_rows: HashSet::new(),
+            xor_reasons: vec![],
+            values: vec![],
+            levels: vec![],
+            reasons: vec![],
+            trail: vec![],
+            trail_limits: vec![],
+            queue_head: 0,
+            activity: vec![],
+            var_increment: 1.0,
+            heap: Heap::default(),
+            phases: vec![],
+            seen: vec![],
+            failed: vec![],
+            learnts: 0,
+            conflicts: 0,
+            reduce_interval: REDUCE_BASE,
+            next_reduce: REDUCE_BASE,
+            ok: true,
+            gauss_trail: None,
+            model: vec![],
+            time_limit: time_limit.map(Duration::from_secs_f64),
+            stats: Statistics::default(),
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|v| v == lit.positive())
+    }
+
+    fn level(&self) -> usize {
+        self.trail_limits.len()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Reason) {
+        let var = lit.var();
+        self.values[var] = Some(lit.positive());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let limit = self.trail_limits[level];
+        for lit in self.trail.drain(limit..).rev() {
+            let var = lit.var();
+            self.values[var] = None;
+            self.phases[var] = lit.positive();
+            self.heap.insert(var, &self.activity);
+        }
+        self.trail_limits.truncate(level);
+        self.queue_head = limit;
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>, learnt: bool, lbd: usize) -> usize {
+        let id = self.clauses.len();
+        self.watches[lits[0].index()].push(Watch {
+            clause: id,
+            blocker: lits[1],
+        });
+        self.watches[lits[1].index()].push(Watch {
+            clause: id,
+            blocker: lits[0],
+        });
+        self.clauses.push(Clause {
+            lits,
+            learnt,
+            lbd,
+            deleted: false,
+        });
+        id
+    }
+
+    /// Add an XOR clause over unassigned variables, at level 0.
+    fn attach_xor(&mut self, vars: Vec<usize>, rhs: bool) {
+        if !self.xor_rows.insert((vars.clone(), rhs)) {
+            return;
+        }
+        let id = self.xors.len();
+        self.xor_watches[vars[0]].push(id);
+        self.xor_watches[vars[1]].push(id);
+        self.xors.push(Xor {
+            vars,
+            rhs,
+            watched: [0, 1],
+        });
+    }
+
+    /// Propagate the trail, returning the literals of a falsified clause on conflict.
+    fn propagate(&mut self) -> Option<Vec<Lit>> {
+        while self.queue_head < self.trail.len() {
+            let lit = self.trail[self.queue_head];
+            self.queue_head += 1;
+            if let Some(conflict) = self.propagate_clauses(!lit) {
+                return Some(conflict);
+            }
+            if let Some(conflict) = self.propagate_xors(lit.var()) {
+                return Some(conflict);
+            }
+        }
+        None
+    }
+
+    /// Visit the clauses watching `false_lit`, which has just become false.
+    fn propagate_clauses(&mut self, false_lit: Lit) -> Option<Vec<Lit>> {
+        let mut watches = std::mem::take(&mut self.watches[false_lit.index()]);
+        let mut conflict = None;
+        let mut kept = 0;
+        let mut i = 0;
+        while i < watches.len() {
+            let watch = watches[i];
+            i += 1;
+            if self.value(watch.blocker) == Some(true) {
+                watches[kept] = watch;
+                kept += 1;
+                continue;
+            }
+            let id = watch.clause;
+            if self.clauses[id].deleted {
+                continue;
+            }
+            let lits = &mut self.clauses[id].lits;
+            if lits[0] == false_lit {
+                lits.swap(0, 1);
+            }
+            let first = lits[0];
+            let blocker = watch.blocker;
+            let watch = Watch {
+                clause: id,
+                blocker: first,
+            };
+            if first != blocker && self.values[first.var()] == Some(first.positive()) {
+                watches[kept] = watch;
+                kept += 1;
+                continue;
+            }
+            // Look for another literal to watch.
+            let values = &self.values;
+            let replacement =
+                (2..lits.len()).find(|&k| values[lits[k].var()] != Some(!lits[k].positive()));
+            if let Some(k) = replacement {
+                lits.swap(1, k);
+                let new_watch = lits[1];
+                self.watches[new_watch.index()].push(watch);
+                continue;
+            }
+            watches[kept] = watch;
+            kept += 1;
+            match self.value(first) {
+                Some(false) => {
+                    conflict = Some(self.clauses[id].lits.clone());
+                    while i < watches.len() {
+                        watches[kept] = watches[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                }
+                Some(true) => {}
+                None => self.assign(first, Reason::Clause(id)),
+            }
+        }
+        watches.truncate(kept);
+        self.watches[false_lit.index()] = watches;
+        conflict
+    }
+
+    /// Visit the XOR clauses watching `var`, which has just been assigned.
+    fn propagate_xors(&mut self, var: usize) -> Option<Vec<Lit>> {
+        let watching = std::mem::take(&mut self.xor_watches[var]);
+        let mut kept = vec![];
+        let mut conflict = None;
+        for (n, &id) in watching.iter().enumerate() {
+            if conflict.is_some() {
+                kept.extend_from_slice(&watching[n..]);
+                break;
+            }
+            let xor = &mut self.xors[id];
+            let w = if xor.vars[xor.watched[0]] == var {
+                0
+            } else {
+                1
+            };
+            let other = xor.vars[xor.watched[1 - w]];
+            let values = &self.values;
+            let replacement = (0..xor.vars.len())
+                .find(|&k| k != xor.watched[1 - w] && values[xor.vars[k]].is_none());
+            if let Some(k) = replacement {
+                xor.watched[w] = k;
+                let new_var = xor.vars[k];
+                self.xor_watches[new_var].push(id);
+                continue;
+            }
+            kept.push(id);
+            // Every variable but `other` is assigned.
+            let parity = xor
+                .vars
+                .iter()
+                .filter(|&&v| v != other)
+                .fold(xor.rhs, |acc, &v| acc ^ values[v].unwrap());
+            let falsified = |v: usize| Lit::new(v, !values[v].unwrap());
+            match values[other] {
+                None => {
+                    let mut reason = vec![Lit::new(other, parity)];
+                    reason.extend(
+                        xor.vars
+                            .iter()
+                            .filter(|&&v| v != other)
+                            .map(|&v| falsified(v)),
+                    );
+                    self.xor_reasons[other] = reason;
+                    self.assign(Lit::new(other, parity), Reason::Xor);
+                }
+                Some(value) if value != parity => {
+                    conflict = Some(xor.vars.iter().map(|&v| falsified(v)).collect());
+                }
+                Some(_) => {}
+            }
+        }
+        self.xor_watches[var].extend(kept);
+        conflict
+    }
+
+    /// The literals of the reason of `var` but its own.
+    fn reason_lits(&self, var: usize) -> &[Lit] {
+        match self.reasons[var] {
+            Reason::Clause(id) => &self.clauses[id].lits[1..],
+            Reason::Xor => &self.xor_reasons[var][1..],
+            Reason::Decision => &[],
+        }
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.var_increment;
+        if self.activity[var] > 1e100 {
+            for a in self.activity.iter_mut() {
+                *a *= 1e-100;
+            }
+            self.var_increment *= 1e-100;
+        }
+        self.heap.increased(var, &self.activity);
+    }
+
+    /// Learn a clause from a conflict, with its asserting literal first and a literal of the
+    /// level to backtrack to second.
+    fn analyze(&mut self, conflict: Vec<Lit>) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut lits = conflict;
+        loop {
+            for &lit in lits.iter() {
+                let var = lit.var();
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == self.level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let implied = self.trail[index];
+            self.seen[implied.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !implied;
+                break;
+            }
+            lits = self.reason_lits(implied.var()).to_vec();
+        }
+
+        // Drop the literals implied by the others.
+        let levels = learnt[1..]
+            .iter()
+            .fold(0u32, |levels, l| levels | self.abstract_level(l.var()));
+        let mut cleared: Vec<usize> = learnt.iter().map(|l| l.var()).collect();
+        let mut k = 0;
+        let redundant: Vec<bool> = learnt
+            .iter()
+            .map(|&lit| {
+                k += 1;
+                k > 1 && self.redundant(lit, levels, &mut cleared)
+            })
+            .collect();
+        for &var in cleared.iter() {
+            self.seen[var] = false;
+            self.failed[var] = false;
+        }
+        let mut k = 0;
+        learnt.retain(|_| {
+            k += 1;
+            !redundant[k - 1]
+        });
+
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let (i, max) = (1..learnt.len())
+                .map(|i| (i, self.levels[learnt[i].var()]))
+                .max_by_key(|&(_, level)| level)
+                .unwrap();
+            learnt.swap(1, i);
+            level = max;
+        }
+        (learnt, level)
+    }
+
+    fn abstract_level(&self, var: usize) -> u32 {
+        1 << (self.levels[var] & 31)
+    }
+
+    /// Whether `lit` of a learnt clause follows from its other literals, marked as seen. The
+    /// variables visited along the way are added to `cleared`, and marked as failed when `lit`
+    /// does not follow so that they are not visited again.
+    fn redundant(&mut self, lit: Lit, levels: u32, cleared: &mut Vec<usize>) -> bool {
+        if self.reasons[lit.var()] == Reason::Decision {
+            return false;
+        }
+        let top = cleared.len();
+        let mut stack = vec![lit.var()];
+        while let Some(var) = stack.pop() {
+            for i in 0..self.reason_lits(var).len() {
+                let v = self.reason_lits(var)[i].var();
+                if self.seen[v] || self.levels[v] == 0 {
+                    continue;
+                }
+                if self.failed[v]
+                    || self.reasons[v] == Reason::Decision
+                    || self.abstract_level(v) & levels == 0
+                {
+                    for &var in cleared[top..].iter() {
+                        self.seen[var] = false;
+                        self.failed[var] = true;
+                    }
+                    return false;
+                }
+                self.seen[v] = true;
+                stack.push(v);
+                cleared.push(v);
+            }
+        }
+        true
+    }
+
+    /// Number of distinct decision levels among `lits`.
+    fn lbd(&self, lits: &[Lit]) -> usize {
+        let levels: HashSet<usize> = lits.iter().map(|l| self.levels[l.var()]).collect();
+        levels.len()
+    }
+
+    /// Delete half of the learnt clauses, those with the largest LBD, keeping reasons.
+    fn reduce(&mut self) {
+        let mut candidates: Vec<usize> = (0..self.clauses.len())
+            .filter(|&id| {
+                let clause = &self.clauses[id];
+                if !clause.learnt || clause.deleted || clause.lbd <= 2 {
+                    return false;
+                }
+                let first = clause.lits[0].var();
+                !(self.reasons[first] == Reason::Clause(id) && self.values[first].is_some())
+            })
+            .collect();
+        candidates.sort_by_key(|&id| std::cmp::Reverse(self.clauses[id].lbd));
+        for &id in candidates.iter().take(candidates.len() / 2) {
+            self.clauses[id].deleted = true;
+            self.clauses[id].lits = vec![];
+            self.learnts -= 1;
+        }
+        for watches in self.watches.iter_mut() {
+            let clauses = &self.clauses;
+            watches.retain(|w| !clauses[w.clause].deleted);
+        }
+    }
+
+    /// Reduce the XOR clauses over the unassigned variables by Gauss-Jordan elimination at
+    /// level 0, assigning the units found and adding short rows as new XOR clauses.
+    fn gauss(&mut self) {
+        if self.gauss_trail == Some(self.trail.len()) {
+            return;
+        }
+        self.gauss_trail = Some(self.trail.len());
+        let mut columns: Vec<usize> = vec![];
+        let mut column = vec![usize::MAX; self.values.len()];
+        let mut rows: Vec<(Vec<usize>, bool)> = vec![];
+        for xor in self.xors.iter() {
+            let mut rhs = xor.rhs;
+            let mut vars = vec![];
+            for &v in xor.vars.iter() {
+                match self.values[v] {
+                    Some(value) => rhs ^= value,
+                    None => vars.push(v),
+                }
+            }
+            for &v in vars.iter() {
+                if column[v] == usize::MAX {
+                    column[v] = columns.len();
+                    columns.push(v);
+                }
+            }
+            rows.push((vars, rhs));
+        }
+        let n = rows.len();
+        if n.saturating_mul(n).saturating_mul(columns.len()) > GAUSS_BUDGET {
+            return;
+        }
+
+        // Rows as bit sets, with the right-hand side in the last bit.
+        let words = columns.len() / 64 + 1;
+        let rhs_bit = columns.len();
+        let mut matrix: Vec<Vec<u64>> = rows
+            .iter()
+            .map(|(vars, rhs)| {
+                let mut row = vec![0u64; words];
+                for &v in vars.iter() {
+                    row[column[v] / 64] |= 1 << (column[v] % 64);
+                }
+                row[rhs_bit / 64] |= (*rhs as u64) << (rhs_bit % 64);
+                row
+            })
+            .collect();
+        let mut pivot_row = 0;
+        for c in 0..columns.len() {
+            let (word, bit) = (c / 64, 1u64 << (c % 64));
+            let pivot = match (pivot_row..n).find(|&r| matrix[r][word] & bit != 0) {
+                Some(r) => r,
+                None => continue,
+            };
+            matrix.swap(pivot_row, pivot);
+            let (above, rest) = matrix.split_at_mut(pivot_row);
+            let (pivot, below) = rest.split_first_mut().unwrap();
+            for row in above.iter_mut().chain(below.iter_mut()) {
+                if row[word] & bit != 0 {
+                    for (a, b) in row.iter_mut().zip(pivot.iter()) {
+                        *a ^= b;
+                    }
+                }
+            }
+            pivot_row += 1;
+        }
+
+        for row in matrix.iter() {
+            let rhs = row[rhs_bit / 64] >> (rhs_bit % 64) & 1 == 1;
+            let vars: Vec<usize> = (0..columns.len())
+                .filter(|&c| row[c / 64] >> (c % 64) & 1 == 1)
+                .map(|c| columns[c])
+                .collect();
+            match vars.len() {
+                0 if rhs => {
+                    self.ok = false;
+                    return;
+                }
+                0 => {}
+                1 => self.assign(Lit::new(vars[0], rhs), Reason::Decision),
+                len if len <= GAUSS_ROW => {
+                    let mut vars = vars;
+                    vars.sort_unstable();
+                    self.attach_xor(vars, rhs);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Propagate at level 0, then eliminate the XOR clauses until nothing new is found.
+    fn simplify(&mut self) {
+        while self.ok {
+            if self.propagate().is_some() {
+                self.ok = false;
+                return;
+            }
+            if self.gauss_trail == Some(self.trail.len()) {
+                return;
+            }
+            self.gauss();
+        }
+    }
+
+    fn pick(&mut self) -> Option<Lit> {
+        while let Some(var) = self.heap.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(Lit::new(var, self.phases[var]));
+            }
+        }
+        None
+    }
+
+    /// Search until a model, a contradiction, `conflicts` conflicts or the `deadline`. `None`
+    /// means a restart, or that the deadline passed.
+    fn search(
+        &mut self,
+        assumptions: &[Lit],
+        conflicts: u64,
+        deadline: Option<Instant>,
+    ) -> Option<bool> {
+        let mut count = 0;
+        let mut steps: u64 = 0;
+        loop {
+            steps += 1;
+            if steps.is_multiple_of(DEADLINE_STEPS) && deadline.is_some_and(|d| Instant::now() > d)
+            {
+                self.backtrack(0);
+                return None;
+            }
+            if let Some(conflict) = self.propagate() {
+                count += 1;
+                self.conflicts += 1;
+                if self.level() == 0 {
+                    self.ok = false;
+                    return Some(false);
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], Reason::Decision);
+                } else {
+                    let lbd = self.lbd(&learnt);
+                    let first = learnt[0];
+                    let id = self.attach(learnt, true, lbd);
+                    self.learnts += 1;
+                    self.assign(first, Reason::Clause(id));
+                }
+                self.var_increment /= 0.95;
+                continue;
+            }
+            if count >= conflicts {
+                self.backtrack(0);
+                return None;
+            }
+            if self.conflicts >= self.next_reduce {
+                self.reduce();
+                self.reduce_interval += REDUCE_STEP;
+                self.next_reduce = self.conflicts + self.reduce_interval;
+            }
+            let next = if self.level() < assumptions.len() {
+                let lit = assumptions[self.level()];
+                match self.value(lit) {
+                    Some(true) => {
+                        self.trail_limits.push(self.trail.len());
+                        continue;
+                    }
+                    Some(false) => return Some(false),
+                    None => lit,
+                }
+            } else {
+                match self.pick() {
+                    Some(lit) => lit,
+                    None => return Some(true),
+                }
+            };
+            self.trail_limits.push(self.trail.len());
+            self.assign(next, Reason::Decision);
+        }
+    }
+}
+
+impl Solver for Cdcl {
+    fn new_var(&mut self) -> u32 {
+        let var = self.values.len();
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.xor_watches.push(vec![]);
+        self.xor_reasons.push(vec![]);
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(Reason::Decision);
+        self.activity.push(0.0);
+        self.heap.position.push(None);
+        self.heap.insert(var, &self.activity);
+        self.phases.push(false);
+        self.seen.push(false);
+        self.failed.push(false);
+        self.stats.variables += 1;
+        self.stats.variables
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.stats.clauses += 1;
+        if !self.ok {
+            return;
+        }
+        let mut clause: Vec<Lit> = vec![];
+        for &(var, positive) in lits.iter() {
+            let lit = Lit::new(var as usize - 1, positive);
+            match self.value(lit) {
+                Some(true) => return,
+                Some(false) => {}
+                None if clause.contains(&!lit) => return,
+                None if clause.contains(&lit) => {}
+                None => clause.push(lit),
+            }
+        }
+        match clause.len() {
+            0 => self.ok = false,
+            1 => {
+                self.assign(clause[0], Reason::Decision);
+                self.ok = self.propagate().is_none();
+            }
+            _ => {
+                self.attach(clause, false, 0);
+            }
+        }
+    }
+
+    fn add_xor(&mut self, lits: &[Literal]) {
+        self.stats.xor_clauses += 1;
+        self.gauss_trail = None;
+        if !self.ok {
+            return;
+        }
+        // The XOR of the variables equals `rhs`, and repeated variables cancel out.
+        let mut rhs = true;
+        let mut vars: Vec<usize> = vec![];
+        for &(var, positive) in lits.iter() {
+            let var = var as usize - 1;
+            rhs ^= !positive;
+            match self.values[var] {
+                Some(value) => rhs ^= value,
+                None => match vars.iter().position(|&v| v == var) {
+                    Some(i) => {
+                        vars.swap_remove(i);
+                    }
+                    None => vars.push(var),
+                },
+            }
+        }
+        match vars.len() {
+            0 => self.ok = !rhs,
+            1 => {
+                self.assign(Lit::new(vars[0], rhs), Reason::Decision);
+                self.ok = self.propagate().is_none();
+            }
+            _ => {
+                vars.sort_unstable();
+                self.attach_xor(vars, rhs);
+            }
+        }
+    }
+
+    fn solve(&mut self, assumptions: &[Literal]) -> Result<Option<bool>, String> {
+        let start = Instant::now();
+        let deadline = self.time_limit.map(|limit| start + limit);
+        self.stats.solves += 1;
+        let assumptions: Vec<Lit> = assumptions
+            .iter()
+            .map(|&(var, positive)| Lit::new(var as usize - 1, positive))
+            .collect();
+        let mut result = None;
+        let mut restarts = 0;
+        while result.is_none() {
+            self.simplify();
+            if !self.ok {
+                result = Some(false);
+                break;
+            }
+            if deadline.is_some_and(|d| Instant::now() > d) {
+                break;
+            }
+            restarts += 1;
+            result = self.search(&assumptions, luby(restarts) * RESTART_BASE, deadline);
+        }
+        if result == Some(true) {
+            self.model = self.values.iter().map(|v| v.unwrap_or(false)).collect();
+        }
+        self.backtrack(0);
+        self.stats.time += start.elapsed();
+        Ok(result)
+    }
+
+    fn model(&self) -> Vec<bool> {
+        self.model.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::load;
+    use eva_builder::generic::Clause;
+    use eva_builder::symbolic::{trace, Word};
+    use eva_crypto::generic::create_u8x4x4;
+    use eva_crypto::present::{self, PRESENT};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Whether `values[v - 1]`, the value of each variable `v`, satisfies the clauses.
+    fn satisfies(clauses: &[(bool, Vec<Literal>)], values: &[bool]) -> bool {
+        clauses.iter().all(|(xor, lits)| {
+            let mut lits = lits
+                .iter()
+                .map(|&(var, positive)| values[var as usize - 1] == positive);
+            match xor {
+                true => lits.fold(false, |acc, value| acc ^ value),
+                false => lits.any(|value| value),
+            }
+        })
+    }
+
+    fn random_clause(rng: &mut StdRng, vars: u32, len: usize) -> Vec<Literal> {
+        (0..len)
+            .map(|_| (rng.gen_range(1, vars + 1), rng.gen()))
+            .collect()
+    }
+
+    /// Small random instances of clauses and XOR clauses, solved again under random assumptions
+    /// and with clauses added between the solves, against all their assignments.
+    #[test]
+    fn random_instances_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut answers = [0; 2];
+        for &xor_ratio in [0.0, 0.3, 1.0].iter() {
+            for _ in 0..300 {
+                let vars = rng.gen_range(1, 11);
+                let mut solver = Cdcl::new(None);
+                for _ in 0..vars {
+                    solver.new_var();
+                }
+                let mut clauses: Vec<(bool, Vec<Literal>)> = vec![];
+                for round in 0..4 {
+                    let count = match round {
+                        0 => rng.gen_range(0, 3 * vars),
+                        _ => rng.gen_range(0, 3),
+                    };
+                    for _ in 0..count {
+                        let xor = rng.gen_bool(xor_ratio);
+                        let len = rng.gen_range(1, if xor { 6 } else { 4 });
+                        let lits = random_clause(&mut rng, vars, len);
+                        match xor {
+                            true => solver.add_xor(&lits),
+                            false => solver.add_clause(&lits),
+                        }
+                        clauses.push((xor, lits));
+                    }
+                    let len = rng.gen_range(0, 3);
+                    let assumptions = random_clause(&mut rng, vars, len);
+                    let expected = (0..1u32 << vars).any(|assignment| {
+                        let values: Vec<bool> =
+                            (0..vars).map(|v| (assignment >> v) & 1 == 1).collect();
+                        satisfies(&clauses, &values)
+                            && assumptions
+                                .iter()
+                                .all(|&(var, positive)| values[var as usize - 1] == positive)
+                    });
+                    assert_eq!(solver.solve(&assumptions).unwrap(), Some(expected));
+                    answers[expected as usize] += 1;
+                    if expected {
+                        let model = solver.model();
+                        assert!(satisfies(&clauses, &model));
+                        for &(var, positive) in assumptions.iter() {
+                            assert_eq!(model[var as usize - 1], positive);
+                        }
+                    }
+                }
+            }
+        }
+        assert!(answers.iter().all(|&n| n > 500), "{:?}", answers);
+    }
+
+    /// Full PRESENT with its first round key unknown, from a plaintext and its ciphertext.
+    #[test]
+    fn present_first_round_key() {
+        let key: Vec<u8> = (0..20).map(|x| (x * 7 + 1) % 16).collect();
+        let plaintext: Vec<u8> = (0..16).map(|x| (x * 5 + 3) % 16).collect();
+        let cipher = PRESENT::new(&key);
+        let (_, mut instance) = trace(|| {
+            let mut round_keys: Vec<[[Word; 4]; 4]> = cipher
+                .round_keys
+                .iter()
+                .map(|rk| {
+                    let words: Vec<Word> = rk.concat().iter().map(|&x| Word::from(x)).collect();
+                    create_u8x4x4(&words)
+                })
+                .collect();
+            let first: Vec<Word> = (0..16)
+                .map(|i| Word::variable(&format!("K#{}", i), 4))
+                .collect();
+            round_keys[0] = create_u8x4x4(&first);
+            let plaintext: Vec<Word> = plaintext.iter().map(|&x| Word::from(x)).collect();
+            let ciphertext = present::encrypt_block(&plaintext, &round_keys, &present::SBOX);
+            for (i, word) in ciphertext.iter().enumerate() {
+                word.bind(&format!("C#{}", i));
+            }
+        });
+        for (i, &c) in cipher.encrypt(&plaintext).iter().enumerate() {
+            for b in 1..=8 {
+                let bit = (c >> (8 - b)) & 1 == 1;
+                let name = format!("C#{}#{}#", i, b);
+                instance.add_clause(Clause::new(false, vec![(&name, bit)]));
+            }
+        }
+        let key_vars: Vec<u32> = (0..16)
+            .flat_map(|i| (1..=4).map(move |b| format!("K#{}#{}#", i, b)))
+            .map(|name| instance.variables[&name])
+            .collect();
+
+        let mut solver = Cdcl::new(None);
+        load(&mut solver, &instance).unwrap();
+        assert_eq!(solver.solve(&[]).unwrap(), Some(true));
+        let model = solver.model();
+        let nibbles: Vec<u8> = key_vars
+            .chunks(4)
+            .map(|bits| {
+                bits.iter()
+                    .fold(0, |acc, &var| (acc << 1) | model[var as usize - 1] as u8)
+            })
+            .collect();
+        assert_eq!(nibbles, cipher.round_keys[0].concat());
+
+        // The round key is the only solution.
+        let first = key_vars[0];
+        let wrong = (first, !model[first as usize - 1]);
+        assert_eq!(solver.solve(&[wrong]).unwrap(), Some(false));
+        let blocking: Vec<Literal> = key_vars
+            .iter()
+            .map(|&var| (var, !model[var as usize - 1]))
+            .collect();
+        solver.add_clause(&blocking);
+        assert_eq!(solver.solve(&[]).unwrap(), Some(false));
+    }
+
+    /// Ten pigeons in nine holes, which takes far longer than the time limit to refute.
+    #[test]
+    fn time_limit_stops_search() {
+        let (pigeons, holes) = (10, 9);
+        let var = |p: u32, h: u32| p * holes + h + 1;
+        let mut solver = Cdcl::new(Some(0.2));
+        for _ in 0..pigeons * holes {
+            solver.new_var();
+        }
+        for p in 0..pigeons {
+            let lits: Vec<Literal> = (0..holes).map(|h| (var(p, h), true)).collect();
+            solver.add_clause(&lits);
+        }
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    solver.add_clause(&[(var(p, h), false), (var(q, h), false)]);
+                }
+            }
+        }
+        let start = Instant::now();
+        assert_eq!(solver.solve(&[]).unwrap(), None);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    /// PRESENT-80 reduced to two rounds and a final key, with the whole master key unknown and
+    /// run through the key schedule, from four plaintexts and their ciphertexts.
+    #[test]
+    fn reduced_present_master_key() {
+        let rounds = 3;
+        let key: Vec<u8> = (0..20).map(|x| (x * 7 + 1) % 16).collect();
+        let plaintexts: Vec<Vec<u8>> = (0..4)
+            .map(|p| (0..16).map(|x| (x * 5 + p * 3 + 3) % 16).collect())
+            .collect();
+        let round_keys = present::expand_key(&key, rounds);
+        let (_, mut instance) = trace(|| {
+            let key: Vec<Word> = (0..20)
+                .map(|i| Word::variable(&format!("K#{}", i), 4))
+                .collect();
+            let round_keys = present::expand_key(&key, rounds);
+            for (p, plaintext) in plaintexts.iter().enumerate() {
+                let plaintext: Vec<Word> = plaintext.iter().map(|&x| Word::from(x)).collect();
+                let ciphertext = present::encrypt_block(&plaintext, &round_keys, &present::SBOX);
+                for (i, word) in ciphertext.iter().enumerate() {
+                    word.bind(&format!("C#{}#{}", p, i));
+                }
+            }
+        });
+        for (p, plaintext) in plaintexts.iter().enumerate() {
+            let ciphertext = present::encrypt_block(plaintext, &round_keys, &present::SBOX);
+            for (i, &c) in ciphertext.iter().enumerate() {
+                for b in 1..=8 {
+                    let bit = (c >> (8 - b)) & 1 == 1;
+                    let name = format!("C#{}#{}#{}#", p, i, b);
+                    instance.add_clause(Clause::new(false, vec![(&name, bit)]));
+                }
+            }
+        }
+        let key_vars: Vec<u32> = (0..20)
+            .flat_map(|i| (1..=4).map(move |b| format!("K#{}#{}#", i, b)))
+            .map(|name| instance.variables[&name])
+            .collect();
+
+        let mut solver = Cdcl::new(None);
+        load(&mut solver, &instance).unwrap();
+        assert_eq!(solver.solve(&[]).unwrap(), Some(true));
+        let model = solver.model();
+        let nibbles: Vec<u8> = key_vars
+            .chunks(4)
+            .map(|bits| {
+                bits.iter()
+                    .fold(0, |acc, &var| (acc << 1) | model[var as usize - 1] as u8)
+            })
+            .collect();
+        assert_eq!(nibbles, key);
+        let blocking: Vec<Literal> = key_vars
+            .iter()
+            .map(|&var| (var, !model[var as usize - 1]))
+            .collect();
+        solver.add_clause(&blocking);
+        assert_eq!(solver.solve(&[]).unwrap(), Some(false));
+    }
+}
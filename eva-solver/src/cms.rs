@@ -0,0 +1,77 @@
+//! The CryptoMiniSat library, which takes XOR clauses natively.
+use super::backend::{Literal, Solver, Statistics};
+use cryptominisat::{Lbool, Lit};
+use std::time::Instant;
+
+pub struct CryptoMiniSat {
+    solver: cryptominisat::Solver,
+    stats: Statistics,
+}
+
+/// The solver numbers variables from 0.
+fn lit(&(var, positive): &Literal) -> Lit {
+    Lit::new(var - 1, !positive).unwrap()
+}
+
+impl CryptoMiniSat {
+    pub fn new(threads: u32, time_limit: Option<f64>) -> Self {
+        let mut solver = cryptominisat::Solver::new();
+        solver.set_num_threads(threads);
+        if let Some(seconds) = time_limit {
+            solver.set_max_time(seconds);
+        }
+        CryptoMiniSat {
+            solver,
+            stats: Statistics::default(),
+        }
+    }
+}
+
+impl Solver for CryptoMiniSat {
+    fn new_var(&mut self) -> u32 {
+        self.solver.new_var();
+        self.stats.variables += 1;
+        self.stats.variables
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        let lits: Vec<Lit> = lits.iter().map(lit).collect();
+        self.solver.add_clause(&lits);
+        self.stats.clauses += 1;
+    }
+
+    fn add_xor(&mut self, lits: &[Literal]) {
+        // The solver takes positive literals and the value of their XOR.
+        let rhs = lits
+            .iter()
+            .fold(true, |rhs, &(_, positive)| rhs ^ !positive);
+        let vars: Vec<Lit> = lits.iter().map(|&(var, _)| lit(&(var, true))).collect();
+        self.solver.add_xor_literal_clause(&vars, rhs);
+        self.stats.xor_clauses += 1;
+    }
+
+    fn solve(&mut self, assumptions: &[Literal]) -> Result<Option<bool>, String> {
+        let assumptions: Vec<Lit> = assumptions.iter().map(lit).collect();
+        let start = Instant::now();
+        let result = self.solver.solve_with_assumptions(&assumptions);
+        self.stats.time += start.elapsed();
+        self.stats.solves += 1;
+        Ok(match result {
+            Lbool::True => Some(true),
+            Lbool::False => Some(false),
+            Lbool::Undef => None,
+        })
+    }
+
+    fn model(&self) -> Vec<bool> {
+        self.solver
+            .get_model()
+            .iter()
+            .map(|&v| v == Lbool::True)
+            .collect()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.stats
+    }
+}
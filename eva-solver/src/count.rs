@@ -0,0 +1,106 @@
+//! Approximate model counting projected on some variables, with random XOR hashes as in
+//! ApproxMC (Chakraborty, Meel and Vardi, IJCAI 2016).
+use super::backend::{Literal, Solver};
+use rand::Rng;
+
+/// A number of models, exact when it is below the threshold of the counter.
+pub struct Estimate {
+    pub count: f64,
+    pub exact: bool,
+}
+
+/// Number of models which differ on `vars` under `assumptions`, up to `limit`.
+fn bounded_count(
+    solver: &mut dyn Solver,
+    vars: &[u32],
+    assumptions: &[Literal],
+    limit: usize,
+) -> Result<usize, String> {
+    // Blocking clauses hold while the selector is assumed, and are satisfied afterwards.
+    let selector = solver.new_var();
+    let mut assumptions = assumptions.to_vec();
+    assumptions.push((selector, true));
+    let mut count = 0;
+    while count < limit {
+        match solver.solve(&assumptions)? {
+            Some(true) => {}
+            Some(false) => break,
+            None => return Err("no answer within the time limit".to_string()),
+        }
+        let model = solver.model();
+        let mut blocking = vec![(selector, false)];
+        blocking.extend(vars.iter().map(|&v| (v, !model[v as usize - 1])));
+        solver.add_clause(&blocking);
+        count += 1;
+    }
+    solver.add_clause(&[(selector, false)]);
+    Ok(count)
+}
+
+/// Count the models which differ on `vars`, within a factor `1 + epsilon` of the exact count
+/// with probability at least `1 - delta`.
+pub fn approx_count<R: Rng>(
+    solver: &mut dyn Solver,
+    vars: &[u32],
+    epsilon: f64,
+    delta: f64,
+    rng: &mut R,
+) -> Result<Estimate, String> {
+    let threshold = 1.0 + 9.84 * (1.0 + epsilon / (1.0 + epsilon)) * (1.0 + 1.0 / epsilon).powi(2);
+    let threshold = threshold.ceil() as usize;
+    let count = bounded_count(solver, vars, &[], threshold)?;
+    if count < threshold {
+        return Ok(Estimate {
+            count: count as f64,
+            exact: true,
+        });
+    }
+
+    let iterations = (17.0 * (3.0 / delta).log2()).ceil() as usize;
+    let mut estimates = vec![];
+    for _ in 0..iterations {
+        // Row `i` of the hash holds when its switch is assumed false. Rows over a free switch
+        // constrain nothing, so they are left in the solver.
+        let rows: Vec<Literal> = vars
+            .iter()
+            .map(|_| {
+                let switch = solver.new_var();
+                let mut lits: Vec<Literal> = vars
+                    .iter()
+                    .filter(|_| rng.gen())
+                    .map(|&v| (v, true))
+                    .collect();
+                lits.push((switch, rng.gen()));
+                solver.add_xor(&lits);
+                (switch, false)
+            })
+            .collect();
+        // Cells shrink as rows are added: find the fewest rows leaving a cell under the
+        // threshold, by binary search.
+        let mut count = |m: usize| bounded_count(solver, vars, &rows[..m], threshold);
+        let (mut lo, mut hi) = (0, rows.len());
+        let mut cell = count(hi)?;
+        if cell >= threshold {
+            continue;
+        }
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            let c = count(mid)?;
+            if c >= threshold {
+                lo = mid;
+            } else {
+                hi = mid;
+                cell = c;
+            }
+        }
+        estimates.push(cell as f64 * 2f64.powi(hi as i32));
+    }
+    if estimates.is_empty() {
+        return Err("no hash split the models".to_string());
+    }
+    estimates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Ok(Estimate {
+        count: estimates[estimates.len() / 2],
+        exact: false,
+    })
+}
@@ -0,0 +1,168 @@
+//! Any solver program reading DIMACS and printing `s` and `v` lines, run once per solve.
+use super::backend::{Literal, Solver, Statistics};
+use eva_builder::generic::{xor_to_cnf, SolverOutput};
+use std::env;
+use std::fs::{self, File};
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct External {
+    command: Vec<String>,
+    time_limit: Option<Duration>,
+    native_xor: bool,
+    clauses: Vec<Vec<Literal>>,
+    xor_clauses: Vec<Vec<Literal>>,
+    model: Vec<bool>,
+    stats: Statistics,
+}
+
+fn line(lits: &[Literal]) -> String {
+    let mut line = String::new();
+    for &(var, positive) in lits.iter() {
+        if !positive {
+            line.push('-');
+        }
+        line.push_str(&var.to_string());
+        line.push(' ');
+    }
+    line.push('0');
+    line
+}
+
+impl External {
+    /// Run `command`, the program followed by its arguments, with the path of the instance
+    /// appended. The solver is stopped after `time_limit` seconds.
+    pub fn new(command: Vec<String>, time_limit: Option<f64>) -> Self {
+        External {
+            command,
+            time_limit: time_limit.map(Duration::from_secs_f64),
+            native_xor: false,
+            clauses: vec![],
+            xor_clauses: vec![],
+            model: vec![],
+            stats: Statistics::default(),
+        }
+    }
+
+    /// Write XOR clauses as `x` lines, for solvers which read them such as cryptominisat5,
+    /// rather than cutting them into CNF.
+    pub fn with_native_xor(mut self, native_xor: bool) -> Self {
+        self.native_xor = native_xor;
+        self
+    }
+
+    fn dimacs(&self, assumptions: &[Literal]) -> String {
+        let mut vars = self.stats.variables;
+        let mut lines: Vec<String> = self.clauses.iter().map(|lits| line(lits)).collect();
+        lines.extend(assumptions.iter().map(|&lit| line(&[lit])));
+        for lits in self.xor_clauses.iter() {
+            match self.native_xor {
+                true => lines.push(format!("x{}", line(lits))),
+                false => lines.extend(xor_to_cnf(lits, &mut vars).iter().map(|c| line(c))),
+            }
+        }
+        let mut dimacs = format!("p cnf {} {}\n", vars, lines.len());
+        for line in lines.iter() {
+            dimacs.push_str(line);
+            dimacs.push('\n');
+        }
+        dimacs
+    }
+
+    /// Run the solver on `path`, writing its output to `output`. `None` means it was stopped.
+    fn run(&self, path: &str, output: File) -> Result<Option<ExitStatus>, String> {
+        let program = &self.command[0];
+        let mut child = Command::new(program)
+            .args(&self.command[1..])
+            .arg(path)
+            .stdout(output)
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))?;
+        let limit = match self.time_limit {
+            Some(limit) => limit,
+            None => {
+                let status = child.wait().map_err(|e| format!("{}: {}", program, e))?;
+                return Ok(Some(status));
+            }
+        };
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return Ok(Some(status)),
+                Ok(None) if start.elapsed() > limit => {
+                    child.kill().and_then(|_| child.wait()).ok();
+                    return Ok(None);
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(format!("{}: {}", program, e)),
+            }
+        }
+    }
+}
+
+impl Solver for External {
+    fn new_var(&mut self) -> u32 {
+        self.stats.variables += 1;
+        self.stats.variables
+    }
+
+    fn add_clause(&mut self, lits: &[Literal]) {
+        self.clauses.push(lits.to_vec());
+        self.stats.clauses += 1;
+    }
+
+    fn add_xor(&mut self, lits: &[Literal]) {
+        self.xor_clauses.push(lits.to_vec());
+        self.stats.xor_clauses += 1;
+    }
+
+    fn solve(&mut self, assumptions: &[Literal]) -> Result<Option<bool>, String> {
+        let base = format!("eva-solver-{}-{}", process::id(), self.stats.solves);
+        let path = env::temp_dir().join(format!("{}.cnf", base));
+        let output = env::temp_dir().join(format!("{}.out", base));
+        let io_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        fs::write(&path, self.dimacs(assumptions)).map_err(io_error)?;
+        let file = File::create(&output).map_err(io_error)?;
+
+        let start = Instant::now();
+        let finished = self.run(path.to_str().unwrap(), file);
+        self.stats.time += start.elapsed();
+        self.stats.solves += 1;
+        fs::remove_file(&path).ok();
+        let text = fs::read_to_string(&output);
+        fs::remove_file(&output).ok();
+        let program = &self.command[0];
+        let status = match finished? {
+            Some(status) => status,
+            None => return Ok(None),
+        };
+        // Solvers exit with 10 and 20 for satisfiable and unsatisfiable instances.
+        if !matches!(status.code(), Some(0) | Some(10) | Some(20)) {
+            return Err(format!("{} failed with {}", program, status));
+        }
+        let text = text.map_err(|e| format!("{}: {}", output.display(), e))?;
+        if !text
+            .lines()
+            .any(|line| line.split_whitespace().next() == Some("s"))
+        {
+            return Err(format!("{} printed no s line", program));
+        }
+        let result: SolverOutput = text.parse().map_err(|e| format!("{}: {}", program, e))?;
+        if result.satisfiable == Some(true) {
+            self.model = (1..=self.stats.variables)
+                .map(|var| result.values.get(&var).cloned().unwrap_or(false))
+                .collect();
+        }
+        Ok(result.satisfiable)
+    }
+
+    fn model(&self) -> Vec<bool> {
+        self.model.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.stats
+    }
+}
@@ -1,27 +1,384 @@
+//! Recover the key of a cipher description from known plaintext/ciphertext and leakage.
+#[cfg(feature = "cryptominisat")]
 extern crate cryptominisat;
-use cryptominisat::{Lbool, Lit, Solver};
+extern crate rand;
 
-type Literal = (u32, bool);
+mod backend;
+mod cdcl;
+#[cfg(feature = "cryptominisat")]
+mod cms;
+mod count;
+mod external;
 
-fn clause(l: &[Literal]) -> Vec<Lit> {
-    let lit = |var: u32, neg: bool| Lit::new(var, neg).unwrap();
-    l.iter().map(|(var, val)| lit(*var, *val)).collect()
+use backend::{load, Literal, Solver};
+use cdcl::Cdcl;
+#[cfg(feature = "cryptominisat")]
+use cms::CryptoMiniSat;
+use count::approx_count;
+use eva_builder::compiler::{Compiled, Compiler};
+use eva_builder::constant::ConstantTable;
+use eva_builder::description::{Description, VarRef};
+use eva_builder::leakage::LeakageSpec;
+use eva_crypto::generic::expand_bits;
+use external::External;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: eva-solver --cipher DIR [options]
+
+DIR holds the description files const, key and enc, such as eva-builder/ciphers/aes.
+
+Options:
+    --plaintext HEX         known plaintext
+    --ciphertext HEX        known ciphertext
+    --leakage PATH          leakage specification, with collision and bits lines
+    --key-word REF          word to recover (default K1#0#0#)
+    --plaintext-word REF    word fixed to the plaintext (default A4#1#1#)
+    --ciphertext-word REF   word fixed to the ciphertext (default A4#10#5#)
+    --solver COMMAND        cryptominisat to use the library, cdcl for the solver written in
+                            Rust, or a DIMACS solver program with its arguments, run on the
+                            path of each instance. The default is cryptominisat when built
+                            with the cryptominisat feature, which is on by default
+    --solver-xor            give XOR clauses to the program as x lines, not as CNF
+    --threads N             number of threads of cryptominisat (default 1)
+    --time-limit SECONDS    give up on each solve after this time
+    --stats                 print the statistics of the solver
+    --cnf PATH              write the instance in DIMACS to PATH
+    --enumerate LIMIT       list up to LIMIT keys, then count them and print their entropy
+    --count                 estimate the number of keys and their entropy instead
+    --epsilon E             tolerance of the estimate, as a factor 1 + E (default 0.8)
+    --delta D               probability that the estimate is off (default 0.2)
+    --seed N                seed of the random hashes, drawn at random if absent";
+
+struct Options {
+    cipher: PathBuf,
+    plaintext: Option<String>,
+    ciphertext: Option<String>,
+    leakage: Option<PathBuf>,
+    key_word: VarRef,
+    plaintext_word: VarRef,
+    ciphertext_word: VarRef,
+    threads: u32,
+    time_limit: Option<f64>,
+    cnf: Option<PathBuf>,
+    enumerate: Option<usize>,
+    count: bool,
+    epsilon: f64,
+    delta: f64,
+    seed: Option<u64>,
+    solver: Option<String>,
+    solver_xor: bool,
+    stats: bool,
 }
 
-fn main() {
-    let mut solver = Solver::new();
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("invalid hex string {}", hex))?;
+    if digits.len() % 2 == 1 {
+        return Err(format!("odd number of hex digits in {}", hex));
+    }
+    Ok(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+}
+
+fn parse_word(text: &str) -> Result<VarRef, String> {
+    let var: VarRef = text
+        .parse()
+        .map_err(|_| format!("invalid word reference {}", text))?;
+    if var.round < 0 {
+        return Err(format!("{}: rounds are absolute", text));
+    }
+    Ok(var)
+}
 
-    solver.set_num_threads(4);
-    solver.new_vars(3);
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        cipher: PathBuf::new(),
+        plaintext: None,
+        ciphertext: None,
+        leakage: None,
+        key_word: parse_word("K1#0#0#")?,
+        plaintext_word: parse_word("A4#1#1#")?,
+        ciphertext_word: parse_word("A4#10#5#")?,
+        threads: 1,
+        time_limit: None,
+        cnf: None,
+        enumerate: None,
+        count: false,
+        epsilon: 0.8,
+        delta: 0.2,
+        seed: None,
+        solver: None,
+        solver_xor: false,
+        stats: false,
+    };
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(|s| s.as_str())
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+        match flag.as_str() {
+            "--cipher" => options.cipher = PathBuf::from(value()?),
+            "--plaintext" => options.plaintext = Some(value()?.to_string()),
+            "--ciphertext" => options.ciphertext = Some(value()?.to_string()),
+            "--leakage" => options.leakage = Some(PathBuf::from(value()?)),
+            "--key-word" => options.key_word = parse_word(value()?)?,
+            "--plaintext-word" => options.plaintext_word = parse_word(value()?)?,
+            "--ciphertext-word" => options.ciphertext_word = parse_word(value()?)?,
+            "--threads" => {
+                options.threads = value()?.parse().map_err(|_| "invalid thread count")?
+            }
+            "--time-limit" => {
+                let seconds: f64 = value()?.parse().map_err(|_| "invalid time limit")?;
+                // Also rules out NaN, infinity and times too long for a `Duration`.
+                if seconds <= 0.0 || Duration::try_from_secs_f64(seconds).is_err() {
+                    return Err("invalid time limit".to_string());
+                }
+                options.time_limit = Some(seconds);
+            }
+            "--cnf" => options.cnf = Some(PathBuf::from(value()?)),
+            "--enumerate" => {
+                let limit = value()?.parse().map_err(|_| "invalid key limit")?;
+                if limit == 0 {
+                    return Err("--enumerate needs a limit of at least 1".to_string());
+                }
+                options.enumerate = Some(limit);
+            }
+            "--count" => options.count = true,
+            "--epsilon" => options.epsilon = value()?.parse().map_err(|_| "invalid epsilon")?,
+            "--delta" => options.delta = value()?.parse().map_err(|_| "invalid delta")?,
+            "--seed" => options.seed = Some(value()?.parse().map_err(|_| "invalid seed")?),
+            "--solver" => {
+                let command = value()?;
+                if command.trim().is_empty() {
+                    return Err("--solver needs a command".to_string());
+                }
+                options.solver = Some(command.to_string());
+            }
+            "--solver-xor" => options.solver_xor = true,
+            "--stats" => options.stats = true,
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+    if options.cipher.as_os_str().is_empty() {
+        return Err("--cipher is required".to_string());
+    }
+    if options.count && options.enumerate.is_some() {
+        return Err("--count and --enumerate are exclusive".to_string());
+    }
+    let (epsilon, delta) = (options.epsilon, options.delta);
+    if !epsilon.is_finite() || epsilon <= 0.0 || delta.is_nan() || delta <= 0.0 || delta >= 1.0 {
+        return Err("epsilon must be positive and delta between 0 and 1".to_string());
+    }
+    Ok(options)
+}
 
-    solver.add_clause(&clause(&[(0, false)]));
-    solver.add_clause(&clause(&[(1, true)]));
-    solver.add_xor_literal_clause(&clause(&[(0, true), (1, false), (2, false)]), true);
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
 
-    let ret = solver.solve();
+/// Compile the key schedule and the encryption of the description directory `dir`.
+fn compile(dir: &Path) -> Result<Compiled, String> {
+    let table: ConstantTable = read(&dir.join("const"))?
+        .parse()
+        .map_err(|e| format!("{}: {}", dir.join("const").display(), e))?;
+    let mut compiler = Compiler::new(table.into_constants());
+    for file in ["key", "enc"].iter() {
+        let path = dir.join(file);
+        let desc: Description = read(&path)?
+            .parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        compiler
+            .compile(&desc)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(compiler.finish())
+}
 
-    assert!(ret == Lbool::True);
-    assert!(solver.get_model()[0] == Lbool::True);
-    assert!(solver.get_model()[1] == Lbool::False);
-    assert!(solver.get_model()[2] == Lbool::True);
+/// Hexadecimal digits of a word, most significant bit first, padded in front to whole digits.
+fn to_hex(bits: &[bool]) -> String {
+    let mut padded = vec![false; (4 - bits.len() % 4) % 4];
+    padded.extend_from_slice(bits);
+    padded
+        .chunks(4)
+        .map(|digit| {
+            let value = digit.iter().fold(0, |acc, &b| acc << 1 | b as u32);
+            std::char::from_digit(value, 16).unwrap()
+        })
+        .collect()
+}
+
+/// Fix a word, whose width must match the number of bits given.
+fn fix(compiled: &mut Compiled, var: &VarRef, hex: &str) -> Result<(), String> {
+    let bits = expand_bits(&parse_hex(hex)?, 0);
+    match compiled.width(&var.name, var.index) {
+        Some(width) if width as usize == bits.len() => {
+            compiled.fix(&var.name, var.round, var.index, &bits);
+            Ok(())
+        }
+        Some(width) => Err(format!(
+            "{} has {} bits, {} given",
+            var.name,
+            width,
+            bits.len()
+        )),
+        None => Err(format!("unknown word {} {}", var.name, var.index)),
+    }
+}
+
+/// The solver chosen by the options.
+fn solver(options: &Options) -> Result<Box<dyn Solver>, String> {
+    let cdcl = || Box::new(Cdcl::new(options.time_limit));
+    #[cfg(feature = "cryptominisat")]
+    let cryptominisat = || Box::new(CryptoMiniSat::new(options.threads, options.time_limit));
+    Ok(match options.solver.as_deref() {
+        #[cfg(feature = "cryptominisat")]
+        None | Some("cryptominisat") => cryptominisat(),
+        #[cfg(not(feature = "cryptominisat"))]
+        None => cdcl(),
+        #[cfg(not(feature = "cryptominisat"))]
+        Some("cryptominisat") => return Err("built without cryptominisat".to_string()),
+        Some("cdcl") => cdcl(),
+        Some(command) => {
+            let command = command.split_whitespace().map(String::from).collect();
+            let external = External::new(command, options.time_limit);
+            Box::new(external.with_native_xor(options.solver_xor))
+        }
+    })
+}
+
+/// Up to `limit` models which differ on the variables `vars`, blocking the values of `vars` in
+/// each model found.
+fn enumerate(
+    solver: &mut dyn Solver,
+    vars: &[u32],
+    limit: usize,
+) -> Result<Vec<Vec<bool>>, String> {
+    let mut models = vec![];
+    while models.len() < limit {
+        match solver.solve(&[])? {
+            Some(true) => {}
+            Some(false) => break,
+            None => return Err("no answer within the time limit".to_string()),
+        }
+        let model = solver.model();
+        let blocking: Vec<Literal> = vars
+            .iter()
+            .map(|&var| (var, !model[var as usize - 1]))
+            .collect();
+        solver.add_clause(&blocking);
+        models.push(model);
+    }
+    Ok(models)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut compiled = compile(&options.cipher)?;
+    if let Some(plaintext) = &options.plaintext {
+        fix(&mut compiled, &options.plaintext_word, plaintext)?;
+    }
+    if let Some(ciphertext) = &options.ciphertext {
+        fix(&mut compiled, &options.ciphertext_word, ciphertext)?;
+    }
+    if let Some(path) = &options.leakage {
+        let spec: LeakageSpec = read(path)?
+            .parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        compiled
+            .observe(&spec)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    if let Some(path) = &options.cnf {
+        let dimacs = compiled.instance.to_dimacs().map_err(|e| e.to_string())?;
+        fs::write(path, dimacs).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    let var = &options.key_word;
+    let mut key_vars = compiled
+        .variables(&var.name, var.round, var.index)
+        .ok_or_else(|| format!("unknown word {} {}", var.name, var.index))?;
+    key_vars.sort_unstable();
+    key_vars.dedup();
+    let mut solver = solver(&options)?;
+    load(solver.as_mut(), &compiled.instance).map_err(|e| e.to_string())?;
+    let result = match options.count {
+        true => count(solver.as_mut(), &key_vars, &options),
+        false => recover(solver.as_mut(), &compiled, &key_vars, &options),
+    };
+    if options.stats {
+        eprintln!("{}", solver.statistics());
+    }
+    result
+}
+
+/// Print the estimated number of keys and their entropy.
+fn count(solver: &mut dyn Solver, key_vars: &[u32], options: &Options) -> Result<(), String> {
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (epsilon, delta) = (options.epsilon, options.delta);
+    let estimate = approx_count(solver, key_vars, epsilon, delta, &mut rng)?;
+    if estimate.exact {
+        println!("Keys: {}", estimate.count);
+        println!("Residue Entropy: {}", estimate.count.log2());
+    } else {
+        println!("Keys: about {}", estimate.count);
+        println!(
+            "Residue Entropy: {} (epsilon {}, delta {}, seed {})",
+            estimate.count.log2(),
+            epsilon,
+            delta,
+            seed
+        );
+    }
+    Ok(())
+}
+
+/// Print a key, or up to the limit of `--enumerate` keys followed by their number.
+fn recover(
+    solver: &mut dyn Solver,
+    compiled: &Compiled,
+    key_vars: &[u32],
+    options: &Options,
+) -> Result<(), String> {
+    let limit = options.enumerate.unwrap_or(1);
+    let models = enumerate(solver, key_vars, limit)?;
+    if models.is_empty() {
+        return Err("no key matches".to_string());
+    }
+    let var = &options.key_word;
+    for model in models.iter() {
+        let key = compiled
+            .read(model, &var.name, var.round, var.index)
+            .unwrap();
+        println!("{}", to_hex(&key));
+    }
+    if options.enumerate.is_some() {
+        if models.len() == limit {
+            println!("Keys: at least {}", models.len());
+        } else {
+            println!("Keys: {}", models.len());
+            println!("Residue Entropy: {}", (models.len() as f64).log2());
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    if let Err(e) = parse_args(&args).and_then(run) {
+        eprintln!("eva-solver: {}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,285 @@
+use eva_builder::generic::Instance;
+use eva_crypto::aes::AES;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn eva_solver(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_eva-solver"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A cipher with a 12-bit key and no encryption.
+fn toy_cipher(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("const"), "").unwrap();
+    fs::write(dir.join("key"), "1,rx,K1#0#0#1#12#,H8#-1#1#1#12#,\n").unwrap();
+    fs::write(dir.join("enc"), "").unwrap();
+    dir
+}
+
+#[cfg(test)]
+#[test]
+fn recover_leaked_key() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let ciphertext = AES::new(&key).encrypt(&plaintext);
+    let bits: String = key.iter().map(|b| format!("{:08b}", b)).collect();
+    let leakage = env::temp_dir().join("eva-solver-key.leak");
+    fs::write(
+        &leakage,
+        format!("# the whole key\nbits,K1#0#0#,{},\n", bits),
+    )
+    .unwrap();
+    let cnf = env::temp_dir().join("eva-solver-aes.cnf");
+
+    let output = eva_solver(&[
+        "--cipher",
+        "../eva-builder/ciphers/aes",
+        "--plaintext",
+        &hex(&plaintext),
+        "--ciphertext",
+        &hex(&ciphertext),
+        "--leakage",
+        leakage.to_str().unwrap(),
+        "--cnf",
+        cnf.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), hex(&key));
+    let instance: Instance = fs::read_to_string(&cnf).unwrap().parse().ok().unwrap();
+    assert!(instance.variables.contains_key("K1#0#0#1#"));
+}
+
+#[test]
+fn reject_bad_inputs() {
+    let output = eva_solver(&[
+        "--cipher",
+        "../eva-builder/ciphers/aes",
+        "--plaintext",
+        "0011",
+        "--ciphertext",
+        "0011",
+    ]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap().trim(),
+        "eva-solver: A4 has 128 bits, 16 given"
+    );
+    let output = eva_solver(&["--cipher", "x", "--threads"]);
+    assert!(!output.status.success());
+    let output = eva_solver(&["--cipher", "x", "--enumerate", "0"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap().trim(),
+        "eva-solver: --enumerate needs a limit of at least 1"
+    );
+    for limit in ["-1", "0", "NaN", "inf", "1e300"].iter() {
+        let output = eva_solver(&["--cipher", "x", "--time-limit", limit]);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap().trim(),
+            "eva-solver: invalid time limit"
+        );
+    }
+    for command in ["", " \t"].iter() {
+        let output = eva_solver(&["--cipher", "x", "--solver", command]);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap().trim(),
+            "eva-solver: --solver needs a command"
+        );
+    }
+    for option in ["--epsilon", "--delta"].iter() {
+        let output = eva_solver(&["--cipher", "x", option, "NaN"]);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap().trim(),
+            "eva-solver: epsilon must be positive and delta between 0 and 1"
+        );
+    }
+}
+
+#[test]
+fn enumerate_keys() {
+    let key: Vec<u8> = (0..16).collect();
+    let plaintext: Vec<u8> = (0..16).map(|x| x * 0x11).collect();
+    let ciphertext = AES::new(&key).encrypt(&plaintext);
+    let bits: String = key.iter().map(|b| format!("{:08b}", b)).collect();
+    let leakage = env::temp_dir().join("eva-solver-partial-key.leak");
+    fs::write(&leakage, format!("bits,K1#0#0#3#128#,{},\n", &bits[2..])).unwrap();
+    let (plaintext, ciphertext) = (hex(&plaintext), hex(&ciphertext));
+    for solver in [&[][..], &["--solver", "cdcl"][..]].iter() {
+        let mut args = vec![
+            "--cipher",
+            "../eva-builder/ciphers/aes",
+            "--leakage",
+            leakage.to_str().unwrap(),
+            "--enumerate",
+            "10",
+        ];
+        args.extend(solver.iter());
+
+        let output = eva_solver(&args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.split_off(4), ["Keys: 4", "Residue Entropy: 2"]);
+        lines.sort();
+        assert_eq!(lines[0], hex(&key));
+        assert_eq!(lines[3], format!("c{}", &hex(&key)[1..]));
+
+        args.extend(&["--plaintext", &plaintext, "--ciphertext", &ciphertext]);
+        let output = eva_solver(&args);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            stdout,
+            format!("{}\nKeys: 1\nResidue Entropy: 0\n", hex(&key))
+        );
+    }
+}
+
+#[test]
+fn count_keys() {
+    let dir = toy_cipher("eva-solver-toy");
+    let leakage = dir.join("leak");
+    let count = |leaked: &str, solver: &[&str]| {
+        fs::write(
+            &leakage,
+            format!("bits,K1#0#0#1#{}#,{},\n", leaked.len(), leaked),
+        )
+        .unwrap();
+        let mut args = vec![
+            "--cipher",
+            dir.to_str().unwrap(),
+            "--leakage",
+            leakage.to_str().unwrap(),
+            "--count",
+            "--epsilon",
+            "2",
+            "--delta",
+            "0.5",
+            "--seed",
+            "1",
+        ];
+        args.extend(solver.iter());
+        let output = eva_solver(&args);
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    for solver in [&[][..], &["--solver", "cdcl"][..]].iter() {
+        assert_eq!(count("0110110011", solver), "Keys: 4\nResidue Entropy: 2\n");
+        let stdout = count("0110", solver);
+        let keys: f64 = stdout
+            .lines()
+            .next()
+            .unwrap()
+            .strip_prefix("Keys: about ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((256.0 / 3.0..=256.0 * 3.0).contains(&keys));
+    }
+}
+
+#[test]
+fn external_solver() {
+    let dir = toy_cipher("eva-solver-external");
+    fs::write(dir.join("leak"), "bits,K1#0#0#,101100111000,\n").unwrap();
+    // Every clause is a unit clause, so their literals make up the model.
+    let script = dir.join("units.sh");
+    fs::write(
+        &script,
+        "#!/bin/sh\necho 's SATISFIABLE'\nawk '$1 != \"p\" { printf \"v %s 0\\n\", $1 }' \"$1\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = eva_solver(&[
+        "--cipher",
+        dir.to_str().unwrap(),
+        "--leakage",
+        dir.join("leak").to_str().unwrap(),
+        "--solver",
+        script.to_str().unwrap(),
+        "--stats",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b38\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Variables: 12\nClauses: 13\nXOR clauses: 0\nSolves: 1\n"));
+
+    // A crash and a missing status line are errors, not running out of time.
+    for (name, body, error) in [
+        ("crash.sh", "exit 3", "failed with exit status: 3"),
+        ("silent.sh", "echo 'c nothing'", "printed no s line"),
+    ]
+    .iter()
+    {
+        let script = dir.join(name);
+        fs::write(&script, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let output = eva_solver(&[
+            "--cipher",
+            dir.to_str().unwrap(),
+            "--solver",
+            script.to_str().unwrap(),
+        ]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.trim().ends_with(error), "{}", stderr);
+    }
+}
+
+#[test]
+fn cdcl_solver() {
+    // AES reduced to 3 rounds, with the last 8 bits of the key unknown.
+    let aes = PathBuf::from("../eva-builder/ciphers/aes");
+    let dir = env::temp_dir().join("eva-solver-aes3");
+    fs::create_dir_all(&dir).unwrap();
+    fs::copy(aes.join("const"), dir.join("const")).unwrap();
+    fs::copy(aes.join("key"), dir.join("key")).unwrap();
+    let enc: String = fs::read_to_string(aes.join("enc"))
+        .unwrap()
+        .lines()
+        .map(|line| match line {
+            "CP,8" => "CP,1\n".to_string(),
+            _ if line.starts_with("10,") => format!("3,{}\n", &line[3..]),
+            _ => format!("{}\n", line),
+        })
+        .collect();
+    fs::write(dir.join("enc"), enc).unwrap();
+    let key: Vec<u8> = (0..16).collect();
+    let bits: String = key.iter().map(|b| format!("{:08b}", b)).collect();
+    let leakage = dir.join("leak");
+    fs::write(&leakage, format!("bits,K1#0#0#1#120#,{},\n", &bits[..120])).unwrap();
+
+    let output = eva_solver(&[
+        "--cipher",
+        dir.to_str().unwrap(),
+        "--plaintext",
+        "00112233445566778899aabbccddeeff",
+        "--ciphertext",
+        "8d2656262eb632cc3b3ec75fc430b16c",
+        "--ciphertext-word",
+        "A4#3#5#",
+        "--leakage",
+        leakage.to_str().unwrap(),
+        "--enumerate",
+        "2",
+        "--solver",
+        "cdcl",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\nKeys: 1\nResidue Entropy: 0\n", hex(&key))
+    );
+}